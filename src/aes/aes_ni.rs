@@ -0,0 +1,275 @@
+/// Resources used:
+/// https://en.wikipedia.org/wiki/AES_instruction_set
+/// https://doc.rust-lang.org/core/arch/x86_64/index.html
+
+use aes::{Aes, Key, Nb};
+use mode::BlockCipher;
+
+/// AES running on the AES-NI instructions of x86_64 processors when they have them, and on the
+/// software `Aes` otherwise. The choice is made once, on construction.
+pub struct AesNi {
+    implementation: Implementation,
+}
+
+enum Implementation {
+    #[cfg(target_arch = "x86_64")]
+    Hardware(x86_64::RoundKeys),
+    Software(Aes),
+}
+
+impl AesNi {
+    pub fn new(key: &Key) -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("aes") && is_x86_feature_detected!("sse2") {
+                // the features were just detected
+                let round_keys = unsafe { x86_64::RoundKeys::new(key) };
+
+                return AesNi { implementation: Implementation::Hardware(round_keys) };
+            }
+        }
+
+        AesNi::software(key)
+    }
+
+    /// Software fallback, whatever the processor.
+    pub fn software(key: &Key) -> Self {
+        AesNi { implementation: Implementation::Software(Aes::new(key)) }
+    }
+
+    pub fn is_hardware_accelerated(&self) -> bool {
+        match self.implementation {
+            #[cfg(target_arch = "x86_64")]
+            Implementation::Hardware(_) => true,
+            Implementation::Software(_) => false,
+        }
+    }
+}
+
+impl BlockCipher for AesNi {
+    fn block_size(&self) -> usize {
+        4 * Nb
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        assert_eq!(block.len(), 4 * Nb);
+
+        self.encrypt_blocks(block);
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        assert_eq!(block.len(), 4 * Nb);
+
+        self.decrypt_blocks(block);
+    }
+
+    fn encrypt_blocks(&self, blocks: &mut [u8]) {
+        assert_eq!(blocks.len() % (4 * Nb), 0);
+
+        match self.implementation {
+            // round keys only exist when the features were detected
+            #[cfg(target_arch = "x86_64")]
+            Implementation::Hardware(ref round_keys) => unsafe { round_keys.encrypt(blocks) },
+            Implementation::Software(ref aes) => aes.encrypt_blocks(blocks),
+        }
+    }
+
+    fn decrypt_blocks(&self, blocks: &mut [u8]) {
+        assert_eq!(blocks.len() % (4 * Nb), 0);
+
+        match self.implementation {
+            #[cfg(target_arch = "x86_64")]
+            Implementation::Hardware(ref round_keys) => unsafe { round_keys.decrypt(blocks) },
+            Implementation::Software(ref aes) => aes.decrypt_blocks(blocks),
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86_64 {
+    use std::arch::x86_64::*;
+    use aes::{Key, Nb, Rcon};
+
+    pub struct RoundKeys {
+        encryption: Vec<__m128i>,
+        /// Round keys of the Equivalent Inverse Cipher, from round 0 to Nr.
+        decryption: Vec<__m128i>,
+    }
+
+    impl RoundKeys {
+        /// Key Expansion of Sec. 5.2, whose SubWord goes through AESKEYGENASSIST, then
+        /// InvMixColumns of the inner round keys through AESIMC.
+        #[target_feature(enable = "aes,sse2")]
+        pub unsafe fn new(key: &Key) -> Self {
+            let key_size = key.size();
+            let nk = key_size.nk();
+            let nr = key_size.nr();
+            let mut w: Vec<[u8; 4]> = key.as_bytes()
+                .chunks(4)
+                .map(|word| [word[0], word[1], word[2], word[3]])
+                .collect();
+
+            for i in nk..Nb * (nr + 1) {
+                let mut temp = w[i - 1];
+                if i % nk == 0 {
+                    temp = rot_sub_word(temp);
+                    temp[0] ^= Rcon[i / nk - 1][0];
+                } else if nk > 6 && i % nk == 4 {
+                    temp = sub_word(temp);
+                }
+
+                let previous = w[i - nk];
+                w.push([
+                    previous[0] ^ temp[0],
+                    previous[1] ^ temp[1],
+                    previous[2] ^ temp[2],
+                    previous[3] ^ temp[3],
+                ]);
+            }
+
+            let mut encryption = Vec::with_capacity(nr + 1);
+            for round_key in w.chunks(Nb) {
+                encryption.push(load(&round_key.concat()));
+            }
+            let mut decryption = encryption.clone();
+            for round in 1..nr {
+                decryption[round] = _mm_aesimc_si128(encryption[round]);
+            }
+
+            RoundKeys { encryption, decryption }
+        }
+
+        #[target_feature(enable = "aes,sse2")]
+        pub unsafe fn encrypt(&self, blocks: &mut [u8]) {
+            let rk = &self.encryption;
+            let nr = rk.len() - 1;
+
+            for block in blocks.chunks_mut(4 * Nb) {
+                let mut state = _mm_xor_si128(load(block), rk[0]);
+                for round_key in rk[1..nr].iter() {
+                    state = _mm_aesenc_si128(state, *round_key);
+                }
+                state = _mm_aesenclast_si128(state, rk[nr]);
+
+                _mm_storeu_si128(block.as_mut_ptr() as *mut __m128i, state);
+            }
+        }
+
+        #[target_feature(enable = "aes,sse2")]
+        pub unsafe fn decrypt(&self, blocks: &mut [u8]) {
+            let dk = &self.decryption;
+            let nr = dk.len() - 1;
+
+            for block in blocks.chunks_mut(4 * Nb) {
+                let mut state = _mm_xor_si128(load(block), dk[nr]);
+                for round_key in dk[1..nr].iter().rev() {
+                    state = _mm_aesdec_si128(state, *round_key);
+                }
+                state = _mm_aesdeclast_si128(state, dk[0]);
+
+                _mm_storeu_si128(block.as_mut_ptr() as *mut __m128i, state);
+            }
+        }
+    }
+
+    #[target_feature(enable = "sse2")]
+    unsafe fn load(bytes: &[u8]) -> __m128i {
+        assert_eq!(bytes.len(), 16);
+
+        _mm_loadu_si128(bytes.as_ptr() as *const __m128i)
+    }
+
+    /// AESKEYGENASSIST gives SubWord of the last word of its input in its third word, and
+    /// RotWord(SubWord()) of it (xored with a round constant, here 0) in its last word.
+    #[target_feature(enable = "aes,sse2")]
+    unsafe fn key_generation_assist(word: [u8; 4]) -> [u8; 16] {
+        let mut input = [0u8; 16];
+        input[12..].copy_from_slice(&word);
+
+        let mut output = [0u8; 16];
+        let assisted = _mm_aeskeygenassist_si128(load(&input), 0);
+        _mm_storeu_si128(output.as_mut_ptr() as *mut __m128i, assisted);
+
+        output
+    }
+
+    #[target_feature(enable = "aes,sse2")]
+    unsafe fn sub_word(word: [u8; 4]) -> [u8; 4] {
+        let output = key_generation_assist(word);
+
+        [output[8], output[9], output[10], output[11]]
+    }
+
+    #[target_feature(enable = "aes,sse2")]
+    unsafe fn rot_sub_word(word: [u8; 4]) -> [u8; 4] {
+        let output = key_generation_assist(word);
+
+        [output[12], output[13], output[14], output[15]]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::generate::generate_bytes_for_length;
+    use hex::hex_string_to_bytes;
+
+    /// The accelerated cipher when the processor has AES-NI, and the software fallback in any case.
+    fn both_implementations(key: &Key) -> Vec<AesNi> {
+        vec![AesNi::new(key), AesNi::software(key)]
+    }
+
+    #[test]
+    fn new_uses_aes_ni_when_detected() {
+        let aes_ni = AesNi::new(&Key::new(&[0; 16]));
+
+        #[cfg(target_arch = "x86_64")]
+        assert_eq!(aes_ni.is_hardware_accelerated(), is_x86_feature_detected!("aes"));
+        assert!(!AesNi::software(&Key::new(&[0; 16])).is_hardware_accelerated());
+    }
+
+    #[test]
+    fn encrypt_block_fips_197_appendix_c_test_cases() {
+        let raw = &(0..16).map(|i| i * 0x11).collect::<Vec<u8>>();
+        let test_cases = vec![
+            (16, "69c4e0d86a7b0430d8cdb78070b4c55a"),
+            (24, "dda97ca4864cdfe06eaf70a0ec0d7191"),
+            (32, "8ea2b7ca516745bfeafc49904b496089"),
+        ];
+
+        for (key_length, expected_cipher) in test_cases.iter() {
+            let key = Key::new(&(0..*key_length).collect::<Vec<u8>>());
+
+            for block_cipher in both_implementations(&key).iter() {
+                let block = &mut raw.clone();
+
+                block_cipher.encrypt_block(block);
+                assert_eq!(block, &hex_string_to_bytes(expected_cipher));
+
+                block_cipher.decrypt_block(block);
+                assert_eq!(block, raw);
+            }
+        }
+    }
+
+    #[test]
+    fn encrypt_blocks_matches_the_state_path() {
+        for key_length in [16, 24, 32].iter() {
+            let key = Key::new(&generate_bytes_for_length(*key_length));
+            let reference = Aes::new(&key);
+            let blocks = generate_bytes_for_length(16 * 10);
+            let mut expected = blocks.clone();
+            reference.encrypt_blocks(&mut expected);
+
+            for block_cipher in both_implementations(&key).iter() {
+                let mut actual = blocks.clone();
+
+                block_cipher.encrypt_blocks(&mut actual);
+                assert_eq!(actual, expected);
+
+                block_cipher.decrypt_blocks(&mut actual);
+                assert_eq!(actual, blocks);
+            }
+        }
+    }
+}
@@ -0,0 +1,417 @@
+/// Resources used:
+/// https://csrc.nist.gov/csrc/media/publications/fips/197/final/documents/fips-197.pdf
+/// https://www.bearssl.org/constanttime.html
+/// https://eprint.iacr.org/2009/129.pdf
+
+use aes::{Key, Nb, Rcon};
+use mode::BlockCipher;
+
+/// Number of blocks processed at once: the 128 bits of each plane hold one bit of 16 bytes for 8
+/// blocks.
+const BLOCKS_PER_BATCH: usize = 8;
+
+/// Bitsliced State of up to 8 blocks: plane b holds bit b of every byte, the byte r + 4c of block
+/// k being at position 16k + r + 4c.
+type Planes = [u128; 8];
+
+/// One bit set at the position of the first byte of every block.
+const LANES: u128 = 0x0001_0001_0001_0001_0001_0001_0001_0001;
+
+/// Table-free AES: the State of 8 blocks is split into bit planes, and every transformation is a
+/// fixed sequence of and, xor and shift operations over the planes. SubBytes computes the inverse
+/// in GF(2^8) as x^254 with bitsliced multiplications, followed by the affine transformation. No
+/// memory access or branch depends on the key or the data, including in the Key Expansion.
+pub struct BitslicedAes {
+    /// Round keys, each one repeated in every block of the planes.
+    round_keys: Vec<Planes>,
+}
+
+impl BitslicedAes {
+    pub fn new(key: &Key) -> Self {
+        let round_keys = key_expansion(key)
+            .chunks(4 * Nb)
+            .map(|round_key| bitslice(&round_key.repeat(BLOCKS_PER_BATCH)))
+            .collect();
+
+        BitslicedAes { round_keys }
+    }
+
+    fn rounds(&self) -> usize {
+        self.round_keys.len() - 1
+    }
+
+    fn encrypt_planes(&self, state: &mut Planes) {
+        let nr = self.rounds();
+
+        add_round_key(state, &self.round_keys[0]);
+
+        for round in 1..nr {
+            sub_bytes(state);
+            shift_rows(state);
+            mix_columns(state);
+            add_round_key(state, &self.round_keys[round]);
+        }
+
+        sub_bytes(state);
+        shift_rows(state);
+        add_round_key(state, &self.round_keys[nr]);
+    }
+
+    /// Inverse Cipher of Sec. 5.3, with the Round Keys of the Cipher.
+    fn decrypt_planes(&self, state: &mut Planes) {
+        let nr = self.rounds();
+
+        add_round_key(state, &self.round_keys[nr]);
+
+        for round in (1..nr).rev() {
+            inv_shift_rows(state);
+            inv_sub_bytes(state);
+            add_round_key(state, &self.round_keys[round]);
+            inv_mix_columns(state);
+        }
+
+        inv_shift_rows(state);
+        inv_sub_bytes(state);
+        add_round_key(state, &self.round_keys[0]);
+    }
+}
+
+impl BlockCipher for BitslicedAes {
+    fn block_size(&self) -> usize {
+        4 * Nb
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        assert_eq!(block.len(), 4 * Nb);
+
+        self.encrypt_blocks(block);
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        assert_eq!(block.len(), 4 * Nb);
+
+        self.decrypt_blocks(block);
+    }
+
+    fn encrypt_blocks(&self, blocks: &mut [u8]) {
+        assert_eq!(blocks.len() % (4 * Nb), 0);
+
+        for batch in blocks.chunks_mut(BLOCKS_PER_BATCH * 4 * Nb) {
+            let mut state = bitslice(batch);
+            self.encrypt_planes(&mut state);
+            batch.copy_from_slice(&unbitslice(&state)[..batch.len()]);
+        }
+    }
+
+    fn decrypt_blocks(&self, blocks: &mut [u8]) {
+        assert_eq!(blocks.len() % (4 * Nb), 0);
+
+        for batch in blocks.chunks_mut(BLOCKS_PER_BATCH * 4 * Nb) {
+            let mut state = bitslice(batch);
+            self.decrypt_planes(&mut state);
+            batch.copy_from_slice(&unbitslice(&state)[..batch.len()]);
+        }
+    }
+}
+
+/// Splits up to 128 bytes into bit planes, the missing bytes being zeros.
+fn bitslice(bytes: &[u8]) -> Planes {
+    assert!(bytes.len() <= 128);
+    let mut planes = [0u128; 8];
+
+    for (position, byte) in bytes.iter().enumerate() {
+        for (bit, plane) in planes.iter_mut().enumerate() {
+            *plane |= (((*byte >> bit) & 1) as u128) << position;
+        }
+    }
+
+    planes
+}
+
+fn unbitslice(planes: &Planes) -> Vec<u8> {
+    (0..128)
+        .map(|position| {
+            planes.iter()
+                .enumerate()
+                .fold(0u8, |byte, (bit, plane)| byte | (((plane >> position) & 1) as u8) << bit)
+        })
+        .collect()
+}
+
+fn add_round_key(state: &mut Planes, round_key: &Planes) {
+    for (plane, key_plane) in state.iter_mut().zip(round_key.iter()) {
+        *plane ^= key_plane;
+    }
+}
+
+/// S-box of every byte: its inverse in GF(2^8) (0 being its own inverse), then the affine
+/// transformation b_i ^ b_(i+4) ^ b_(i+5) ^ b_(i+6) ^ b_(i+7) ^ c_i, with c = 0x63 (Sec. 5.1.1).
+fn sub_bytes(state: &mut Planes) {
+    let inverse = invert(state);
+
+    for i in 0..8 {
+        state[i] = inverse[i] ^ inverse[(i + 4) % 8] ^ inverse[(i + 5) % 8]
+            ^ inverse[(i + 6) % 8] ^ inverse[(i + 7) % 8] ^ constant_plane(0x63, i);
+    }
+}
+
+/// Inverse affine transformation b_(i+2) ^ b_(i+5) ^ b_(i+7) ^ d_i, with d = 0x05, then the inverse
+/// in GF(2^8).
+fn inv_sub_bytes(state: &mut Planes) {
+    let mut affine = [0u128; 8];
+    for i in 0..8 {
+        affine[i] = state[(i + 2) % 8] ^ state[(i + 5) % 8] ^ state[(i + 7) % 8]
+            ^ constant_plane(0x05, i);
+    }
+
+    *state = invert(&affine);
+}
+
+/// Plane of bit i of a constant byte, in every byte of every block.
+fn constant_plane(constant: u8, i: usize) -> u128 {
+    ((constant as u128 >> i) & 1).wrapping_neg()
+}
+
+/// x^254 = x^-1 in GF(2^8), by the addition chain 2, 3, 6, 12, 15, 30, 60, 120, 240, 252, 254.
+fn invert(x: &Planes) -> Planes {
+    let x2 = multiply(x, x);
+    let x3 = multiply(&x2, x);
+    let x6 = multiply(&x3, &x3);
+    let x12 = multiply(&x6, &x6);
+    let x15 = multiply(&x12, &x3);
+    let x30 = multiply(&x15, &x15);
+    let x60 = multiply(&x30, &x30);
+    let x120 = multiply(&x60, &x60);
+    let x240 = multiply(&x120, &x120);
+    let x252 = multiply(&x240, &x12);
+
+    multiply(&x252, &x2)
+}
+
+/// Product of every pair of bytes in GF(2^8): the schoolbook product of the polynomials, reduced
+/// by x^8 + x^4 + x^3 + x + 1.
+fn multiply(a: &Planes, b: &Planes) -> Planes {
+    let mut product = [0u128; 15];
+    for i in 0..8 {
+        for j in 0..8 {
+            product[i + j] ^= a[i] & b[j];
+        }
+    }
+
+    // x^k = x^(k-4) + x^(k-5) + x^(k-7) + x^(k-8), from the highest degree down
+    for k in (8..15).rev() {
+        product[k - 4] ^= product[k];
+        product[k - 5] ^= product[k];
+        product[k - 7] ^= product[k];
+        product[k - 8] ^= product[k];
+    }
+
+    let mut reduced = [0u128; 8];
+    reduced.copy_from_slice(&product[..8]);
+
+    reduced
+}
+
+/// Multiplication by x of every byte.
+fn xtime(state: &Planes) -> Planes {
+    let high_bit = state[7];
+
+    [
+        high_bit,
+        state[0] ^ high_bit,
+        state[1],
+        state[2] ^ high_bit,
+        state[3] ^ high_bit,
+        state[4],
+        state[5],
+        state[6],
+    ]
+}
+
+/// Moves the bits selected by the mask by the given number of positions.
+fn move_bits(plane: u128, mask: u128, shift: isize) -> u128 {
+    if shift >= 0 {
+        (plane & mask) << shift
+    } else {
+        (plane & mask) >> -shift
+    }
+}
+
+/// Row r of the State is cyclically shifted by r bytes to the left (or the right, when inverted),
+/// i.e. the byte in column c comes from column c + r.
+fn shift_rows_by(state: &mut Planes, direction: isize) {
+    for plane in state.iter_mut() {
+        let mut shifted = 0;
+        for r in 0..4 {
+            for c in 0..4 {
+                let destination = (c as isize - direction * r as isize).rem_euclid(4);
+                let mask = LANES << (r + 4 * c);
+                shifted |= move_bits(*plane, mask, 4 * (destination - c as isize));
+            }
+        }
+        *plane = shifted;
+    }
+}
+
+fn shift_rows(state: &mut Planes) {
+    shift_rows_by(state, 1);
+}
+
+fn inv_shift_rows(state: &mut Planes) {
+    shift_rows_by(state, -1);
+}
+
+/// State whose row r is the row r + k of every column.
+fn rotate_rows(state: &Planes, k: usize) -> Planes {
+    let mut rotated = [0u128; 8];
+
+    for (rotated_plane, plane) in rotated.iter_mut().zip(state.iter()) {
+        for r in 0..4 {
+            let source = (r + k) % 4;
+            let mask = (0..4).fold(0, |mask, c| mask | LANES << (source + 4 * c));
+            *rotated_plane |= move_bits(*plane, mask, r as isize - source as isize);
+        }
+    }
+
+    rotated
+}
+
+fn xor_planes(a: &Planes, b: &Planes) -> Planes {
+    let mut xored = *a;
+    add_round_key(&mut xored, b);
+
+    xored
+}
+
+/// s'_r = {02}s_r ^ {03}s_(r+1) ^ s_(r+2) ^ s_(r+3) = xtime(s_r ^ s_(r+1)) ^ s_(r+1) ^ s_(r+2) ^
+/// s_(r+3), for every column.
+fn mix_columns(state: &mut Planes) {
+    let s1 = rotate_rows(state, 1);
+    let s2 = rotate_rows(state, 2);
+    let s3 = rotate_rows(state, 3);
+
+    let doubled = xtime(&xor_planes(state, &s1));
+    *state = xor_planes(&xor_planes(&doubled, &s1), &xor_planes(&s2, &s3));
+}
+
+/// InvMixColumns is MixColumns after adding {04}(s_r ^ s_(r+2)) to every s_r, since
+/// {0e}, {0b}, {0d}, {09} = {02}, {03}, {01}, {01} * {05}, {00}, {04}, {00}.
+fn inv_mix_columns(state: &mut Planes) {
+    let s2 = rotate_rows(state, 2);
+    let quadrupled = xtime(&xtime(&xor_planes(state, &s2)));
+
+    *state = xor_planes(state, &quadrupled);
+    mix_columns(state);
+}
+
+/// Key Expansion of Sec. 5.2, whose SubWord goes through the bitsliced S-box.
+fn key_expansion(key: &Key) -> Vec<u8> {
+    let key_size = key.size();
+    let nk = key_size.nk();
+    let nr = key_size.nr();
+    let mut w: Vec<[u8; 4]> = key.as_bytes()
+        .chunks(4)
+        .map(|word| [word[0], word[1], word[2], word[3]])
+        .collect();
+
+    for i in nk..Nb * (nr + 1) {
+        let mut temp = w[i - 1];
+        if i % nk == 0 {
+            temp = sub_word([temp[1], temp[2], temp[3], temp[0]]);
+            temp[0] ^= Rcon[i / nk - 1][0];
+        } else if nk > 6 && i % nk == 4 {
+            temp = sub_word(temp);
+        }
+
+        let previous = w[i - nk];
+        w.push([
+            previous[0] ^ temp[0],
+            previous[1] ^ temp[1],
+            previous[2] ^ temp[2],
+            previous[3] ^ temp[3],
+        ]);
+    }
+
+    w.concat()
+}
+
+fn sub_word(word: [u8; 4]) -> [u8; 4] {
+    let mut state = bitslice(&word);
+    sub_bytes(&mut state);
+    let bytes = unbitslice(&state);
+
+    [bytes[0], bytes[1], bytes[2], bytes[3]]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::{Aes, S_BOX, INVERSE_S_BOX};
+    use aes::generate::generate_bytes_for_length;
+    use hex::hex_string_to_bytes;
+    use mode;
+
+    #[test]
+    fn sub_bytes_matches_the_s_boxes() {
+        let bytes = (0..=255).collect::<Vec<u8>>();
+
+        for chunk in bytes.chunks(128) {
+            let mut state = bitslice(chunk);
+            sub_bytes(&mut state);
+            let substituted = unbitslice(&state);
+            inv_sub_bytes(&mut state);
+            let inverted = unbitslice(&state);
+
+            let mut state = bitslice(chunk);
+            inv_sub_bytes(&mut state);
+            let inverse_substituted = unbitslice(&state);
+
+            for (i, byte) in chunk.iter().enumerate() {
+                assert_eq!(substituted[i], S_BOX[*byte as usize]);
+                assert_eq!(inverted[i], *byte);
+                assert_eq!(inverse_substituted[i], INVERSE_S_BOX[*byte as usize]);
+            }
+        }
+    }
+
+    #[test]
+    fn encrypt_block_fips_197_appendix_c_test_cases() {
+        let raw = &(0..16).map(|i| i * 0x11).collect::<Vec<u8>>();
+        let test_cases = vec![
+            (16, "69c4e0d86a7b0430d8cdb78070b4c55a"),
+            (24, "dda97ca4864cdfe06eaf70a0ec0d7191"),
+            (32, "8ea2b7ca516745bfeafc49904b496089"),
+        ];
+
+        for (key_length, expected_cipher) in test_cases.iter() {
+            let key = Key::new(&(0..*key_length).collect::<Vec<u8>>());
+            let block_cipher = BitslicedAes::new(&key);
+            let block = &mut raw.clone();
+
+            block_cipher.encrypt_block(block);
+            assert_eq!(block, &hex_string_to_bytes(expected_cipher));
+
+            block_cipher.decrypt_block(block);
+            assert_eq!(block, raw);
+        }
+    }
+
+    #[test]
+    fn encrypt_blocks_matches_the_state_path() {
+        for key_length in [16, 24, 32].iter() {
+            let key = Key::new(&generate_bytes_for_length(*key_length));
+            let reference = Aes::new(&key);
+            let bitsliced_aes = BitslicedAes::new(&key);
+
+            // a partial batch, a full one, and more than one
+            for blocks_count in [1, 3, 8, 19].iter() {
+                let bytes = generate_bytes_for_length(16 * blocks_count);
+
+                let cipher = mode::ecb_encrypt(&bitsliced_aes, &bytes);
+
+                assert_eq!(cipher, mode::ecb_encrypt(&reference, &bytes));
+                assert_eq!(mode::ecb_decrypt(&bitsliced_aes, &cipher), bytes);
+            }
+        }
+    }
+}
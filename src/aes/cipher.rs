@@ -0,0 +1,165 @@
+use aes::{Key, KeySchedule, Nb, key_expansion};
+use aes::state::State;
+use mode::BlockCipher;
+
+/// AES block cipher built once from a Key. The Key Expansion (and the derivation of the inverse
+/// key schedule) only runs on construction, so that encrypting or decrypting many blocks under the
+/// same key does not repeat it.
+pub struct Aes {
+    key_schedule: KeySchedule,
+    /// Key schedule of the Equivalent Inverse Cipher, see Sec. 5.3.5.
+    inverse_key_schedule: KeySchedule,
+}
+
+impl Aes {
+    pub fn new(key: &Key) -> Self {
+        let key_schedule = key_expansion(key);
+        let inverse_key_schedule = inverse_key_schedule(&key_schedule);
+
+        Aes {
+            key_schedule,
+            inverse_key_schedule,
+        }
+    }
+
+    /// At the start of the Cipher, the input is copied to the State array using the conventions
+    /// described in Sec. 3.4. After an initial Round Key addition, the State array is transformed
+    /// by implementing a round function 10, 12, or 14 times (depending on the key length), with
+    /// the final round differing slightly from the first Nr -1 rounds. The final State is then
+    /// copied to the output as described in Sec. 3.4.
+    pub(crate) fn encrypt_state(&self, state: &mut State) {
+        let w = &self.key_schedule;
+        let nr = w.rounds();
+
+        state.add_round_key(w.round_key(0));
+
+        for round in 1..nr {
+            state.sub_bytes();
+            state.shift_rows();
+            state.mix_columns();
+            state.add_round_key(w.round_key(round));
+        }
+
+        state.sub_bytes();
+        state.shift_rows();
+        state.add_round_key(w.round_key(nr));
+    }
+
+    /// The Equivalent Inverse Cipher has the same sequence of transformations as the Cipher, with
+    /// each transformation replaced by its inverse, which is made possible by applying
+    /// InvMixColumns() to the Round Keys of rounds 1 to Nr - 1 beforehand (see Sec. 5.3.5).
+    pub(crate) fn decrypt_state(&self, state: &mut State) {
+        let dw = &self.inverse_key_schedule;
+        let nr = dw.rounds();
+
+        state.add_round_key(dw.round_key(nr));
+
+        for round in (1..nr).rev() {
+            state.inv_sub_bytes();
+            state.inv_shift_rows();
+            state.inv_mix_columns();
+            state.add_round_key(dw.round_key(round));
+        }
+
+        state.inv_sub_bytes();
+        state.inv_shift_rows();
+        state.add_round_key(dw.round_key(0));
+    }
+}
+
+impl BlockCipher for Aes {
+    fn block_size(&self) -> usize {
+        4 * Nb
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        assert_eq!(block.len(), 4 * Nb);
+
+        let mut state = State::from_part(block);
+        self.encrypt_state(&mut state);
+        state.write_block(block);
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        assert_eq!(block.len(), 4 * Nb);
+
+        let mut state = State::from_part(block);
+        self.decrypt_state(&mut state);
+        state.write_block(block);
+    }
+}
+
+/// Derives the decryption key schedule dw of the Equivalent Inverse Cipher from the key schedule w.
+pub(super) fn inverse_key_schedule(key_schedule: &KeySchedule) -> KeySchedule {
+    let nr = key_schedule.rounds();
+    let mut dw = key_schedule.0.clone();
+
+    for round in 1..nr {
+        let mut state = State::from_part(&key_schedule.round_key(round).concat());
+        state.inv_mix_columns();
+
+        for (i, word) in state.to_block().chunks(4).enumerate() {
+            dw[round * Nb + i] = [word[0], word[1], word[2], word[3]];
+        }
+    }
+
+    KeySchedule(dw, key_schedule.key_size())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_and_decrypt_block_nist_appendix_c_test_cases() {
+        let raw: &[u8] = &[
+            0x00, 0x11, 0x22, 0x33,
+            0x44, 0x55, 0x66, 0x77,
+            0x88, 0x99, 0xaa, 0xbb,
+            0xcc, 0xdd, 0xee, 0xff
+        ];
+        let test_cases: Vec<(Vec<u8>, Vec<u8>)> = vec![
+            ((0x00..0x10).collect(), vec![
+                0x69, 0xc4, 0xe0, 0xd8,
+                0x6a, 0x7b, 0x04, 0x30,
+                0xd8, 0xcd, 0xb7, 0x80,
+                0x70, 0xb4, 0xc5, 0x5a
+            ]),
+            ((0x00..0x18).collect(), vec![
+                0xdd, 0xa9, 0x7c, 0xa4,
+                0x86, 0x4c, 0xdf, 0xe0,
+                0x6e, 0xaf, 0x70, 0xa0,
+                0xec, 0x0d, 0x71, 0x91
+            ]),
+            ((0x00..0x20).collect(), vec![
+                0x8e, 0xa2, 0xb7, 0xca,
+                0x51, 0x67, 0x45, 0xbf,
+                0xea, 0xfc, 0x49, 0x90,
+                0x4b, 0x49, 0x60, 0x89
+            ]),
+        ];
+
+        for (key, expected_cipher) in test_cases.iter() {
+            let block_cipher = Aes::new(&Key::new(key));
+            let block = &mut raw.to_vec();
+
+            block_cipher.encrypt_block(block);
+            assert_eq!(block, expected_cipher);
+
+            block_cipher.decrypt_block(block);
+            assert_eq!(&block[..], raw);
+        }
+    }
+
+    #[test]
+    fn inverse_key_schedule_keeps_first_and_last_round_keys() {
+        let key_schedule = key_expansion(&Key::new_from_string("YELLOW SUBMARINE"));
+        let nr = key_schedule.rounds();
+
+        let inverse_key_schedule = inverse_key_schedule(&key_schedule);
+
+        assert_eq!(inverse_key_schedule.round_key(0), key_schedule.round_key(0));
+        assert_eq!(inverse_key_schedule.round_key(nr), key_schedule.round_key(nr));
+        assert_ne!(inverse_key_schedule.round_key(1), key_schedule.round_key(1));
+    }
+}
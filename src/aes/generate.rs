@@ -16,12 +16,11 @@ fn random_byte() -> u8 {
 }
 
 pub fn generate_aes_128_key() -> aes::Key {
-    let mut key = [0; 16];
-    for item in key.iter_mut() {
-        *item = random_byte();
-    }
+    generate_aes_key(aes::KeySize::AES128)
+}
 
-    aes::Key(key)
+pub fn generate_aes_key(key_size: aes::KeySize) -> aes::Key {
+    aes::Key::new(&generate_bytes_for_length(key_size.length()))
 }
 
 pub fn generate_bytes_for_length(length: usize) -> Vec<u8> {
@@ -51,10 +50,21 @@ mod tests {
 
     #[test]
     fn generate_aes_128_key_test() {
-        let key = generate_aes_128_key().0;
+        let key = generate_aes_128_key();
+
+        assert_some_randomness(key.as_bytes());
+        assert_eq!(key.as_bytes().len(), 16);
+    }
+
+    #[test]
+    fn generate_aes_key_test() {
+        for key_size in [aes::KeySize::AES128, aes::KeySize::AES192, aes::KeySize::AES256].iter() {
+            let key = generate_aes_key(*key_size);
 
-        assert_some_randomness(&key[..]);
-        assert_eq!(key.len(), 16);
+            assert_some_randomness(key.as_bytes());
+            assert_eq!(key.as_bytes().len(), key_size.length());
+            assert_eq!(&key.size(), key_size);
+        }
     }
 
     #[test]
@@ -24,6 +24,82 @@ pub fn multiply_in_g(polynomial_value: u8, state_value: u8) -> u8 {
     p
 }
 
+/// Element of GF(2^128) as used by GCM (see SP 800-38D, Sec. 6.3). The bits of a block are the
+/// coefficients of the polynomial, the leftmost bit being the coefficient of x^0, so that the
+/// block is read as a big-endian integer with the lowest degree term in its most significant bit.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Gf128(pub u128);
+
+impl Gf128 {
+    pub fn zero() -> Self {
+        Gf128(0)
+    }
+
+    pub fn one() -> Self {
+        Gf128(1 << 127)
+    }
+
+    pub fn from_block(block: &[u8]) -> Self {
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(block);
+
+        Gf128(u128::from_be_bytes(bytes))
+    }
+
+    pub fn to_block(&self) -> [u8; 16] {
+        self.0.to_be_bytes()
+    }
+
+    /// Multiplication modulo x^128 + x^7 + x^2 + x + 1, following Algorithm 1 of SP 800-38D. Masks
+    /// stand in for the branches on the bits of the operands, so that the running time does not
+    /// depend on them.
+    pub fn multiply(&self, other: &Gf128) -> Gf128 {
+        let r = 0xe1u128 << 120;
+        let mut z = 0u128;
+        let mut v = other.0;
+
+        for i in (0..128).rev() {
+            let x_i = (self.0 >> i) & 1;
+            z ^= v & x_i.wrapping_neg();
+            let lsb = v & 1;
+            v = (v >> 1) ^ (r & lsb.wrapping_neg());
+        }
+
+        Gf128(z)
+    }
+
+    pub fn add(&self, other: &Gf128) -> Gf128 {
+        Gf128(self.0 ^ other.0)
+    }
+}
+
+/// Multiplication by x in GF(2^128) with the polynomial basis of CMAC (see RFC 4493, Sec. 2.3):
+/// the block is read as a big-endian integer, its most significant bit being the coefficient of
+/// x^127. The reduction by x^128 + x^7 + x^2 + x + 1 is masked rather than branched upon.
+pub fn double_in_gf_128(block: &[u8]) -> Vec<u8> {
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(block);
+
+    double_u128_in_gf_128(u128::from_be_bytes(bytes)).to_be_bytes().to_vec()
+}
+
+/// Multiplication by x (alpha) in GF(2^128) with the basis of XTS (see IEEE 1619, Sec. 5.2): the
+/// block is read as a little-endian integer, the first byte holding the lowest coefficients.
+pub fn double_in_gf_128_little_endian(block: &[u8]) -> Vec<u8> {
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(block);
+
+    double_u128_in_gf_128(u128::from_le_bytes(bytes)).to_le_bytes().to_vec()
+}
+
+/// Multiplication by x in GF(2^128) of an element held by an integer, whose highest bit is the
+/// coefficient of x^127, without allocating.
+pub fn double_u128_in_gf_128(value: u128) -> u128 {
+    let msb = value >> 127;
+
+    (value << 1) ^ (0x87 & msb.wrapping_neg())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -49,4 +125,42 @@ mod tests {
             assert_eq!(actual_result, case.expected);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn multiply_in_gf_128_test() {
+        // H and X1 of test case 2 of the GCM specification
+        let h = Gf128(0x66e94bd4ef8a2c3b884cfa59ca342b2e);
+        let c = Gf128(0x0388dace60b6a392f328c2b971b2fe78);
+        let expected = Gf128(0x5e2ec746917062882c85b0685353deb7);
+
+        assert_eq!(c.multiply(&h), expected);
+        assert_eq!(h.multiply(&c), expected);
+        assert_eq!(h.multiply(&Gf128::one()), h);
+        assert_eq!(h.multiply(&Gf128::zero()), Gf128::zero());
+    }
+
+    #[test]
+    fn double_in_gf_128_test() {
+        // subkey generation of RFC 4493, Sec. 4
+        let l = 0x7df76b0c1ab899b33e42f047b91b546fu128.to_be_bytes();
+        let k1 = 0xfbeed618357133667c85e08f7236a8deu128.to_be_bytes();
+        let k2 = 0xf7ddac306ae266ccf90bc11ee46d513bu128.to_be_bytes();
+
+        assert_eq!(double_in_gf_128(&l), k1);
+        assert_eq!(double_in_gf_128(&k1), k2);
+    }
+
+    #[test]
+    fn double_in_gf_128_little_endian_test() {
+        let mut carried = [0u8; 16];
+        carried[15] = 0x80;
+        carried[0] = 0x01;
+        let mut expected = [0u8; 16];
+        expected[0] = 0x02 ^ 0x87;
+
+        assert_eq!(double_in_gf_128_little_endian(&carried), expected);
+        assert_eq!(double_in_gf_128_little_endian(&[0x40; 16]), [0x80; 16]);
+        // a carry goes from the top bit of each byte into the next one
+        assert_eq!(double_in_gf_128_little_endian(&[0x80; 16])[..2], [0x87, 0x01]);
+    }
+}
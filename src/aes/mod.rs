@@ -3,28 +3,34 @@
 /// https://en.wikipedia.org/wiki/Rijndael_MixColumns#Implementation_example
 /// https://en.wikipedia.org/wiki/Block_cipher_mode_of_operation
 
-use ::xor;
-use aes::PaddingError::{PaddingNotConsistent, InvalidLastPaddingByte};
-use aes::Padding::PKCS7;
+use ::{mode, xor};
+use mode::{BlockCipher, CiphertextStealing};
+use mode::counter::CounterBlock;
+use mode::{in_place, parallel};
 
 mod state;
-mod math;
+pub mod math;
+mod aes_ni;
+pub mod bitsliced;
+mod cipher;
+mod padding;
+mod reduced_round;
+pub mod t_table;
+pub mod trace;
 pub mod generate;
 
+pub use self::aes_ni::AesNi;
+pub use self::cipher::Aes;
+pub use self::reduced_round::{ReducedRoundAes, Transformation};
+pub use self::state::State;
+pub use self::padding::{
+    Padding, PaddingError, pkcs7_pad, validate_pkcs7_pad, remove_pkcs7_padding,
+};
+
 /// Number of columns (32-bit words) comprising the State. For this standard, Nb = 4.
 #[allow(non_upper_case_globals)]
 pub(crate) const Nb: usize = 4;
 
-/// Number of rounds, which is a function of Nk and Nb (which is fixed). For this implementation,
-/// Nr = 10. (because this is only aes-128)
-#[allow(non_upper_case_globals)]
-const Nr: usize = 10;
-
-/// Number of 32-bit words comprising the Cipher Key. For this implementation, Nk = 4. (because
-/// this is only aes-128)
-#[allow(non_upper_case_globals)]
-const Nk: usize = 4;
-
 /// Non-linear substitution table used in several byte substitution transformations and in the
 /// Key Expansion routine to perform a one-for-one substitution of a byte value.
 const S_BOX: [u8; 256] = [
@@ -48,7 +54,7 @@ const S_BOX: [u8; 256] = [
 
 /// Inverse of the S-BOX. Used in the InvSubBytes step to perform reverse one-for-one substitution
 /// of a byte.
-const INVERSE_S_BOX: [u8; 256] = [
+pub(crate) const INVERSE_S_BOX: [u8; 256] = [
     0x52, 0x09, 0x6a, 0xd5, 0x30, 0x36, 0xa5, 0x38, 0xbf, 0x40, 0xa3, 0x9e, 0x81, 0xf3, 0xd7, 0xfb,
     0x7c, 0xe3, 0x39, 0x82, 0x9b, 0x2f, 0xff, 0x87, 0x34, 0x8e, 0x43, 0x44, 0xc4, 0xde, 0xe9, 0xcb,
     0x54, 0x7b, 0x94, 0x32, 0xa6, 0xc2, 0x23, 0x3d, 0xee, 0x4c, 0x95, 0x0b, 0x42, 0xfa, 0xc3, 0x4e,
@@ -82,21 +88,67 @@ const Rcon: [[u8; 4]; 10] = [
     [0x36, 0x00, 0x00, 0x00],
 ];
 
-pub struct Key(pub [u8; 16]);
+/// Length of the Cipher Key. The AES algorithm is capable of using cryptographic keys of 128,
+/// 192, and 256 bits, which respectively determine Nk and Nr.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum KeySize {
+    AES128,
+    AES192,
+    AES256,
+}
+
+impl KeySize {
+    pub fn from_length(length: usize) -> Option<KeySize> {
+        match length {
+            16 => Some(KeySize::AES128),
+            24 => Some(KeySize::AES192),
+            32 => Some(KeySize::AES256),
+            _ => None
+        }
+    }
+
+    /// Number of 32-bit words comprising the Cipher Key. For this standard, Nk = 4, 6, or 8.
+    pub fn nk(&self) -> usize {
+        match self {
+            KeySize::AES128 => 4,
+            KeySize::AES192 => 6,
+            KeySize::AES256 => 8,
+        }
+    }
+
+    /// Number of rounds, which is a function of Nk and Nb (which is fixed). For this standard,
+    /// Nr = 10, 12, or 14.
+    pub fn nr(&self) -> usize {
+        self.nk() + 6
+    }
+
+    pub fn length(&self) -> usize {
+        4 * self.nk()
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Key(Vec<u8>);
 
 impl Key {
+    pub fn new(bytes: &[u8]) -> Self {
+        if KeySize::from_length(bytes.len()).is_none() {
+            panic!("Key must be 16, 24 or 32 bytes long, got {} bytes.", bytes.len());
+        }
+
+        Key(bytes.to_vec())
+    }
+
     pub fn new_from_string(string: &str) -> Self {
-        Key(Key::key_from_string(string))
+        Key::new(string.as_bytes())
     }
 
-    fn key_from_string(s: &str) -> [u8; 16] {
-        let mut out = [0u8; 16];
-        let bytes = s.as_bytes();
-        for (i, byte) in out.iter_mut().enumerate() {
-            *byte = bytes[i];
-        }
+    pub fn size(&self) -> KeySize {
+        KeySize::from_length(self.0.len()).unwrap()
+    }
 
-        out
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
     }
 }
 
@@ -129,6 +181,13 @@ pub enum BlockCipherMode<'a> {
     ECB,
     CBC(&'a Iv),
     CTR(&'a Nonce),
+    OFB(&'a Iv),
+    /// Cipher Feedback with 1-bit, 8-bit and 128-bit segments.
+    CFB1(&'a Iv),
+    CFB8(&'a Iv),
+    CFB128(&'a Iv),
+    /// CBC with ciphertext stealing, in any of its variants.
+    CBCCS(&'a Iv, CiphertextStealing),
 }
 
 #[derive(PartialEq, Debug)]
@@ -138,265 +197,462 @@ impl Block {
     pub fn empty() -> Self {
         Block([[0; 4]; Nb])
     }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.concat()
+    }
 }
 
 pub type Iv = Block;
 pub type Nonce = [u8; 8];
 
-#[derive(PartialEq, Debug)]
-pub enum Padding {
-    PKCS7,
-    None,
+/// Reasons why bytes can't be encrypted with the given options.
+#[derive(Debug, PartialEq)]
+pub enum EncryptError {
+    /// Ciphertext stealing requires at least one block, and ECB and CBC a whole number of blocks
+    /// once padded.
+    InvalidLength { length: usize, block_size: usize },
+    /// Ciphertext stealing keeps the cipher as long as the plaintext, which can't be padded. Nor
+    /// can the bytes encrypted in place without room for the padding.
+    UnsupportedPadding,
+    /// The buffer has no room for the padding of the bytes it holds.
+    BufferTooSmall { length: usize, required_length: usize },
+    /// The iv of the mode is not a block long, or its nonce leaves no room for the counter.
+    InvalidIv { length: usize, block_size: usize },
 }
 
-/// At the start of the Cipher, the input is copied to the State array using the conventions
-/// described in Sec. 3.4. After an initial Round Key addition, the State array is transformed by
-/// implementing a round function 10, 12, or 14 times (depending on the key length), with the
-/// final round differing slightly from the first Nr -1 rounds. The final State is then copied to
-/// the output as described in Sec. 3.4.
-pub fn encrypt_aes_128(raw_bytes: &[u8], key: &Key, options: &AESEncryptionOptions) -> Vec<u8> {
-    let block_size = 16;
-    let ctr_counter: u8 = 0;
-
-    let w = &key_expansion(key).0;
-    let bytes = &if options.padding == &PKCS7 {
-        pkcs7_pad(raw_bytes, block_size)
-    } else {
-        if let BlockCipherMode::CTR(nonce) = &options.block_cipher_mode {
-            generate_ctr_bytes_for_length(raw_bytes.len(), &nonce)
-        } else {
-            raw_bytes.to_vec()
-        }
-    };
-    let parts = bytes_to_parts(bytes);
-
-    let mut cipher: Vec<u8> = Vec::with_capacity(raw_bytes.len());
-    let mut previous_state: state::State = state::State::empty();
-
-    for (i, part) in parts.iter().enumerate() {
-        let mut state = state::State::from_part(part);
-        if let BlockCipherMode::CBC(iv) = &options.block_cipher_mode {
-            if i == 0 {
-                state.xor_with_iv(&iv);
-            } else {
-                state.xor_with_state(&previous_state);
-            };
+/// Encrypts bytes under a key, using the block cipher mode and padding of the given options, on
+/// the AES-NI instructions when the processor has them. The key is expanded on every call, prefer
+/// `encrypt` with a built cipher when encrypting repeatedly under the same key.
+pub fn encrypt_aes_128(raw_bytes: &[u8], key: &Key, options: &AESEncryptionOptions)
+                       -> Result<Vec<u8>, EncryptError> {
+    encrypt(&AesNi::new(key), raw_bytes, options)
+}
+
+/// Encrypts bytes with an already built block cipher, using the block cipher mode and padding of
+/// the given options. Any AES implementation will do: `Aes`, `t_table::TTableAes`,
+/// `bitsliced::BitslicedAes` or `AesNi`.
+pub fn encrypt<C: BlockCipher + Sync>(
+    block_cipher: &C,
+    raw_bytes: &[u8],
+    options: &AESEncryptionOptions,
+) -> Result<Vec<u8>, EncryptError> {
+    let block_size = block_cipher.block_size();
+    if let Some(length) = invalid_iv_length(options.block_cipher_mode, block_size) {
+        return Err(EncryptError::InvalidIv { length, block_size });
+    }
+    if let BlockCipherMode::CBCCS(..) = options.block_cipher_mode {
+        if options.padding != &Padding::None {
+            return Err(EncryptError::UnsupportedPadding);
         }
+    }
 
-        state.add_round_key(&w[0..Nb]);
+    let bytes = &options.padding.pad(raw_bytes, block_size);
+    if !fits_mode(options.block_cipher_mode, bytes.len(), block_size) {
+        return Err(EncryptError::InvalidLength { length: bytes.len(), block_size });
+    }
 
-        for round in 1..Nr {
-            state.sub_bytes();
-            state.shift_rows();
-            state.mix_columns();
-            state.add_round_key(&w[round * Nb..(round + 1) * Nb]);
+    let cipher = match options.block_cipher_mode {
+        BlockCipherMode::ECB => parallel::par_ecb_encrypt(block_cipher, bytes),
+        BlockCipherMode::CBC(iv) => mode::cbc_encrypt(block_cipher, &iv.to_bytes(), bytes),
+        BlockCipherMode::CTR(nonce) => {
+            parallel::par_ctr(block_cipher, &CounterBlock::little_endian(&nonce[..]), bytes)
+        }
+        BlockCipherMode::OFB(iv) => mode::ofb(block_cipher, &iv.to_bytes(), bytes),
+        BlockCipherMode::CFB1(iv) => mode::cfb_encrypt(block_cipher, &iv.to_bytes(), 1, bytes),
+        BlockCipherMode::CFB8(iv) => mode::cfb_encrypt(block_cipher, &iv.to_bytes(), 8, bytes),
+        BlockCipherMode::CFB128(iv) => mode::cfb_encrypt(block_cipher, &iv.to_bytes(), 128, bytes),
+        BlockCipherMode::CBCCS(iv, variant) => {
+            mode::cbc_cs_encrypt(block_cipher, &iv.to_bytes(), *variant, bytes)
         }
+    };
 
-        state.sub_bytes();
-        state.shift_rows();
-        state.add_round_key(&w[Nr * Nb..(Nr + 1) * Nb]);
+    Ok(cipher)
+}
 
-        if let BlockCipherMode::CBC(_iv) = &options.block_cipher_mode {
-            previous_state = state.clone();
-        }
+/// Reasons why a cipher can't be decrypted with the given options.
+#[derive(Debug, PartialEq)]
+pub enum DecryptError {
+    /// The plaintext is not padded as the options say.
+    Padding(PaddingError),
+    /// The mode (ECB and CBC) or the padding requires a whole number of blocks, ciphertext
+    /// stealing at least one block.
+    InvalidLength { length: usize, block_size: usize },
+    /// Ciphertext stealing keeps the cipher as long as the plaintext, which can't be padded.
+    UnsupportedPadding,
+    /// The iv of the mode is not a block long, or its nonce leaves no room for the counter.
+    InvalidIv { length: usize, block_size: usize },
+}
 
-        cipher.append(state.to_block().as_mut());
+impl From<PaddingError> for DecryptError {
+    fn from(error: PaddingError) -> Self {
+        DecryptError::Padding(error)
     }
+}
 
-    if let BlockCipherMode::CTR(_nonce) = &options.block_cipher_mode {
-        xor::fixed_key_xor(&raw_bytes, &cipher)
-    } else {
-        cipher
+/// Decrypts bytes under a key, using the block cipher mode and padding of the given options, on
+/// the AES-NI instructions when the processor has them. The key is expanded on every call, prefer
+/// `decrypt` with a built cipher when decrypting repeatedly under the same key.
+pub fn decrypt_aes_128(cipher: &[u8], key: &Key, options: &AESEncryptionOptions)
+                       -> Result<Vec<u8>, DecryptError> {
+    decrypt(&AesNi::new(key), cipher, options)
+}
+
+/// Decrypts bytes with an already built block cipher, using the block cipher mode and padding of
+/// the given options.
+pub fn decrypt<C: BlockCipher + Sync>(
+    block_cipher: &C,
+    cipher: &[u8],
+    options: &AESEncryptionOptions,
+) -> Result<Vec<u8>, DecryptError> {
+    let block_size = block_cipher.block_size();
+    if let Some(length) = invalid_iv_length(options.block_cipher_mode, block_size) {
+        return Err(DecryptError::InvalidIv { length, block_size });
     }
+    let requires_whole_blocks = match options.block_cipher_mode {
+        BlockCipherMode::ECB | BlockCipherMode::CBC(_) => true,
+        _ => options.padding != &Padding::None,
+    };
+    // every padding but zero padding adds at least a byte, and so a block
+    let shortest_length = options.padding.padded_length(0, block_size);
+    if (requires_whole_blocks && cipher.len() % block_size != 0) || cipher.len() < shortest_length {
+        return Err(DecryptError::InvalidLength { length: cipher.len(), block_size });
+    }
+    if let BlockCipherMode::CBCCS(..) = options.block_cipher_mode {
+        if options.padding != &Padding::None {
+            return Err(DecryptError::UnsupportedPadding);
+        }
+        if cipher.len() < block_size {
+            return Err(DecryptError::InvalidLength { length: cipher.len(), block_size });
+        }
+    }
+
+    let deciphered = match options.block_cipher_mode {
+        BlockCipherMode::ECB => parallel::par_ecb_decrypt(block_cipher, cipher),
+        BlockCipherMode::CBC(iv) => {
+            parallel::par_cbc_decrypt(block_cipher, &iv.to_bytes(), cipher)
+        }
+        BlockCipherMode::CTR(nonce) => {
+            parallel::par_ctr(block_cipher, &CounterBlock::little_endian(&nonce[..]), cipher)
+        }
+        BlockCipherMode::OFB(iv) => mode::ofb(block_cipher, &iv.to_bytes(), cipher),
+        BlockCipherMode::CFB1(iv) => mode::cfb_decrypt(block_cipher, &iv.to_bytes(), 1, cipher),
+        BlockCipherMode::CFB8(iv) => mode::cfb_decrypt(block_cipher, &iv.to_bytes(), 8, cipher),
+        BlockCipherMode::CFB128(iv) => {
+            mode::cfb_decrypt(block_cipher, &iv.to_bytes(), 128, cipher)
+        }
+        BlockCipherMode::CBCCS(iv, variant) => {
+            mode::cbc_cs_decrypt(block_cipher, &iv.to_bytes(), *variant, cipher)
+        }
+    };
+
+    Ok(options.padding.unpad(&deciphered, block_size)?)
 }
 
-fn generate_ctr_bytes_for_length(length: usize, nonce: &Nonce) -> Vec<u8> {
-    let block_size = 16;
-    let mut counter = 0u8;
-    (0..length - (length % block_size) + block_size).collect::<Vec<usize>>()
-        .iter()
-        .enumerate()
-        .map(|(i, _)|
-            if (i % block_size) < nonce.len() {
-                nonce[i % block_size]
-            } else if (i % block_size) == nonce.len() {
-                counter += 1;
-
-                counter - 1
-            } else {
-                0u8
-            }
-        )
-        .collect::<Vec<u8>>()
+/// Encrypts the bytes of the buffer in place. The mode doesn't allocate, the block cipher may (see
+/// `mode::in_place`). The cipher takes the place of the bytes, which leaves no room for a padding
+/// (see `encrypt_padded_in_place`): ECB and CBC require a whole number of blocks.
+pub fn encrypt_in_place<C: BlockCipher>(
+    block_cipher: &C,
+    buffer: &mut [u8],
+    options: &AESEncryptionOptions,
+) -> Result<(), EncryptError> {
+    if options.padding != &Padding::None {
+        return Err(EncryptError::UnsupportedPadding);
+    }
+
+    encrypt_unpadded_in_place(block_cipher, buffer, options.block_cipher_mode)
 }
 
-/// The Cipher transformations in Sec. 5.1 can be inverted and then implemented in reverse order to
-/// produce a straightforward Inverse Cipher for the AES algorithm. The individual transformations
-/// used in the Inverse Cipher - InvShiftRows(), InvSubBytes(),InvMixColumns(),
-/// and AddRoundKey() – process the State and are described in the following subsections.
-pub fn decrypt_aes_128(cipher: &[u8], key: &Key, mode: &BlockCipherMode) -> Vec<u8> {
-    let block_size = 16;
-    let w = &key_expansion(key).0;
-    let parts = bytes_to_parts(cipher);
-    let mut deciphered: Vec<u8> = Vec::with_capacity(cipher.len());
-    let mut previous_state = state::State::empty();
-
-    for (i, part) in parts.iter().enumerate() {
-        let mut state = state::State::from_part(part);
-
-        state.add_round_key(&w[Nr * Nb..(Nr + 1) * Nb]);
-
-        for round in (1..Nr).rev() {
-            state.inv_shift_rows();
-            state.inv_sub_bytes();
-            state.add_round_key(&w[round * Nb..(round + 1) * Nb]);
-            state.inv_mix_columns();
-        }
-
-        state.inv_shift_rows();
-        state.inv_sub_bytes();
-        state.add_round_key(&w[0..Nb]);
-
-        if let BlockCipherMode::CBC(iv) = mode {
-            if i == 0 {
-                state.xor_with_iv(iv);
-            } else {
-                state.xor_with_state(&previous_state);
-            };
-            previous_state = state::State::from_part(part);
+/// Pads the first length bytes of the buffer, then encrypts them in place. The rest of the buffer
+/// must have room for the padding. Returns the length of the cipher, found at the start of the
+/// buffer.
+pub fn encrypt_padded_in_place<C: BlockCipher>(
+    block_cipher: &C,
+    buffer: &mut [u8],
+    length: usize,
+    options: &AESEncryptionOptions,
+) -> Result<usize, EncryptError> {
+    let block_size = block_cipher.block_size();
+    if let BlockCipherMode::CBCCS(..) = options.block_cipher_mode {
+        if options.padding != &Padding::None {
+            return Err(EncryptError::UnsupportedPadding);
         }
+    }
 
-        deciphered.append(state.to_block().as_mut());
+    let padded_length = options.padding.padded_length(length, block_size);
+    if buffer.len() < padded_length {
+        return Err(EncryptError::BufferTooSmall {
+            length: buffer.len(),
+            required_length: padded_length,
+        });
     }
+    options.padding.pad_in_place(buffer, length, block_size);
 
-    deciphered
+    let padded = &mut buffer[..padded_length];
+    encrypt_unpadded_in_place(block_cipher, padded, options.block_cipher_mode)?;
+
+    Ok(padded_length)
 }
 
-pub fn bytes_to_parts(bytes: &[u8]) -> Vec<Vec<u8>> {
-    let block_size = 16;
+fn encrypt_unpadded_in_place<C: BlockCipher>(
+    block_cipher: &C,
+    buffer: &mut [u8],
+    mode: &BlockCipherMode,
+) -> Result<(), EncryptError> {
+    let block_size = block_cipher.block_size();
+    if let Some(length) = invalid_iv_length(mode, block_size) {
+        return Err(EncryptError::InvalidIv { length, block_size });
+    }
+    if !fits_mode(mode, buffer.len(), block_size) {
+        return Err(EncryptError::InvalidLength { length: buffer.len(), block_size });
+    }
 
-    let mut parts = vec![
-        vec![0; block_size as usize]; (bytes.len() as f32 / block_size as f32).ceil() as usize
-    ];
-    for (i, byte) in bytes.iter().enumerate() {
-        parts[(i as f32 / block_size as f32).floor() as usize][i % block_size as usize] = *byte;
+    match mode {
+        BlockCipherMode::ECB => in_place::ecb_encrypt_in_place(block_cipher, buffer),
+        BlockCipherMode::CBC(iv) => {
+            in_place::cbc_encrypt_in_place(block_cipher, &iv.to_bytes(), buffer)
+        }
+        BlockCipherMode::CTR(nonce) => {
+            in_place::ctr_in_place(block_cipher, &CounterBlock::little_endian(&nonce[..]), buffer)
+        }
+        BlockCipherMode::OFB(iv) => in_place::ofb_in_place(block_cipher, &iv.to_bytes(), buffer),
+        BlockCipherMode::CFB1(iv) => {
+            in_place::cfb_encrypt_in_place(block_cipher, &iv.to_bytes(), 1, buffer)
+        }
+        BlockCipherMode::CFB8(iv) => {
+            in_place::cfb_encrypt_in_place(block_cipher, &iv.to_bytes(), 8, buffer)
+        }
+        BlockCipherMode::CFB128(iv) => {
+            in_place::cfb_encrypt_in_place(block_cipher, &iv.to_bytes(), 128, buffer)
+        }
+        BlockCipherMode::CBCCS(iv, variant) => {
+            in_place::cbc_cs_encrypt_in_place(block_cipher, &iv.to_bytes(), *variant, buffer)
+        }
     }
 
-    parts
+    Ok(())
 }
 
-struct KeySchedule(pub [[u8; 4]; Nb * (Nr + 1)]);
+/// Decrypts the cipher of the buffer in place, the mode without allocating. The padding of the
+/// options must be None, see `decrypt_padded_in_place` otherwise.
+pub fn decrypt_in_place<C: BlockCipher>(
+    block_cipher: &C,
+    buffer: &mut [u8],
+    options: &AESEncryptionOptions,
+) -> Result<(), DecryptError> {
+    if options.padding != &Padding::None {
+        return Err(DecryptError::UnsupportedPadding);
+    }
 
-/// Routine used to generate a series of Round Keys from the Cipher Key.
-/// The Key Expansion generates a total of Nb (Nr + 1) words: the algorithm requires
-/// an initial set of Nb words, and each of the Nr rounds requires Nb words of key data. The
-/// resulting key schedule consists of a linear array of 4-byte words, denoted [wi ], with i in
-/// the range 0 <= i < Nb(Nr + 1).
-fn key_expansion(key: &Key) -> KeySchedule {
-    let mut w = [[0u8; Nk]; Nb * (Nr + 1)];
+    decrypt_unpadded_in_place(block_cipher, buffer, options.block_cipher_mode)
+}
 
-    for i in 0..Nk {
-        let key_part = &key.0[4 * i..4 * i + 4];
-        w[i] = [key_part[0], key_part[1], key_part[2], key_part[3]];
+/// Decrypts the cipher of the buffer in place, then validates its padding. Returns the length of
+/// the plaintext, found at the start of the buffer.
+pub fn decrypt_padded_in_place<C: BlockCipher>(
+    block_cipher: &C,
+    buffer: &mut [u8],
+    options: &AESEncryptionOptions,
+) -> Result<usize, DecryptError> {
+    let block_size = block_cipher.block_size();
+    if let BlockCipherMode::CBCCS(..) = options.block_cipher_mode {
+        if options.padding != &Padding::None {
+            return Err(DecryptError::UnsupportedPadding);
+        }
+    }
+    let shortest_length = options.padding.padded_length(0, block_size);
+    if (options.padding != &Padding::None && buffer.len() % block_size != 0)
+        || buffer.len() < shortest_length {
+        return Err(DecryptError::InvalidLength { length: buffer.len(), block_size });
     }
 
-    for i in Nk..(Nb * (Nr + 1)) {
-        let mut temp = w[i - 1].to_vec();
-        if i % Nk == 0 {
-            let xored = xor::fixed_key_xor(
-                &sub_word(&rot_word(&temp)),
-                &Rcon[(i / Nk) - 1],
-            );
-            temp = xored;
-        } else if Nk > 6 && i % Nk == 4 {
-            temp = sub_word(&temp);
+    decrypt_unpadded_in_place(block_cipher, buffer, options.block_cipher_mode)?;
+
+    Ok(options.padding.unpadded_length(buffer, block_size)?)
+}
+
+fn decrypt_unpadded_in_place<C: BlockCipher>(
+    block_cipher: &C,
+    buffer: &mut [u8],
+    mode: &BlockCipherMode,
+) -> Result<(), DecryptError> {
+    let block_size = block_cipher.block_size();
+    if let Some(length) = invalid_iv_length(mode, block_size) {
+        return Err(DecryptError::InvalidIv { length, block_size });
+    }
+    if !fits_mode(mode, buffer.len(), block_size) {
+        return Err(DecryptError::InvalidLength { length: buffer.len(), block_size });
+    }
+
+    match mode {
+        BlockCipherMode::ECB => in_place::ecb_decrypt_in_place(block_cipher, buffer),
+        BlockCipherMode::CBC(iv) => {
+            in_place::cbc_decrypt_in_place(block_cipher, &iv.to_bytes(), buffer)
+        }
+        BlockCipherMode::CTR(nonce) => {
+            in_place::ctr_in_place(block_cipher, &CounterBlock::little_endian(&nonce[..]), buffer)
+        }
+        BlockCipherMode::OFB(iv) => in_place::ofb_in_place(block_cipher, &iv.to_bytes(), buffer),
+        BlockCipherMode::CFB1(iv) => {
+            in_place::cfb_decrypt_in_place(block_cipher, &iv.to_bytes(), 1, buffer)
+        }
+        BlockCipherMode::CFB8(iv) => {
+            in_place::cfb_decrypt_in_place(block_cipher, &iv.to_bytes(), 8, buffer)
+        }
+        BlockCipherMode::CFB128(iv) => {
+            in_place::cfb_decrypt_in_place(block_cipher, &iv.to_bytes(), 128, buffer)
+        }
+        BlockCipherMode::CBCCS(iv, variant) => {
+            in_place::cbc_cs_decrypt_in_place(block_cipher, &iv.to_bytes(), *variant, buffer)
         }
-        let key = xor::fixed_key_xor(&w[i - Nk][..], &temp);
-        w[i] = [key[0], key[1], key[2], key[3]];
     }
 
-    KeySchedule(w)
+    Ok(())
 }
 
-/// Function used in the Key Expansion routine that takes a four-byte
-/// word and performs a cyclic permutation.
-fn rot_word(word: &[u8]) -> Vec<u8> {
-    assert_eq!(word.len(), 4);
-
-    [&word[1..], &[word[0]]].concat()
+/// Whether bytes of the given length, once padded, can be processed in the mode: ECB and CBC
+/// require whole blocks rather than silently filling the last one with zeros, and ciphertext
+/// stealing at least one block.
+fn fits_mode(mode: &BlockCipherMode, length: usize, block_size: usize) -> bool {
+    match mode {
+        BlockCipherMode::ECB | BlockCipherMode::CBC(_) => length % block_size == 0,
+        BlockCipherMode::CBCCS(..) => length >= block_size,
+        _ => true,
+    }
 }
 
-/// Function used in the Key Expansion routine that takes a four-byte
-/// input word and applies an S-box to each of the four bytes to
-/// produce an output word.
-fn sub_word(word: &[u8]) -> Vec<u8> {
-    assert_eq!(word.len(), 4);
+/// Length of the iv or nonce of the mode, when the mode can't run on blocks of the given size
+/// with it: the iv must be a block long, and the nonce must leave room for the counter.
+fn invalid_iv_length(mode: &BlockCipherMode, block_size: usize) -> Option<usize> {
+    let (length, is_valid) = match mode {
+        BlockCipherMode::ECB => return None,
+        BlockCipherMode::CTR(nonce) => (nonce.len(), nonce.len() < block_size),
+        BlockCipherMode::CBC(_)
+        | BlockCipherMode::OFB(_)
+        | BlockCipherMode::CFB1(_)
+        | BlockCipherMode::CFB8(_)
+        | BlockCipherMode::CFB128(_)
+        | BlockCipherMode::CBCCS(..) => (4 * Nb, 4 * Nb == block_size),
+    };
 
-    word.iter().map(|word| S_BOX[*word as usize]).collect()
+    if is_valid {
+        None
+    } else {
+        Some(length)
+    }
 }
 
-/// see https://tools.ietf.org/html/rfc5652#section-6.3
-pub fn pkcs7_pad(bytes: &[u8], block_size: u8) -> Vec<u8> {
-    let mut pad_length = block_size - (bytes.len() as u8 % block_size);
+/// The words w[i] produced by the Key Expansion, Nb (Nr + 1) of them, and the size of the Cipher
+/// Key they were expanded from, which a truncated schedule can't tell from its length.
+pub struct KeySchedule(pub Vec<[u8; 4]>, KeySize);
 
-    if pad_length == 0 {
-        pad_length = block_size;
+impl KeySchedule {
+    /// Number of rounds the schedule holds Round Keys for (Nr).
+    pub fn rounds(&self) -> usize {
+        self.0.len() / Nb - 1
     }
 
-    [&bytes[..], &vec![pad_length; pad_length as usize][..]].concat()
-}
+    /// Nb words of the key schedule used as the Round Key of the given round.
+    pub fn round_key(&self, round: usize) -> &[[u8; 4]] {
+        &self.0[round * Nb..(round + 1) * Nb]
+    }
 
-#[derive(Debug, PartialEq)]
-pub enum PaddingError {
-    PaddingNotConsistent,
-    InvalidLastPaddingByte,
+    pub fn key_size(&self) -> KeySize {
+        self.1
+    }
+
+    /// Cipher Key the schedule was expanded from, its first Nk words.
+    pub fn key(&self) -> Key {
+        Key::new(&self.0[..self.1.nk()].concat())
+    }
 }
 
-pub fn validate_pkcs7_pad(bytes: &[u8], block_size: u8) -> Result<(), PaddingError> {
-    assert!(bytes.len() >= 2);
-    assert!(block_size >= 2);
+/// Routine used to generate a series of Round Keys from the Cipher Key.
+/// The Key Expansion generates a total of Nb (Nr + 1) words: the algorithm requires
+/// an initial set of Nk words, and each of the Nr rounds requires Nb words of key data. The
+/// resulting key schedule consists of a linear array of 4-byte words, denoted [wi ], with i in
+/// the range 0 <= i < Nb(Nr + 1).
+pub fn key_expansion(key: &Key) -> KeySchedule {
+    let key_size = key.size();
+    let nk = key_size.nk();
+    let nr = key_size.nr();
+    let mut w = vec![[0u8; 4]; Nb * (nr + 1)];
+
+    for i in 0..nk {
+        let key_part = &key.as_bytes()[4 * i..4 * i + 4];
+        w[i] = [key_part[0], key_part[1], key_part[2], key_part[3]];
+    }
 
-    let padding_length = *bytes.last().unwrap();
+    for i in nk..(Nb * (nr + 1)) {
+        let temp = key_expansion_temp(&w[i - 1], i, nk);
+        let key = xor::fixed_key_xor(&w[i - nk][..], &temp);
+        w[i] = [key[0], key[1], key[2], key[3]];
+    }
 
-    if padding_length == 0
-        || padding_length > block_size
-        || padding_length as usize > bytes.len() {
-        return Err(InvalidLastPaddingByte);
+    KeySchedule(w, key_size)
+}
+
+/// Runs the Key Expansion backwards, from the Round Key of the given round preceded by the words
+/// of the previous Round Keys needed to make up Nk words: 16 bytes for AES-128, 24 for AES-192
+/// and 32 for AES-256. Since w[i] = w[i - Nk] xor temp, where temp only depends on w[i - 1],
+/// every w[i - Nk] is recovered from the words which follow it, down to the Cipher Key.
+pub fn inverse_key_expansion(last_words: &[u8], round: usize, key_size: KeySize)
+                             -> KeySchedule {
+    let nk = key_size.nk();
+    assert_eq!(last_words.len(), 4 * nk, "The key schedule is inverted from {} words.", nk);
+    assert!(round <= key_size.nr() && Nb * (round + 1) >= nk,
+            "Round {} does not end {} words of the key schedule.", round, nk);
+
+    let end = Nb * (round + 1);
+    let mut w = vec![[0u8; 4]; end];
+    for (i, word) in last_words.chunks(4).enumerate() {
+        w[end - nk + i].copy_from_slice(word);
     }
 
-    let last_block = bytes.len() - padding_length as usize..;
-    let pad = &bytes[last_block];
+    for i in (nk..end).rev() {
+        let temp = key_expansion_temp(&w[i - 1], i, nk);
+        let key = xor::fixed_key_xor(&w[i][..], &temp);
+        w[i - nk] = [key[0], key[1], key[2], key[3]];
+    }
 
-    match pad.iter().all(|byte| *byte == padding_length) {
-        true => Ok(()),
-        false => Err(PaddingNotConsistent)
+    key_expansion(&Key::new(&w[..nk].concat()))
+}
+
+/// Word xored with w[i - Nk] to give w[i], computed from w[i - 1].
+fn key_expansion_temp(previous_word: &[u8; 4], i: usize, nk: usize) -> Vec<u8> {
+    if i % nk == 0 {
+        xor::fixed_key_xor(&sub_word(&rot_word(previous_word)), &Rcon[(i / nk) - 1])
+    } else if nk > 6 && i % nk == 4 {
+        sub_word(previous_word)
+    } else {
+        previous_word.to_vec()
     }
 }
 
-pub fn remove_pkcs7_padding(bytes: &[u8]) -> Vec<u8> {
-    let pad = *bytes.last().unwrap();
+/// Function used in the Key Expansion routine that takes a four-byte
+/// word and performs a cyclic permutation.
+fn rot_word(word: &[u8]) -> Vec<u8> {
+    assert_eq!(word.len(), 4);
+
+    [&word[1..], &[word[0]]].concat()
+}
+
+/// Function used in the Key Expansion routine that takes a four-byte
+/// input word and applies an S-box to each of the four bytes to
+/// produce an output word.
+fn sub_word(word: &[u8]) -> Vec<u8> {
+    assert_eq!(word.len(), 4);
 
-    bytes[..bytes.len() - pad as usize].to_vec()
+    word.iter().map(|word| S_BOX[*word as usize]).collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use aes::generate::generate_bytes_for_length;
-
-    #[test]
-    fn validate_pkcs7_pad_test() {
-        let block_size = 16;
-        let block = &generate_bytes_for_length(block_size);
-        let pad = &vec![block_size as u8; block_size];
-
-        let padded_block = &[
-            &block[..],
-            &pad[..]
-        ].concat();
-
-        assert!(validate_pkcs7_pad(padded_block, block_size as u8).is_ok());
-    }
+    use aes::bitsliced::BitslicedAes;
+    use mode::toy::ToyCipher;
 
     #[test]
     fn rot_word_test() {
@@ -417,10 +673,10 @@ mod tests {
         assert_eq!(actual_word.as_slice(), expected_word);
     }
 
-    #[test]
-    fn key_expansion_test() {
+    /// Cipher Key and expansion of FIPS-197 Appendix A.1.
+    fn fips_197_appendix_a_1() -> (Key, Vec<[u8; 4]>) {
         // as provided in official paper
-        let key = &Key([
+        let key = Key::new(&[
             0x2b, 0x7e, 0x15, 0x16,
             0x28, 0xae, 0xd2, 0xa6,
             0xab, 0xf7, 0x15, 0x88,
@@ -477,16 +733,230 @@ mod tests {
             [0xb6, 0x63, 0x0c, 0xa6]
         ];
 
+        (key, expected_key_schedule.to_vec())
+    }
+
+    #[test]
+    fn key_expansion_test() {
+        let (key, expected_key_schedule) = fips_197_appendix_a_1();
+
+        let actual_key_schedule = key_expansion(&key);
+
+        assert_eq!(actual_key_schedule.0.to_vec(), expected_key_schedule);
+    }
+
+    #[test]
+    fn inverse_key_expansion_fips_197_appendix_a_1() {
+        let (key, expected_key_schedule) = fips_197_appendix_a_1();
+
+        for round in 0..=10 {
+            let round_key = &expected_key_schedule[4 * round..4 * round + 4].concat();
+
+            let key_schedule = inverse_key_expansion(round_key, round, KeySize::AES128);
+
+            assert_eq!(key_schedule.0, expected_key_schedule);
+            assert_eq!(key_schedule.key(), key);
+        }
+    }
+
+    #[test]
+    fn inverse_key_expansion_of_192_and_256_bit_keys() {
+        for key_size in [KeySize::AES192, KeySize::AES256].iter() {
+            let key = Key::new(&generate_bytes_for_length(key_size.length()));
+            let w = key_expansion(&key).0;
+            let nk = key_size.nk();
+
+            // from the first round whose Round Key ends Nk words
+            for round in (nk + Nb - 1) / Nb - 1..=key_size.nr() {
+                let end = Nb * (round + 1);
+                let last_words = &w[end - nk..end].concat();
+
+                let key_schedule = inverse_key_expansion(last_words, round, *key_size);
+
+                assert_eq!(key_schedule.key(), key);
+                assert_eq!(key_schedule.0, w);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_key_schedules_keep_their_key() {
+        for key_length in [16, 24, 32].iter() {
+            let key = Key::new(&generate::generate_bytes_for_length(*key_length));
+
+            for rounds in 1..=key.size().nr() {
+                let mut key_schedule = key_expansion(&key);
+                key_schedule.0.truncate(Nb * (rounds + 1));
+
+                assert_eq!(key_schedule.rounds(), rounds);
+                assert_eq!(key_schedule.key_size(), key.size());
+                assert_eq!(key_schedule.key(), key);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "does not end 6 words")]
+    fn inverse_key_expansion_requires_nk_words_up_to_the_round() {
+        inverse_key_expansion(&[0; 24], 0, KeySize::AES192);
+    }
+
+    #[test]
+    fn key_expansion_192_test() {
+        // as provided in official paper, appendix A.2
+        let key = &Key::new(&[
+            0x8e, 0x73, 0xb0, 0xf7,
+            0xda, 0x0e, 0x64, 0x52,
+            0xc8, 0x10, 0xf3, 0x2b,
+            0x80, 0x90, 0x79, 0xe5,
+            0x62, 0xf8, 0xea, 0xd2,
+            0x52, 0x2c, 0x6b, 0x7b
+        ]);
+        let expected_key_schedule: [[u8; 4]; 52] = [
+            // copy of key
+            [0x8e, 0x73, 0xb0, 0xf7],
+            [0xda, 0x0e, 0x64, 0x52],
+            [0xc8, 0x10, 0xf3, 0x2b],
+            [0x80, 0x90, 0x79, 0xe5],
+            [0x62, 0xf8, 0xea, 0xd2],
+            [0x52, 0x2c, 0x6b, 0x7b],
+
+            // rest of expansion
+            [0xfe, 0x0c, 0x91, 0xf7],
+            [0x24, 0x02, 0xf5, 0xa5],
+            [0xec, 0x12, 0x06, 0x8e],
+            [0x6c, 0x82, 0x7f, 0x6b],
+            [0x0e, 0x7a, 0x95, 0xb9],
+            [0x5c, 0x56, 0xfe, 0xc2],
+            [0x4d, 0xb7, 0xb4, 0xbd],
+            [0x69, 0xb5, 0x41, 0x18],
+            [0x85, 0xa7, 0x47, 0x96],
+            [0xe9, 0x25, 0x38, 0xfd],
+            [0xe7, 0x5f, 0xad, 0x44],
+            [0xbb, 0x09, 0x53, 0x86],
+            [0x48, 0x5a, 0xf0, 0x57],
+            [0x21, 0xef, 0xb1, 0x4f],
+            [0xa4, 0x48, 0xf6, 0xd9],
+            [0x4d, 0x6d, 0xce, 0x24],
+            [0xaa, 0x32, 0x63, 0x60],
+            [0x11, 0x3b, 0x30, 0xe6],
+            [0xa2, 0x5e, 0x7e, 0xd5],
+            [0x83, 0xb1, 0xcf, 0x9a],
+            [0x27, 0xf9, 0x39, 0x43],
+            [0x6a, 0x94, 0xf7, 0x67],
+            [0xc0, 0xa6, 0x94, 0x07],
+            [0xd1, 0x9d, 0xa4, 0xe1],
+            [0xec, 0x17, 0x86, 0xeb],
+            [0x6f, 0xa6, 0x49, 0x71],
+            [0x48, 0x5f, 0x70, 0x32],
+            [0x22, 0xcb, 0x87, 0x55],
+            [0xe2, 0x6d, 0x13, 0x52],
+            [0x33, 0xf0, 0xb7, 0xb3],
+            [0x40, 0xbe, 0xeb, 0x28],
+            [0x2f, 0x18, 0xa2, 0x59],
+            [0x67, 0x47, 0xd2, 0x6b],
+            [0x45, 0x8c, 0x55, 0x3e],
+            [0xa7, 0xe1, 0x46, 0x6c],
+            [0x94, 0x11, 0xf1, 0xdf],
+            [0x82, 0x1f, 0x75, 0x0a],
+            [0xad, 0x07, 0xd7, 0x53],
+            [0xca, 0x40, 0x05, 0x38],
+            [0x8f, 0xcc, 0x50, 0x06],
+            [0x28, 0x2d, 0x16, 0x6a],
+            [0xbc, 0x3c, 0xe7, 0xb5],
+            [0xe9, 0x8b, 0xa0, 0x6f],
+            [0x44, 0x8c, 0x77, 0x3c],
+            [0x8e, 0xcc, 0x72, 0x04],
+            [0x01, 0x00, 0x22, 0x02]
+        ];
+
         let actual_key_schedule = key_expansion(key);
 
         assert_eq!(actual_key_schedule.0.to_vec(), expected_key_schedule.to_vec());
     }
 
     #[test]
-    fn generate_ctr_bytes_for_length_test() {
-        // TODO(nich): Implement this
+    fn key_expansion_256_test() {
+        // as provided in official paper, appendix A.3
+        let key = &Key::new(&[
+            0x60, 0x3d, 0xeb, 0x10,
+            0x15, 0xca, 0x71, 0xbe,
+            0x2b, 0x73, 0xae, 0xf0,
+            0x85, 0x7d, 0x77, 0x81,
+            0x1f, 0x35, 0x2c, 0x07,
+            0x3b, 0x61, 0x08, 0xd7,
+            0x2d, 0x98, 0x10, 0xa3,
+            0x09, 0x14, 0xdf, 0xf4
+        ]);
+        let expected_key_schedule: [[u8; 4]; 60] = [
+            // copy of key
+            [0x60, 0x3d, 0xeb, 0x10],
+            [0x15, 0xca, 0x71, 0xbe],
+            [0x2b, 0x73, 0xae, 0xf0],
+            [0x85, 0x7d, 0x77, 0x81],
+            [0x1f, 0x35, 0x2c, 0x07],
+            [0x3b, 0x61, 0x08, 0xd7],
+            [0x2d, 0x98, 0x10, 0xa3],
+            [0x09, 0x14, 0xdf, 0xf4],
 
-        assert_eq!(false, true);
+            // rest of expansion
+            [0x9b, 0xa3, 0x54, 0x11],
+            [0x8e, 0x69, 0x25, 0xaf],
+            [0xa5, 0x1a, 0x8b, 0x5f],
+            [0x20, 0x67, 0xfc, 0xde],
+            [0xa8, 0xb0, 0x9c, 0x1a],
+            [0x93, 0xd1, 0x94, 0xcd],
+            [0xbe, 0x49, 0x84, 0x6e],
+            [0xb7, 0x5d, 0x5b, 0x9a],
+            [0xd5, 0x9a, 0xec, 0xb8],
+            [0x5b, 0xf3, 0xc9, 0x17],
+            [0xfe, 0xe9, 0x42, 0x48],
+            [0xde, 0x8e, 0xbe, 0x96],
+            [0xb5, 0xa9, 0x32, 0x8a],
+            [0x26, 0x78, 0xa6, 0x47],
+            [0x98, 0x31, 0x22, 0x29],
+            [0x2f, 0x6c, 0x79, 0xb3],
+            [0x81, 0x2c, 0x81, 0xad],
+            [0xda, 0xdf, 0x48, 0xba],
+            [0x24, 0x36, 0x0a, 0xf2],
+            [0xfa, 0xb8, 0xb4, 0x64],
+            [0x98, 0xc5, 0xbf, 0xc9],
+            [0xbe, 0xbd, 0x19, 0x8e],
+            [0x26, 0x8c, 0x3b, 0xa7],
+            [0x09, 0xe0, 0x42, 0x14],
+            [0x68, 0x00, 0x7b, 0xac],
+            [0xb2, 0xdf, 0x33, 0x16],
+            [0x96, 0xe9, 0x39, 0xe4],
+            [0x6c, 0x51, 0x8d, 0x80],
+            [0xc8, 0x14, 0xe2, 0x04],
+            [0x76, 0xa9, 0xfb, 0x8a],
+            [0x50, 0x25, 0xc0, 0x2d],
+            [0x59, 0xc5, 0x82, 0x39],
+            [0xde, 0x13, 0x69, 0x67],
+            [0x6c, 0xcc, 0x5a, 0x71],
+            [0xfa, 0x25, 0x63, 0x95],
+            [0x96, 0x74, 0xee, 0x15],
+            [0x58, 0x86, 0xca, 0x5d],
+            [0x2e, 0x2f, 0x31, 0xd7],
+            [0x7e, 0x0a, 0xf1, 0xfa],
+            [0x27, 0xcf, 0x73, 0xc3],
+            [0x74, 0x9c, 0x47, 0xab],
+            [0x18, 0x50, 0x1d, 0xda],
+            [0xe2, 0x75, 0x7e, 0x4f],
+            [0x74, 0x01, 0x90, 0x5a],
+            [0xca, 0xfa, 0xaa, 0xe3],
+            [0xe4, 0xd5, 0x9b, 0x34],
+            [0x9a, 0xdf, 0x6a, 0xce],
+            [0xbd, 0x10, 0x19, 0x0d],
+            [0xfe, 0x48, 0x90, 0xd1],
+            [0xe6, 0x18, 0x8d, 0x0b],
+            [0x04, 0x6d, 0xf3, 0x44],
+            [0x70, 0x6c, 0x63, 0x1e]
+        ];
+
+        let actual_key_schedule = key_expansion(key);
+
+        assert_eq!(actual_key_schedule.0.to_vec(), expected_key_schedule.to_vec());
     }
 
     #[test]
@@ -497,7 +967,7 @@ mod tests {
             0xd8, 0xcd, 0xb7, 0x80,
             0x70, 0xb4, 0xc5, 0x5a
         ];
-        let key = &Key([
+        let key = &Key::new(&[
             0x00, 0x01, 0x02, 0x03,
             0x04, 0x05, 0x06, 0x07,
             0x08, 0x09, 0x0a, 0x0b,
@@ -509,7 +979,8 @@ mod tests {
             0x88, 0x99, 0xaa, 0xbb,
             0xcc, 0xdd, 0xee, 0xff
         ];
-        let actual_raw = decrypt_aes_128(&cipher, &key, &BlockCipherMode::ECB);
+        let actual_raw = decrypt_aes_128(&cipher, &key, &AESEncryptionOptions::default())
+            .unwrap();
 
         assert_eq!(actual_raw, expected_raw);
     }
@@ -522,7 +993,7 @@ mod tests {
             0x88, 0x99, 0xaa, 0xbb,
             0xcc, 0xdd, 0xee, 0xff
         ];
-        let key = &Key([
+        let key = &Key::new(&[
             0x00, 0x01, 0x02, 0x03,
             0x04, 0x05, 0x06, 0x07,
             0x08, 0x09, 0x0a, 0x0b,
@@ -538,11 +1009,71 @@ mod tests {
             &raw,
             &key,
             &AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::None),
-        );
+        ).unwrap();
 
         assert_eq!(actual_cipher, expected_cipher);
     }
 
+    #[test]
+    fn encrypt_and_decrypt_in_ecb_mode_nist_appendix_c_test_cases() {
+        struct TestCase {
+            key: Vec<u8>,
+            expected_cipher: Vec<u8>,
+        }
+        let raw: &[u8] = &[
+            0x00, 0x11, 0x22, 0x33,
+            0x44, 0x55, 0x66, 0x77,
+            0x88, 0x99, 0xaa, 0xbb,
+            0xcc, 0xdd, 0xee, 0xff
+        ];
+        let test_cases: Vec<TestCase> = vec![
+            // C.1 AES-128 (Nk=4, Nr=10)
+            TestCase {
+                key: (0x00..0x10).collect(),
+                expected_cipher: vec![
+                    0x69, 0xc4, 0xe0, 0xd8,
+                    0x6a, 0x7b, 0x04, 0x30,
+                    0xd8, 0xcd, 0xb7, 0x80,
+                    0x70, 0xb4, 0xc5, 0x5a
+                ],
+            },
+            // C.2 AES-192 (Nk=6, Nr=12)
+            TestCase {
+                key: (0x00..0x18).collect(),
+                expected_cipher: vec![
+                    0xdd, 0xa9, 0x7c, 0xa4,
+                    0x86, 0x4c, 0xdf, 0xe0,
+                    0x6e, 0xaf, 0x70, 0xa0,
+                    0xec, 0x0d, 0x71, 0x91
+                ],
+            },
+            // C.3 AES-256 (Nk=8, Nr=14)
+            TestCase {
+                key: (0x00..0x20).collect(),
+                expected_cipher: vec![
+                    0x8e, 0xa2, 0xb7, 0xca,
+                    0x51, 0x67, 0x45, 0xbf,
+                    0xea, 0xfc, 0x49, 0x90,
+                    0x4b, 0x49, 0x60, 0x89
+                ],
+            },
+        ];
+
+        for case in test_cases.iter() {
+            let key = &Key::new(&case.key);
+            let actual_cipher = encrypt_aes_128(
+                &raw,
+                key,
+                &AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::None),
+            ).unwrap();
+            let actual_raw = decrypt_aes_128(&actual_cipher, key, &AESEncryptionOptions::default())
+                .unwrap();
+
+            assert_eq!(actual_cipher, case.expected_cipher);
+            assert_eq!(actual_raw, raw);
+        }
+    }
+
     #[test]
     fn encrypt_and_decrypt_ecb() {
         let raw: &[u8] = &[
@@ -551,7 +1082,7 @@ mod tests {
             0x88, 0x99, 0xaa, 0xbb,
             0xcc, 0xdd, 0xee, 0xff,
         ];
-        let key = &Key([
+        let key = &Key::new(&[
             0x00, 0x01, 0x02, 0x03,
             0x04, 0x05, 0x06, 0x07,
             0x08, 0x09, 0x0a, 0x0b,
@@ -561,8 +1092,9 @@ mod tests {
             &raw,
             &key,
             &AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::None),
-        );
-        let actual_deciphered = decrypt_aes_128(&cipher, &key, &BlockCipherMode::ECB);
+        ).unwrap();
+        let actual_deciphered = decrypt_aes_128(&cipher, &key, &AESEncryptionOptions::default())
+            .unwrap();
 
         assert_eq!(raw, &actual_deciphered[..]);
     }
@@ -580,7 +1112,7 @@ mod tests {
             0x88, 0x99, 0xaa, 0xbb,
             0xcc, 0xdd, 0xee, 0xff,
         ];
-        let key = &Key([
+        let key = &Key::new(&[
             0x00, 0x01, 0x02, 0x03,
             0x04, 0x05, 0x06, 0x07,
             0x08, 0x09, 0x0a, 0x0b,
@@ -593,8 +1125,12 @@ mod tests {
             &raw,
             key,
             &AESEncryptionOptions::new(&BlockCipherMode::CBC(iv), &Padding::None),
-        );
-        let actual_deciphered = decrypt_aes_128(&cipher, key, &BlockCipherMode::CBC(iv));
+        ).unwrap();
+        let actual_deciphered = decrypt_aes_128(
+            &cipher,
+            key,
+            &AESEncryptionOptions::new(&BlockCipherMode::CBC(iv), &Padding::None),
+        ).unwrap();
 
         assert_eq!(raw, &actual_deciphered[..]);
     }
@@ -627,9 +1163,281 @@ mod tests {
         let mode = BlockCipherMode::CTR(&[0u8; 8]);
         let options = &AESEncryptionOptions::new(&mode, &Padding::None);
 
-        let ciphered = encrypt_aes_128(&raw, &key, &options);
-        let deciphered = encrypt_aes_128(&ciphered, &key, &options);
+        let ciphered = encrypt_aes_128(&raw, &key, &options).unwrap();
+        let deciphered = encrypt_aes_128(&ciphered, &key, &options).unwrap();
 
         assert_eq!(deciphered, raw);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn encrypt_and_decrypt_ofb_and_cfb() {
+        let raw = "OFB and CFB need no padding, even for 46 bytes".as_bytes();
+        let key = Key::new_from_string("YELLOW SUBMARINE");
+        let iv = &generate::generate_aes_128_cbc_iv();
+        let modes = [
+            BlockCipherMode::OFB(iv),
+            BlockCipherMode::CFB1(iv),
+            BlockCipherMode::CFB8(iv),
+            BlockCipherMode::CFB128(iv),
+        ];
+
+        for mode in modes.iter() {
+            let options = &AESEncryptionOptions::new(mode, &Padding::None);
+            let cipher = encrypt_aes_128(raw, &key, options).unwrap();
+            let deciphered = decrypt_aes_128(&cipher, &key, options).unwrap();
+
+            assert_eq!(cipher.len(), raw.len());
+            assert_eq!(&deciphered[..], raw);
+        }
+    }
+
+    #[test]
+    fn decrypt_removes_pkcs7_padding() {
+        let raw = "YELLOW SUBMARINE, padded".as_bytes();
+        let key = Key::new_from_string("YELLOW SUBMARINE");
+        let iv = &generate::generate_aes_128_cbc_iv();
+        let mode = &BlockCipherMode::CBC(iv);
+        let options = &AESEncryptionOptions::new(mode, &Padding::PKCS7);
+
+        let cipher = encrypt_aes_128(raw, &key, options).unwrap();
+        let deciphered = decrypt_aes_128(&cipher, &key, options);
+
+        assert_eq!(cipher.len(), 32);
+        assert_eq!(deciphered, Ok(raw.to_vec()));
+    }
+
+    #[test]
+    fn decrypt_returns_errors_instead_of_garbage() {
+        let key = Key::new_from_string("YELLOW SUBMARINE");
+        let padded = &AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::PKCS7);
+        let unpadded = &AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::None);
+        let raw = &[&[0x05; 15][..], &[0x04][..]].concat();
+        let cipher = encrypt_aes_128(raw, &key, unpadded).unwrap();
+
+        assert_eq!(decrypt_aes_128(&cipher, &key, padded),
+                   Err(DecryptError::Padding(PaddingError::PaddingNotConsistent)));
+        assert_eq!(decrypt_aes_128(&cipher[..15], &key, unpadded),
+                   Err(DecryptError::InvalidLength { length: 15, block_size: 16 }));
+        assert_eq!(decrypt_aes_128(&[], &key, padded),
+                   Err(DecryptError::InvalidLength { length: 0, block_size: 16 }));
+    }
+
+    #[test]
+    fn empty_ciphers_only_decrypt_with_zero_padding_or_none() {
+        let block_cipher = Aes::new(&Key::new_from_string("YELLOW SUBMARINE"));
+        let paddings = [
+            (Padding::PKCS7, false),
+            (Padding::AnsiX923, false),
+            (Padding::Iso10126, false),
+            (Padding::Iso7816, false),
+            (Padding::Zero, true),
+            (Padding::None, true),
+        ];
+
+        for (padding, allows_empty_ciphers) in paddings.iter() {
+            let options = &AESEncryptionOptions::new(&BlockCipherMode::ECB, padding);
+            let expected = match allows_empty_ciphers {
+                true => Ok(vec![]),
+                false => Err(DecryptError::InvalidLength { length: 0, block_size: 16 }),
+            };
+
+            assert_eq!(decrypt(&block_cipher, &[], options), expected);
+            assert_eq!(decrypt_padded_in_place(&block_cipher, &mut [], options),
+                       expected.map(|deciphered| deciphered.len()));
+        }
+    }
+
+    #[test]
+    fn modes_reject_ivs_and_nonces_of_other_block_sizes() {
+        let block_cipher = ToyCipher::new(0x0123456789abcdef);
+        let iv = &generate::generate_aes_128_cbc_iv();
+        let (cbc, ctr) = (&BlockCipherMode::CBC(iv), &BlockCipherMode::CTR(&[0; 8]));
+        let cbc = &AESEncryptionOptions::new(cbc, &Padding::PKCS7);
+        let ctr = &AESEncryptionOptions::new(ctr, &Padding::None);
+        let ecb = &AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::PKCS7);
+
+        assert_eq!(encrypt(&block_cipher, &[0; 16], cbc),
+                   Err(EncryptError::InvalidIv { length: 16, block_size: 8 }));
+        assert_eq!(decrypt(&block_cipher, &[0; 16], cbc),
+                   Err(DecryptError::InvalidIv { length: 16, block_size: 8 }));
+        assert_eq!(decrypt(&block_cipher, &[0; 16], ctr),
+                   Err(DecryptError::InvalidIv { length: 8, block_size: 8 }));
+        assert_eq!(decrypt_in_place(&block_cipher, &mut [0; 16], ctr),
+                   Err(DecryptError::InvalidIv { length: 8, block_size: 8 }));
+        // without an iv, any block size will do
+        let cipher = encrypt(&block_cipher, &[0x2a; 13], ecb).unwrap();
+        assert_eq!(decrypt(&block_cipher, &cipher, ecb), Ok(vec![0x2a; 13]));
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_with_ciphertext_stealing() {
+        let raw = "not a multiple of the block size".as_bytes();
+        let key = Key::new_from_string("YELLOW SUBMARINE");
+        let iv = &generate::generate_aes_128_cbc_iv();
+        let variants = [CiphertextStealing::CS1, CiphertextStealing::CS2, CiphertextStealing::CS3];
+
+        for variant in variants.iter() {
+            let mode = &BlockCipherMode::CBCCS(iv, *variant);
+            let options = &AESEncryptionOptions::new(mode, &Padding::None);
+
+            let cipher = encrypt_aes_128(&raw[..30], &key, options).unwrap();
+            let deciphered = decrypt_aes_128(&cipher, &key, options).unwrap();
+
+            assert_eq!(cipher.len(), 30);
+            assert_eq!(&deciphered[..], &raw[..30]);
+        }
+    }
+
+    #[test]
+    fn unpadded_ecb_and_cbc_reject_partial_blocks() {
+        let key = Key::new_from_string("YELLOW SUBMARINE");
+        let iv = &generate::generate_aes_128_cbc_iv();
+
+        for mode in [BlockCipherMode::ECB, BlockCipherMode::CBC(iv)].iter() {
+            let options = &AESEncryptionOptions::new(mode, &Padding::None);
+
+            assert_eq!(encrypt_aes_128(&[0x2a; 20], &key, options),
+                       Err(EncryptError::InvalidLength { length: 20, block_size: 16 }));
+            let cipher = encrypt_aes_128(&[0x2a; 32], &key, options).unwrap();
+            assert_eq!(decrypt_aes_128(&cipher, &key, options), Ok(vec![0x2a; 32]));
+        }
+    }
+
+    #[test]
+    fn ciphertext_stealing_rejects_short_or_padded_inputs() {
+        let key = Key::new_from_string("YELLOW SUBMARINE");
+        let iv = &generate::generate_aes_128_cbc_iv();
+        let mode = &BlockCipherMode::CBCCS(iv, CiphertextStealing::CS3);
+        let options = &AESEncryptionOptions::new(mode, &Padding::None);
+        let padded = &AESEncryptionOptions::new(mode, &Padding::PKCS7);
+
+        assert_eq!(encrypt_aes_128(&[0; 15], &key, options),
+                   Err(EncryptError::InvalidLength { length: 15, block_size: 16 }));
+        assert_eq!(decrypt_aes_128(&[0; 15], &key, options),
+                   Err(DecryptError::InvalidLength { length: 15, block_size: 16 }));
+        assert_eq!(encrypt_aes_128(&[0; 20], &key, padded), Err(EncryptError::UnsupportedPadding));
+        assert_eq!(decrypt_aes_128(&[0; 32], &key, padded), Err(DecryptError::UnsupportedPadding));
+    }
+
+    /// Every mode, with the iv and nonce of the in-place tests.
+    fn all_modes<'a>(iv: &'a Iv, nonce: &'a Nonce) -> Vec<BlockCipherMode<'a>> {
+        vec![
+            BlockCipherMode::ECB,
+            BlockCipherMode::CBC(iv),
+            BlockCipherMode::CTR(nonce),
+            BlockCipherMode::OFB(iv),
+            BlockCipherMode::CFB1(iv),
+            BlockCipherMode::CFB8(iv),
+            BlockCipherMode::CFB128(iv),
+            BlockCipherMode::CBCCS(iv, CiphertextStealing::CS1),
+            BlockCipherMode::CBCCS(iv, CiphertextStealing::CS2),
+            BlockCipherMode::CBCCS(iv, CiphertextStealing::CS3),
+        ]
+    }
+
+    #[test]
+    fn in_place_matches_encrypt_and_decrypt_in_every_mode() {
+        let block_cipher = Aes::new(&Key::new_from_string("YELLOW SUBMARINE"));
+        let iv = &generate::generate_aes_128_cbc_iv();
+        let nonce = &[0x17; 8];
+        let raw = &generate_bytes_for_length(48);
+
+        for mode in all_modes(iv, nonce).iter() {
+            let options = &AESEncryptionOptions::new(mode, &Padding::None);
+            let buffer = &mut raw.clone();
+
+            encrypt_in_place(&block_cipher, buffer, options).unwrap();
+            assert_eq!(buffer, &encrypt(&block_cipher, raw, options).unwrap());
+
+            decrypt_in_place(&block_cipher, buffer, options).unwrap();
+            assert_eq!(buffer, raw);
+        }
+    }
+
+    #[test]
+    fn bitsliced_aes_matches_aes_in_every_mode() {
+        let key = &Key::new_from_string("YELLOW SUBMARINE");
+        let (aes, bitsliced) = (Aes::new(key), BitslicedAes::new(key));
+        let iv = &generate::generate_aes_128_cbc_iv();
+        let nonce = &[0x17; 8];
+        let raw = &generate_bytes_for_length(200);
+
+        for mode in all_modes(iv, nonce).iter() {
+            let padding = match mode {
+                BlockCipherMode::CBCCS(..) => &Padding::None,
+                _ => &Padding::PKCS7,
+            };
+            let options = &AESEncryptionOptions::new(mode, padding);
+
+            let cipher = encrypt(&bitsliced, raw, options).unwrap();
+            assert_eq!(cipher, encrypt(&aes, raw, options).unwrap());
+            assert_eq!(&decrypt(&bitsliced, &cipher, options).unwrap(), raw);
+
+            let buffer = &mut [0u8; 208];
+            buffer[..raw.len()].copy_from_slice(raw);
+            let cipher_length = encrypt_padded_in_place(&bitsliced, buffer, raw.len(), options)
+                .unwrap();
+            assert_eq!(&buffer[..cipher_length], &cipher[..]);
+        }
+    }
+
+    #[test]
+    fn padded_in_place_matches_encrypt_and_decrypt() {
+        let block_cipher = Aes::new(&Key::new_from_string("YELLOW SUBMARINE"));
+        let iv = &generate::generate_aes_128_cbc_iv();
+        let nonce = &[0x17; 8];
+        let raw = "padded in place, with room to spare".as_bytes();
+
+        for mode in all_modes(iv, nonce).iter() {
+            let padding = match mode {
+                BlockCipherMode::CBCCS(..) => &Padding::None,
+                _ => &Padding::PKCS7,
+            };
+            let options = &AESEncryptionOptions::new(mode, padding);
+            let buffer = &mut [0u8; 64];
+            buffer[..raw.len()].copy_from_slice(raw);
+
+            let cipher_length = encrypt_padded_in_place(&block_cipher, buffer, raw.len(), options)
+                .unwrap();
+            let expected_cipher = encrypt(&block_cipher, raw, options).unwrap();
+            assert_eq!(&buffer[..cipher_length], &expected_cipher[..]);
+
+            let raw_length = decrypt_padded_in_place(&block_cipher, &mut buffer[..cipher_length],
+                                                     options).unwrap();
+            assert_eq!(&buffer[..raw_length], raw);
+        }
+    }
+
+    #[test]
+    fn in_place_rejects_what_it_can_not_process() {
+        let block_cipher = Aes::new(&Key::new_from_string("YELLOW SUBMARINE"));
+        let iv = &generate::generate_aes_128_cbc_iv();
+        let ecb = &AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::None);
+        let cbc = &BlockCipherMode::CBC(iv);
+        let padded_cbc = &AESEncryptionOptions::new(cbc, &Padding::PKCS7);
+        let cs = &BlockCipherMode::CBCCS(iv, CiphertextStealing::CS3);
+        let padded_cs = &AESEncryptionOptions::new(cs, &Padding::PKCS7);
+
+        assert_eq!(encrypt_in_place(&block_cipher, &mut [0; 20], ecb),
+                   Err(EncryptError::InvalidLength { length: 20, block_size: 16 }));
+        assert_eq!(decrypt_in_place(&block_cipher, &mut [0; 20], ecb),
+                   Err(DecryptError::InvalidLength { length: 20, block_size: 16 }));
+        assert_eq!(encrypt_in_place(&block_cipher, &mut [0; 32], padded_cbc),
+                   Err(EncryptError::UnsupportedPadding));
+        assert_eq!(decrypt_in_place(&block_cipher, &mut [0; 32], padded_cbc),
+                   Err(DecryptError::UnsupportedPadding));
+        assert_eq!(encrypt_padded_in_place(&block_cipher, &mut [0; 32], 20, padded_cs),
+                   Err(EncryptError::UnsupportedPadding));
+        // a whole block of padding needs a whole block of room
+        assert_eq!(encrypt_padded_in_place(&block_cipher, &mut [0; 20], 16, padded_cbc),
+                   Err(EncryptError::BufferTooSmall { length: 20, required_length: 32 }));
+        assert_eq!(decrypt_padded_in_place(&block_cipher, &mut [0; 20], padded_cbc),
+                   Err(DecryptError::InvalidLength { length: 20, block_size: 16 }));
+
+        let padded_ecb = &AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::PKCS7);
+        let buffer = &mut [0u8; 16];
+        encrypt_in_place(&block_cipher, buffer, ecb).unwrap();
+        assert_eq!(decrypt_padded_in_place(&block_cipher, buffer, padded_ecb),
+                   Err(DecryptError::Padding(PaddingError::InvalidLastPaddingByte)));
+    }
+}
@@ -0,0 +1,316 @@
+/// Resources used:
+/// https://tools.ietf.org/html/rfc5652#section-6.3
+/// https://en.wikipedia.org/wiki/Padding_(cryptography)#Byte_padding
+
+use aes::generate::generate_bytes_for_length;
+use self::PaddingError::{PaddingNotConsistent, InvalidLastPaddingByte};
+
+/// Padding rule extending the plaintext to a whole number of blocks.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Padding {
+    /// Every padding byte is the padding length.
+    PKCS7,
+    /// Zeros, then a last byte holding the padding length.
+    AnsiX923,
+    /// Random bytes, then a last byte holding the padding length.
+    Iso10126,
+    /// A 0x80 byte (a single one bit), then zeros.
+    Iso7816,
+    /// Zeros up to the end of the block, nothing when the plaintext already fills it. Trailing
+    /// zeros of the plaintext itself can't be told apart from the padding.
+    Zero,
+    None,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PaddingError {
+    PaddingNotConsistent,
+    InvalidLastPaddingByte,
+}
+
+impl Padding {
+    /// Pads the bytes to a whole number of blocks. The padding rules writing the padding length in
+    /// the last byte only support blocks of up to 255 bytes.
+    pub fn pad(&self, bytes: &[u8], block_size: usize) -> Vec<u8> {
+        let mut padded = bytes.to_vec();
+        padded.resize(self.padded_length(bytes.len(), block_size), 0);
+        self.pad_in_place(&mut padded, bytes.len(), block_size);
+
+        padded
+    }
+
+    /// Length of the given number of bytes once padded.
+    pub fn padded_length(&self, length: usize, block_size: usize) -> usize {
+        match self {
+            Padding::Zero => (length + block_size - 1) / block_size * block_size,
+            Padding::None => length,
+            _ => (length / block_size + 1) * block_size,
+        }
+    }
+
+    /// Writes the padding of the first length bytes of the buffer right after them, and returns
+    /// the padded length. The buffer must have room for the padding.
+    pub fn pad_in_place(&self, buffer: &mut [u8], length: usize, block_size: usize) -> usize {
+        let padded_length = self.padded_length(length, block_size);
+        assert!(buffer.len() >= padded_length, "The buffer has no room for the padding.");
+        let pad = &mut buffer[length..padded_length];
+        let pad_length = pad.len();
+
+        match self {
+            Padding::PKCS7 => pad.iter_mut().for_each(|byte| *byte = length_byte(pad_length)),
+            Padding::AnsiX923 => {
+                pad.iter_mut().for_each(|byte| *byte = 0);
+                pad[pad_length - 1] = length_byte(pad_length);
+            }
+            Padding::Iso10126 => {
+                pad[..pad_length - 1].copy_from_slice(&generate_bytes_for_length(pad_length - 1));
+                pad[pad_length - 1] = length_byte(pad_length);
+            }
+            Padding::Iso7816 => {
+                pad.iter_mut().for_each(|byte| *byte = 0);
+                pad[0] = 0x80;
+            }
+            Padding::Zero => pad.iter_mut().for_each(|byte| *byte = 0),
+            Padding::None => {}
+        }
+
+        padded_length
+    }
+
+    /// Checks that the bytes end with a padding following the rule.
+    pub fn validate(&self, bytes: &[u8], block_size: usize) -> Result<(), PaddingError> {
+        self.pad_length(bytes, block_size).map(|_| ())
+    }
+
+    /// Removes the padding of the bytes, once it has been validated.
+    pub fn unpad(&self, bytes: &[u8], block_size: usize) -> Result<Vec<u8>, PaddingError> {
+        let pad_length = self.pad_length(bytes, block_size)?;
+
+        Ok(bytes[..bytes.len() - pad_length].to_vec())
+    }
+
+    /// Length of the bytes once their padding, validated, is removed.
+    pub fn unpadded_length(&self, bytes: &[u8], block_size: usize) -> Result<usize, PaddingError> {
+        Ok(bytes.len() - self.pad_length(bytes, block_size)?)
+    }
+
+    fn pad_length(&self, bytes: &[u8], block_size: usize) -> Result<usize, PaddingError> {
+        match self {
+            Padding::PKCS7 => {
+                validate_pkcs7_pad(bytes, block_size)?;
+
+                Ok(*bytes.last().unwrap() as usize)
+            }
+            Padding::AnsiX923 => {
+                let pad_length = last_byte_pad_length(bytes, block_size)?;
+                let fill = &bytes[bytes.len() - pad_length..bytes.len() - 1];
+
+                match fill.iter().all(|byte| *byte == 0) {
+                    true => Ok(pad_length),
+                    false => Err(PaddingNotConsistent),
+                }
+            }
+            Padding::Iso10126 => last_byte_pad_length(bytes, block_size),
+            Padding::Iso7816 => {
+                if bytes.is_empty() {
+                    return Err(InvalidLastPaddingByte);
+                }
+                let zeros = bytes.iter().rev().take_while(|byte| **byte == 0).count();
+
+                if zeros >= block_size || zeros == bytes.len() {
+                    Err(PaddingNotConsistent)
+                } else if bytes[bytes.len() - zeros - 1] != 0x80 {
+                    match zeros {
+                        0 => Err(InvalidLastPaddingByte),
+                        _ => Err(PaddingNotConsistent),
+                    }
+                } else {
+                    Ok(zeros + 1)
+                }
+            }
+            Padding::Zero => {
+                let zeros = bytes.iter().rev().take_while(|byte| **byte == 0).count();
+
+                Ok(zeros.min(block_size - 1))
+            }
+            Padding::None => Ok(0),
+        }
+    }
+}
+
+fn length_byte(pad_length: usize) -> u8 {
+    assert!(pad_length <= 255, "The padding length must fit in a byte.");
+
+    pad_length as u8
+}
+
+/// Padding length held by the last byte, for the rules writing it there.
+fn last_byte_pad_length(bytes: &[u8], block_size: usize) -> Result<usize, PaddingError> {
+    // empty bytes have no padding byte at all
+    let pad_length = match bytes.last() {
+        Some(pad_length) => *pad_length as usize,
+        None => return Err(InvalidLastPaddingByte),
+    };
+
+    if pad_length == 0 || pad_length > block_size || pad_length > bytes.len() {
+        return Err(InvalidLastPaddingByte);
+    }
+
+    Ok(pad_length)
+}
+
+/// see https://tools.ietf.org/html/rfc5652#section-6.3
+pub fn pkcs7_pad(bytes: &[u8], block_size: usize) -> Vec<u8> {
+    let pad_length = block_size - bytes.len() % block_size;
+
+    [&bytes[..], &vec![length_byte(pad_length); pad_length][..]].concat()
+}
+
+pub fn validate_pkcs7_pad(bytes: &[u8], block_size: usize) -> Result<(), PaddingError> {
+    assert!(block_size >= 2);
+
+    let padding_length = last_byte_pad_length(bytes, block_size)?;
+
+    let last_block = bytes.len() - padding_length..;
+    let pad = &bytes[last_block];
+
+    match pad.iter().all(|byte| *byte as usize == padding_length) {
+        true => Ok(()),
+        false => Err(PaddingNotConsistent)
+    }
+}
+
+/// Removes the PKCS#7 padding of the bytes, once it has been validated.
+pub fn remove_pkcs7_padding(bytes: &[u8], block_size: usize) -> Result<Vec<u8>, PaddingError> {
+    Padding::PKCS7.unpad(bytes, block_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_pkcs7_pad_test() {
+        let block_size = 16;
+        let block = &generate_bytes_for_length(block_size);
+        let pad = &vec![block_size as u8; block_size];
+
+        let padded_block = &[
+            &block[..],
+            &pad[..]
+        ].concat();
+
+        assert!(validate_pkcs7_pad(padded_block, block_size).is_ok());
+    }
+
+    #[test]
+    fn remove_pkcs7_padding_test() {
+        assert_eq!(remove_pkcs7_padding(&[1, 2, 3, 0x02, 0x02], 16), Ok(vec![1, 2, 3]));
+        assert_eq!(remove_pkcs7_padding(&[], 16), Err(InvalidLastPaddingByte));
+        assert_eq!(remove_pkcs7_padding(&[1, 2, 3, 0x11], 16), Err(InvalidLastPaddingByte));
+        assert_eq!(remove_pkcs7_padding(&[1, 2, 3, 0x02], 16), Err(PaddingNotConsistent));
+    }
+
+    #[test]
+    fn pkcs7_pad_with_large_inputs_and_blocks() {
+        assert_eq!(pkcs7_pad(&[0; 300], 20)[300..], [20; 20]);
+        assert_eq!(pkcs7_pad(&[0; 300], 255)[300..], [210; 210][..]);
+    }
+
+    #[test]
+    fn pad_test() {
+        let bytes: &[u8] = &[0xaa; 5];
+
+        assert_eq!(Padding::PKCS7.pad(bytes, 8), [bytes, &[3, 3, 3]].concat());
+        assert_eq!(Padding::AnsiX923.pad(bytes, 8), [bytes, &[0, 0, 3]].concat());
+        assert_eq!(Padding::Iso7816.pad(bytes, 8), [bytes, &[0x80, 0, 0]].concat());
+        assert_eq!(Padding::Zero.pad(bytes, 8), [bytes, &[0, 0, 0]].concat());
+        assert_eq!(Padding::None.pad(bytes, 8), bytes);
+        let iso_10126_padded = Padding::Iso10126.pad(bytes, 8);
+        assert_eq!(iso_10126_padded.len(), 8);
+        assert_eq!(iso_10126_padded[7], 3);
+    }
+
+    #[test]
+    fn pad_whole_block() {
+        let bytes: &[u8] = &[0xaa; 8];
+
+        assert_eq!(Padding::AnsiX923.pad(bytes, 8), [bytes, &[0, 0, 0, 0, 0, 0, 0, 8]].concat());
+        assert_eq!(Padding::Iso7816.pad(bytes, 8), [bytes, &[0x80, 0, 0, 0, 0, 0, 0, 0]].concat());
+        assert_eq!(Padding::Zero.pad(bytes, 8), bytes);
+    }
+
+    #[test]
+    fn unpad_reverses_pad() {
+        let paddings = [
+            Padding::PKCS7,
+            Padding::AnsiX923,
+            Padding::Iso10126,
+            Padding::Iso7816,
+            Padding::Zero,
+            Padding::None,
+        ];
+
+        for padding in paddings.iter() {
+            for length in 0..=24 {
+                let bytes = &vec![0xaa; length];
+                let padded = padding.pad(bytes, 8);
+
+                if padding != &Padding::None {
+                    assert_eq!(padded.len() % 8, 0);
+                }
+                assert_eq!(&padding.unpad(&padded, 8).unwrap(), bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn pad_in_place_matches_pad() {
+        let paddings = [Padding::PKCS7, Padding::AnsiX923, Padding::Iso7816, Padding::Zero];
+
+        for padding in paddings.iter() {
+            for length in 0..=16 {
+                let bytes = &vec![0xaa; length];
+                let buffer = &mut [0xbb; 24];
+                buffer[..length].copy_from_slice(bytes);
+
+                let padded_length = padding.pad_in_place(buffer, length, 8);
+                assert_eq!(&buffer[..padded_length], &padding.pad(bytes, 8)[..]);
+                assert_eq!(padding.unpadded_length(&buffer[..padded_length], 8), Ok(length));
+            }
+        }
+        assert_eq!(Padding::None.pad_in_place(&mut [0; 8], 5, 8), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "no room for the padding")]
+    fn pad_in_place_requires_room_for_the_padding() {
+        Padding::PKCS7.pad_in_place(&mut [0; 8], 8, 8);
+    }
+
+    #[test]
+    fn validate_ansi_x923_pad_test() {
+        assert!(Padding::AnsiX923.validate(&[0xaa, 0, 0, 3], 8).is_ok());
+        assert_eq!(Padding::AnsiX923.validate(&[0xaa, 1, 0, 3], 8), Err(PaddingNotConsistent));
+        assert_eq!(Padding::AnsiX923.validate(&[0xaa, 0, 0, 0], 8), Err(InvalidLastPaddingByte));
+        assert_eq!(Padding::AnsiX923.validate(&[0xaa, 0, 0, 9], 8), Err(InvalidLastPaddingByte));
+    }
+
+    #[test]
+    fn validate_iso_10126_pad_test() {
+        assert!(Padding::Iso10126.validate(&[0xaa, 0x12, 0x34, 3], 8).is_ok());
+        assert_eq!(Padding::Iso10126.validate(&[0xaa, 0, 0, 0], 8), Err(InvalidLastPaddingByte));
+        assert_eq!(Padding::Iso10126.validate(&[0xaa, 0, 0, 5], 8), Err(InvalidLastPaddingByte));
+    }
+
+    #[test]
+    fn validate_iso_7816_pad_test() {
+        assert!(Padding::Iso7816.validate(&[0xaa, 0x80, 0, 0], 8).is_ok());
+        assert!(Padding::Iso7816.validate(&[0xaa, 0x80], 8).is_ok());
+        assert_eq!(Padding::Iso7816.validate(&[0xaa, 0x81], 8), Err(InvalidLastPaddingByte));
+        assert_eq!(Padding::Iso7816.validate(&[0xaa, 0x01, 0, 0], 8), Err(PaddingNotConsistent));
+        assert_eq!(Padding::Iso7816.validate(&[0x80, 0, 0, 0, 0, 0, 0, 0, 0], 8),
+                   Err(PaddingNotConsistent));
+        assert_eq!(Padding::Iso7816.validate(&[], 8), Err(InvalidLastPaddingByte));
+    }
+}
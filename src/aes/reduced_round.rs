@@ -0,0 +1,256 @@
+/// Resources used:
+/// https://csrc.nist.gov/csrc/media/publications/fips/197/final/documents/fips-197.pdf
+
+use aes::{Key, KeySchedule, Nb, State, key_expansion};
+use mode::BlockCipher;
+
+/// Transformation of a round after which the hooks of `ReducedRoundAes` are called.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Transformation {
+    SubBytes,
+    ShiftRows,
+    MixColumns,
+    AddRoundKey,
+}
+
+/// AES with a chosen number of rounds, for cryptanalysis experiments: integral, differential and
+/// fault attacks target reduced versions of the cipher. The Round Keys are the first ones of the
+/// standard key schedule, and the final round omits MixColumns unless told otherwise.
+///
+/// Hooks can read or modify the State after every transformation. Encryption calls them with
+/// the round and the transformation just applied, round 0 only adding the first Round Key.
+/// Decryption calls them with the round and the transformation just undone, so that the State a
+/// decryption hook sees is the one the encryption hook saw before that transformation.
+pub struct ReducedRoundAes {
+    key_schedule: KeySchedule,
+    final_mix_columns: bool,
+}
+
+impl ReducedRoundAes {
+    /// Between 1 round and the number of rounds of the key size.
+    pub fn new(key: &Key, rounds: usize) -> Self {
+        let nr = key.size().nr();
+        assert!(rounds >= 1 && rounds <= nr, "{}-byte keys allow 1 to {} rounds.",
+                key.size().length(), nr);
+
+        let mut key_schedule = key_expansion(key);
+        key_schedule.0.truncate(Nb * (rounds + 1));
+
+        ReducedRoundAes { key_schedule, final_mix_columns: false }
+    }
+
+    /// Keeps MixColumns in the final round, making it like the others.
+    pub fn with_final_mix_columns(mut self) -> Self {
+        self.final_mix_columns = true;
+
+        self
+    }
+
+    pub fn rounds(&self) -> usize {
+        self.key_schedule.rounds()
+    }
+
+    pub fn round_key(&self, round: usize) -> [u8; 4 * Nb] {
+        let mut round_key = [0u8; 4 * Nb];
+        round_key.copy_from_slice(&self.key_schedule.round_key(round).concat());
+
+        round_key
+    }
+
+    fn mixes_columns(&self, round: usize) -> bool {
+        round < self.rounds() || self.final_mix_columns
+    }
+
+    /// Encrypts a block, calling the hook after every transformation.
+    pub fn encrypt_block_with_hook<F>(&self, block: &mut [u8], mut hook: F)
+        where F: FnMut(usize, Transformation, &mut State) {
+        assert_eq!(block.len(), 4 * Nb);
+        let w = &self.key_schedule;
+        let mut state = State::from_part(block);
+
+        state.add_round_key(w.round_key(0));
+        hook(0, Transformation::AddRoundKey, &mut state);
+
+        for round in 1..=self.rounds() {
+            state.sub_bytes();
+            hook(round, Transformation::SubBytes, &mut state);
+            state.shift_rows();
+            hook(round, Transformation::ShiftRows, &mut state);
+            if self.mixes_columns(round) {
+                state.mix_columns();
+                hook(round, Transformation::MixColumns, &mut state);
+            }
+            state.add_round_key(w.round_key(round));
+            hook(round, Transformation::AddRoundKey, &mut state);
+        }
+
+        state.write_block(block);
+    }
+
+    /// Decrypts a block with the Inverse Cipher, calling the hook after every inverse
+    /// transformation.
+    pub fn decrypt_block_with_hook<F>(&self, block: &mut [u8], mut hook: F)
+        where F: FnMut(usize, Transformation, &mut State) {
+        assert_eq!(block.len(), 4 * Nb);
+        let w = &self.key_schedule;
+        let mut state = State::from_part(block);
+
+        for round in (1..=self.rounds()).rev() {
+            state.add_round_key(w.round_key(round));
+            hook(round, Transformation::AddRoundKey, &mut state);
+            if self.mixes_columns(round) {
+                state.inv_mix_columns();
+                hook(round, Transformation::MixColumns, &mut state);
+            }
+            state.inv_shift_rows();
+            hook(round, Transformation::ShiftRows, &mut state);
+            state.inv_sub_bytes();
+            hook(round, Transformation::SubBytes, &mut state);
+        }
+
+        state.add_round_key(w.round_key(0));
+        hook(0, Transformation::AddRoundKey, &mut state);
+
+        state.write_block(block);
+    }
+}
+
+impl BlockCipher for ReducedRoundAes {
+    fn block_size(&self) -> usize {
+        4 * Nb
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        self.encrypt_block_with_hook(block, |_, _, _| {});
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        self.decrypt_block_with_hook(block, |_, _, _| {});
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::Aes;
+    use aes::generate::generate_bytes_for_length;
+    use aes::trace::{Step, trace_encrypt};
+
+    #[test]
+    fn full_rounds_match_aes() {
+        for key_length in [16, 24, 32].iter() {
+            let key = Key::new(&generate_bytes_for_length(*key_length));
+            let reduced = ReducedRoundAes::new(&key, key.size().nr());
+            let raw = generate_bytes_for_length(16);
+            let (mut expected, mut actual) = (raw.clone(), raw.clone());
+
+            Aes::new(&key).encrypt_block(&mut expected);
+            reduced.encrypt_block(&mut actual);
+            assert_eq!(actual, expected);
+
+            reduced.decrypt_block(&mut actual);
+            assert_eq!(actual, raw);
+        }
+    }
+
+    #[test]
+    fn hooks_see_the_states_of_the_trace() {
+        let key = Key::new(&generate_bytes_for_length(16));
+        let raw = generate_bytes_for_length(16);
+        let trace = trace_encrypt(&key, &raw);
+        let mut calls = vec![];
+
+        let aes = ReducedRoundAes::new(&key, 10);
+        aes.encrypt_block_with_hook(&mut raw.clone(), |round, transformation, state| {
+            let block = state.to_block();
+            match transformation {
+                // the State between rounds is the one at the start of the next round
+                Transformation::AddRoundKey if round < 10 => {
+                    assert_eq!(&block[..], &trace.value(round + 1, Step::Start).unwrap()[..]);
+                }
+                Transformation::SubBytes => {
+                    assert_eq!(&block[..], &trace.value(round, Step::SubBytes).unwrap()[..]);
+                }
+                _ => {}
+            }
+            calls.push((round, transformation));
+        });
+
+        // no MixColumns in the final round
+        assert_eq!(calls.len(), 1 + 9 * 4 + 3);
+        assert_eq!(&calls[calls.len() - 3..], &[
+            (10, Transformation::SubBytes),
+            (10, Transformation::ShiftRows),
+            (10, Transformation::AddRoundKey),
+        ]);
+    }
+
+    #[test]
+    fn decryption_hooks_see_the_states_before_each_transformation() {
+        let key = Key::new(&generate_bytes_for_length(16));
+        let aes = ReducedRoundAes::new(&key, 4).with_final_mix_columns();
+        let mut block = generate_bytes_for_length(16);
+        let raw = block.clone();
+        let mut encryption_states = vec![block.clone()];
+
+        aes.encrypt_block_with_hook(&mut block, |_, _, state| {
+            encryption_states.push(state.to_block());
+        });
+        let mut decryption_states = vec![];
+        aes.decrypt_block_with_hook(&mut block, |_, _, state| {
+            decryption_states.push(state.to_block());
+        });
+        encryption_states.pop();
+        encryption_states.reverse();
+
+        assert_eq!(decryption_states, encryption_states);
+        assert_eq!(block, raw);
+    }
+
+    #[test]
+    fn reduced_rounds_with_and_without_final_mix_columns() {
+        let key = Key::new(&generate_bytes_for_length(16));
+        let raw = generate_bytes_for_length(16);
+
+        for rounds in 1..=10 {
+            let without = ReducedRoundAes::new(&key, rounds);
+            let with = ReducedRoundAes::new(&key, rounds).with_final_mix_columns();
+            let (mut a, mut b) = (raw.clone(), raw.clone());
+
+            without.encrypt_block(&mut a);
+            with.encrypt_block(&mut b);
+            assert_ne!(a, b);
+
+            without.decrypt_block(&mut a);
+            with.decrypt_block(&mut b);
+            assert_eq!((&a, &b), (&raw, &raw));
+        }
+    }
+
+    #[test]
+    fn hooks_can_modify_the_state() {
+        let key = Key::new(&generate_bytes_for_length(16));
+        let aes = ReducedRoundAes::new(&key, 10);
+        let raw = generate_bytes_for_length(16);
+        let mut faulty = raw.clone();
+
+        aes.encrypt_block_with_hook(&mut faulty, |round, t, state| {
+            if round == 9 && t == Transformation::ShiftRows {
+                let byte = state.byte(0, 0);
+                state.set_byte(0, 0, byte ^ 0x01);
+            }
+        });
+        let mut correct = raw.clone();
+        aes.encrypt_block(&mut correct);
+
+        // a fault before the round 9 MixColumns spreads to a column, then 4 bytes of the cipher
+        let differences = correct.iter().zip(faulty.iter()).filter(|(a, b)| a != b).count();
+        assert_eq!(differences, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "1 to 10 rounds")]
+    fn rounds_are_bounded_by_the_key_size() {
+        ReducedRoundAes::new(&Key::new(&[0; 16]), 11);
+    }
+}
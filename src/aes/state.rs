@@ -24,17 +24,27 @@ impl State {
 
     pub fn to_block(&self) -> Vec<u8> {
         let mut out = vec![0u8; 4 * aes::Nb];
+        self.write_block(&mut out);
+
+        out
+    }
+
+    /// Copies the State to a block, without allocating.
+    pub fn write_block(&self, block: &mut [u8]) {
         for r in 0..4 {
             for c in 0..aes::Nb {
-                out[r + 4 * c] = self.data[c][r];
+                block[r + 4 * c] = self.data[c][r];
             }
         }
+    }
 
-        out
+    /// Byte s(r, c) of the State, at the given row and column.
+    pub fn byte(&self, row: usize, column: usize) -> u8 {
+        self.data[column][row]
     }
 
-    pub fn xor_with_state(&mut self, other: &State) {
-        self.xor(&[&other.data[0], &other.data[1], &other.data[2], &other.data[3]])
+    pub fn set_byte(&mut self, row: usize, column: usize, value: u8) {
+        self.data[column][row] = value;
     }
 
     pub fn xor(&mut self, data: &[&[u8; 4]; aes::Nb]) {
@@ -45,14 +55,6 @@ impl State {
         }
     }
 
-    pub fn xor_with_iv(&mut self, iv: &aes::Iv) {
-        for r in 0..4 {
-            for c in 0..aes::Nb {
-                self.data[r][c] ^= iv.0[r][c];
-            }
-        }
-    }
-
     /// Transformation in the Cipher and Inverse Cipher in which a Round
     /// Key is added to the State using an XOR operation. The length of a
     /// Round Key equals the size of the State (i.e., for Nb = 4, the Round
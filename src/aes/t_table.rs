@@ -0,0 +1,231 @@
+/// Resources used:
+/// https://en.wikipedia.org/wiki/Advanced_Encryption_Standard#Optimization_of_the_cipher
+/// https://github.com/openssl/openssl/blob/master/crypto/aes/aes_core.c
+
+use aes::{Key, KeySchedule, Nb, S_BOX, INVERSE_S_BOX, key_expansion};
+use aes::cipher::inverse_key_schedule;
+use mode::BlockCipher;
+
+/// Encryption tables: TE[i][x] is the column produced by SubBytes then MixColumns for a byte x in
+/// row i of a column, so that a whole round of a column takes 4 lookups and 4 xors. Each table is
+/// the previous one rotated by a byte.
+const TE: [[u32; 256]; 4] = rotated_tables(&S_BOX, [0x02, 0x01, 0x01, 0x03]);
+
+/// Decryption tables, combining InvSubBytes and InvMixColumns.
+const TD: [[u32; 256]; 4] = rotated_tables(&INVERSE_S_BOX, [0x0e, 0x09, 0x0d, 0x0b]);
+
+/// AES with 32-bit T-tables: the State is held as 4 big-endian column words, and SubBytes,
+/// ShiftRows and MixColumns are merged into table lookups. The lookups depend on the key and the
+/// data, so this is no protection against cache-timing attacks. `Aes` remains the reference.
+pub struct TTableAes {
+    round_keys: Vec<u32>,
+    /// Round keys of the Equivalent Inverse Cipher.
+    inverse_round_keys: Vec<u32>,
+}
+
+impl TTableAes {
+    pub fn new(key: &Key) -> Self {
+        let key_schedule = key_expansion(key);
+        let inverse_key_schedule = inverse_key_schedule(&key_schedule);
+
+        TTableAes {
+            round_keys: to_words(&key_schedule),
+            inverse_round_keys: to_words(&inverse_key_schedule),
+        }
+    }
+
+    fn rounds(&self) -> usize {
+        self.round_keys.len() / Nb - 1
+    }
+}
+
+impl BlockCipher for TTableAes {
+    fn block_size(&self) -> usize {
+        4 * Nb
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        assert_eq!(block.len(), 4 * Nb);
+        let rk = &self.round_keys;
+        let nr = self.rounds();
+
+        let mut s = [0u32; Nb];
+        for c in 0..Nb {
+            s[c] = read_word(block, c) ^ rk[c];
+        }
+
+        for round in 1..nr {
+            let mut t = [0u32; Nb];
+            for c in 0..Nb {
+                // the bytes of row i come from column c + i, as shifted by ShiftRows
+                t[c] = TE[0][(s[c] >> 24) as usize]
+                    ^ TE[1][(s[(c + 1) % Nb] >> 16) as u8 as usize]
+                    ^ TE[2][(s[(c + 2) % Nb] >> 8) as u8 as usize]
+                    ^ TE[3][s[(c + 3) % Nb] as u8 as usize]
+                    ^ rk[round * Nb + c];
+            }
+            s = t;
+        }
+
+        // no MixColumns in the final round
+        for c in 0..Nb {
+            let word = u32::from_be_bytes([
+                S_BOX[(s[c] >> 24) as usize],
+                S_BOX[(s[(c + 1) % Nb] >> 16) as u8 as usize],
+                S_BOX[(s[(c + 2) % Nb] >> 8) as u8 as usize],
+                S_BOX[s[(c + 3) % Nb] as u8 as usize],
+            ]) ^ rk[nr * Nb + c];
+            block[4 * c..4 * c + 4].copy_from_slice(&word.to_be_bytes());
+        }
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        assert_eq!(block.len(), 4 * Nb);
+        let dk = &self.inverse_round_keys;
+        let nr = self.rounds();
+
+        let mut s = [0u32; Nb];
+        for c in 0..Nb {
+            s[c] = read_word(block, c) ^ dk[nr * Nb + c];
+        }
+
+        for round in (1..nr).rev() {
+            let mut t = [0u32; Nb];
+            for c in 0..Nb {
+                // InvShiftRows moves the bytes of row i from column c - i
+                t[c] = TD[0][(s[c] >> 24) as usize]
+                    ^ TD[1][(s[(c + 3) % Nb] >> 16) as u8 as usize]
+                    ^ TD[2][(s[(c + 2) % Nb] >> 8) as u8 as usize]
+                    ^ TD[3][s[(c + 1) % Nb] as u8 as usize]
+                    ^ dk[round * Nb + c];
+            }
+            s = t;
+        }
+
+        for c in 0..Nb {
+            let word = u32::from_be_bytes([
+                INVERSE_S_BOX[(s[c] >> 24) as usize],
+                INVERSE_S_BOX[(s[(c + 3) % Nb] >> 16) as u8 as usize],
+                INVERSE_S_BOX[(s[(c + 2) % Nb] >> 8) as u8 as usize],
+                INVERSE_S_BOX[s[(c + 1) % Nb] as u8 as usize],
+            ]) ^ dk[c];
+            block[4 * c..4 * c + 4].copy_from_slice(&word.to_be_bytes());
+        }
+    }
+}
+
+fn read_word(block: &[u8], column: usize) -> u32 {
+    u32::from_be_bytes([
+        block[4 * column],
+        block[4 * column + 1],
+        block[4 * column + 2],
+        block[4 * column + 3],
+    ])
+}
+
+fn to_words(key_schedule: &KeySchedule) -> Vec<u32> {
+    key_schedule.0.iter().map(|word| u32::from_be_bytes(*word)).collect()
+}
+
+/// Tables of the columns s * coefficients, for every substituted byte s, then rotated by 0 to 3
+/// bytes.
+const fn rotated_tables(s_box: &[u8; 256], coefficients: [u8; 4]) -> [[u32; 256]; 4] {
+    let mut tables = [[0u32; 256]; 4];
+
+    let mut x = 0;
+    while x < 256 {
+        let s = s_box[x];
+        let word = u32::from_be_bytes([
+            multiply(coefficients[0], s),
+            multiply(coefficients[1], s),
+            multiply(coefficients[2], s),
+            multiply(coefficients[3], s),
+        ]);
+
+        let mut i = 0;
+        while i < 4 {
+            tables[i][x] = word.rotate_right(8 * i as u32);
+            i += 1;
+        }
+        x += 1;
+    }
+
+    tables
+}
+
+/// Multiplication in GF(2^8) usable at compile time, see `math::multiply_in_g`.
+const fn multiply(a: u8, b: u8) -> u8 {
+    let (mut a, mut b, mut product) = (a, b, 0u8);
+
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        a = (a << 1) ^ if a & 0x80 != 0 { 0x1b } else { 0x00 };
+        b >>= 1;
+    }
+
+    product
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::Aes;
+    use aes::generate::generate_bytes_for_length;
+    use aes::math::multiply_in_g;
+    use hex::hex_string_to_bytes;
+
+    #[test]
+    fn encrypt_block_fips_197_appendix_c_test_cases() {
+        let raw = &(0..16).map(|i| i * 0x11).collect::<Vec<u8>>();
+        let test_cases = vec![
+            (16, "69c4e0d86a7b0430d8cdb78070b4c55a"),
+            (24, "dda97ca4864cdfe06eaf70a0ec0d7191"),
+            (32, "8ea2b7ca516745bfeafc49904b496089"),
+        ];
+
+        for (key_length, expected_cipher) in test_cases.iter() {
+            let block_cipher = TTableAes::new(&Key::new(&(0..*key_length).collect::<Vec<u8>>()));
+            let block = &mut raw.clone();
+
+            block_cipher.encrypt_block(block);
+            assert_eq!(block, &hex_string_to_bytes(expected_cipher));
+
+            block_cipher.decrypt_block(block);
+            assert_eq!(block, raw);
+        }
+    }
+
+    #[test]
+    fn t_tables_match_the_state_path() {
+        for key_length in [16, 24, 32].iter() {
+            let key = Key::new(&generate_bytes_for_length(*key_length));
+            let reference = Aes::new(&key);
+            let t_table_aes = TTableAes::new(&key);
+
+            for _ in 0..50 {
+                let block = generate_bytes_for_length(16);
+                let (mut expected, mut actual) = (block.clone(), block.clone());
+
+                reference.encrypt_block(&mut expected);
+                t_table_aes.encrypt_block(&mut actual);
+                assert_eq!(actual, expected);
+
+                reference.decrypt_block(&mut expected);
+                t_table_aes.decrypt_block(&mut actual);
+                assert_eq!(actual, expected);
+                assert_eq!(actual, block);
+            }
+        }
+    }
+
+    #[test]
+    fn const_multiply_matches_multiply_in_g() {
+        for a in 0..=255 {
+            for b in [0x01, 0x02, 0x03, 0x09, 0x0b, 0x0d, 0x0e].iter() {
+                assert_eq!(multiply(a, *b), multiply_in_g(*b, a));
+            }
+        }
+    }
+}
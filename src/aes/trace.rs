@@ -0,0 +1,415 @@
+/// Resources used:
+/// https://csrc.nist.gov/csrc/media/publications/fips/197/final/documents/fips-197.pdf
+/// (Appendix B, Cipher Example, and Appendix C, Example Vectors)
+
+use std::fmt;
+use aes::{Key, Nb, ReducedRoundAes, State, Transformation};
+
+/// Direction of a traced block.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    /// The Cipher of Sec. 5.1.
+    Cipher,
+    /// The Inverse Cipher of Sec. 5.3, with the Round Keys of the Cipher in reverse order.
+    InverseCipher,
+}
+
+/// Point of a round at which a value is recorded, named as in FIPS-197 Appendix C.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Step {
+    /// The block, before the first AddRoundKey.
+    Input,
+    /// The State at the start of the round.
+    Start,
+    SubBytes,
+    ShiftRows,
+    MixColumns,
+    /// The Round Key added in the round, rather than a State.
+    RoundKey,
+    /// The State after AddRoundKey, which the Inverse Cipher records before InvMixColumns.
+    AddRoundKey,
+    /// The State after the last round.
+    Output,
+}
+
+impl Step {
+    fn label(&self) -> &'static str {
+        match self {
+            Step::Input => "input",
+            Step::Start => "start",
+            Step::SubBytes => "s_box",
+            Step::ShiftRows => "s_row",
+            Step::MixColumns => "m_col",
+            Step::RoundKey => "k_sch",
+            Step::AddRoundKey => "k_add",
+            Step::Output => "output",
+        }
+    }
+}
+
+/// A value recorded while tracing: a State, or the Round Key of the round.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TraceEntry {
+    pub round: usize,
+    pub step: Step,
+    pub value: [u8; 4 * Nb],
+}
+
+/// Every intermediate value of the encryption or decryption of a single block, in order. Its
+/// `Display` renders the lines of FIPS-197 Appendix C, so that a trace can be diffed against the
+/// standard, and `appendix_b_table` the State matrices of Appendix B.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Trace {
+    direction: Direction,
+    entries: Vec<TraceEntry>,
+}
+
+impl Trace {
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries
+    }
+
+    /// Value recorded at a step of a round, if the round has that step.
+    pub fn value(&self, round: usize, step: Step) -> Option<&[u8; 4 * Nb]> {
+        self.entries.iter()
+            .find(|entry| entry.round == round && entry.step == step)
+            .map(|entry| &entry.value)
+    }
+
+    /// Round Keys, in the order they were added.
+    pub fn round_keys(&self) -> Vec<[u8; 4 * Nb]> {
+        self.entries.iter()
+            .filter(|entry| entry.step == Step::RoundKey)
+            .map(|entry| entry.value)
+            .collect()
+    }
+
+    /// Resulting block.
+    pub fn output(&self) -> [u8; 4 * Nb] {
+        self.entries.last().unwrap().value
+    }
+
+    /// Renders the trace as the table of Appendix B: a row of 4 x 4 matrices per round, whose
+    /// columns are the State at the start of the round, after each transformation, and the Round
+    /// Key.
+    pub fn appendix_b_table(&self) -> String {
+        let columns: &[Step] = match self.direction {
+            Direction::Cipher => {
+                &[Step::Start, Step::SubBytes, Step::ShiftRows, Step::MixColumns, Step::RoundKey]
+            }
+            Direction::InverseCipher => {
+                &[Step::Start, Step::ShiftRows, Step::SubBytes, Step::RoundKey, Step::AddRoundKey]
+            }
+        };
+        let rounds = self.entries.last().unwrap().round;
+
+        let mut table = String::new();
+        let headings: Vec<(&str, &str)> = columns.iter().map(|step| table_heading(*step)).collect();
+        table.push_str(&table_line("Round", &headings.iter().map(|h| h.0).collect::<Vec<_>>()));
+        table.push_str(&table_line("Number", &headings.iter().map(|h| h.1).collect::<Vec<_>>()));
+
+        for round in 0..=rounds {
+            // the input and output take the place of the State at the start of a round
+            let mut rows = vec![(round.to_string(), self.matrix_rows(round, columns))];
+            if round == 0 {
+                rows[0].0 = "input".to_string();
+                rows[0].1[0] = self.matrix_rows(0, &[Step::Input])[0].clone();
+            }
+            if round == rounds {
+                let mut output_rows = vec![vec![String::new(); 4]; columns.len()];
+                output_rows[0] = self.matrix_rows(round, &[Step::Output])[0].clone();
+                rows.push(("output".to_string(), output_rows));
+            }
+
+            for (label, matrices) in rows {
+                for r in 0..4 {
+                    let row: Vec<&str> = matrices.iter().map(|matrix| &matrix[r][..]).collect();
+                    table.push_str(&table_line(if r == 0 { &label } else { "" }, &row));
+                }
+                table.push('\n');
+            }
+        }
+
+        table
+    }
+
+    /// Rows of the 4 x 4 matrices of the values recorded at the steps of a round, blank when the
+    /// round has no such step.
+    fn matrix_rows(&self, round: usize, steps: &[Step]) -> Vec<Vec<String>> {
+        steps.iter()
+            .map(|step| {
+                (0..4)
+                    .map(|r| match self.value(round, *step) {
+                        Some(value) => (0..Nb)
+                            .map(|c| format!("{:02x}", value[r + 4 * c]))
+                            .collect::<Vec<String>>()
+                            .join(" "),
+                        None => String::new(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// The lines of FIPS-197 Appendix C, e.g. `round[ 1].s_box 63cab7040953d051cd60e0e7ba70e18c`,
+/// prefixed with an i for the Inverse Cipher.
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let prefix = match self.direction {
+            Direction::Cipher => {
+                writeln!(f, "CIPHER (ENCRYPT):")?;
+                ""
+            }
+            Direction::InverseCipher => {
+                writeln!(f, "INVERSE CIPHER (DECRYPT):")?;
+                "i"
+            }
+        };
+
+        for entry in self.entries.iter() {
+            let label = format!("{}{}", prefix, entry.step.label());
+            let value: String = entry.value.iter().map(|byte| format!("{:02x}", byte)).collect();
+            writeln!(f, "round[{:2}].{:<8}{}", entry.round, label, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn table_heading(step: Step) -> (&'static str, &'static str) {
+    match step {
+        Step::Start => ("Start of", "Round"),
+        Step::SubBytes => ("After", "SubBytes"),
+        Step::ShiftRows => ("After", "ShiftRows"),
+        Step::MixColumns => ("After", "MixColumns"),
+        Step::RoundKey => ("Round Key", "Value"),
+        Step::AddRoundKey => ("After", "AddRoundKey"),
+        Step::Input | Step::Output => ("", ""),
+    }
+}
+
+fn table_line(label: &str, cells: &[&str]) -> String {
+    let cells: Vec<String> = cells.iter().map(|cell| format!("{:<13}", cell)).collect();
+
+    format!("{:<8}{}", label, cells.concat()).trim_end().to_string() + "\n"
+}
+
+/// Encrypts a block with the Cipher of Sec. 5.1, recording every intermediate value. The rounds
+/// are those of `ReducedRoundAes`, the values being recorded by its hook.
+pub fn trace_encrypt(key: &Key, block: &[u8]) -> Trace {
+    let (block_cipher, mut block) = full_aes(key, block);
+    let nr = block_cipher.rounds();
+    let mut trace = Tracer::new(Direction::Cipher);
+
+    trace.state(0, Step::Input, &State::from_part(&block));
+    block_cipher.encrypt_block_with_hook(&mut block, |round, transformation, state| {
+        match transformation {
+            Transformation::SubBytes => trace.state(round, Step::SubBytes, state),
+            Transformation::ShiftRows => trace.state(round, Step::ShiftRows, state),
+            Transformation::MixColumns => trace.state(round, Step::MixColumns, state),
+            Transformation::AddRoundKey => {
+                trace.round_key(round, block_cipher.round_key(round));
+                if round == nr {
+                    trace.state(nr, Step::Output, state);
+                } else {
+                    trace.state(round + 1, Step::Start, state);
+                }
+            }
+        }
+    });
+
+    trace.finish()
+}
+
+/// Decrypts a block with the Inverse Cipher of Sec. 5.3, recording every intermediate value. As
+/// in Appendix C, the rounds are numbered in the order they run: the hook of `ReducedRoundAes`
+/// sees the Round Key of round r added in round nr - r, and the other transformations of round r
+/// undone in round nr + 1 - r.
+pub fn trace_decrypt(key: &Key, block: &[u8]) -> Trace {
+    let (block_cipher, mut block) = full_aes(key, block);
+    let nr = block_cipher.rounds();
+    let mut trace = Tracer::new(Direction::InverseCipher);
+
+    trace.state(0, Step::Input, &State::from_part(&block));
+    block_cipher.decrypt_block_with_hook(&mut block, |round, transformation, state| {
+        match transformation {
+            Transformation::ShiftRows => trace.state(nr + 1 - round, Step::ShiftRows, state),
+            Transformation::SubBytes => trace.state(nr + 1 - round, Step::SubBytes, state),
+            // the State after InvMixColumns starts the next round
+            Transformation::MixColumns => trace.state(nr + 1 - round, Step::Start, state),
+            Transformation::AddRoundKey => {
+                trace.round_key(nr - round, block_cipher.round_key(round));
+                if round == 0 {
+                    trace.state(nr, Step::Output, state);
+                } else if round == nr {
+                    trace.state(1, Step::Start, state);
+                } else {
+                    trace.state(nr - round, Step::AddRoundKey, state);
+                }
+            }
+        }
+    });
+
+    trace.finish()
+}
+
+/// AES with every round of the key size, and a copy of the block it works on.
+fn full_aes(key: &Key, block: &[u8]) -> (ReducedRoundAes, [u8; 4 * Nb]) {
+    assert_eq!(block.len(), 4 * Nb);
+    let mut copy = [0u8; 4 * Nb];
+    copy.copy_from_slice(block);
+
+    (ReducedRoundAes::new(key, key.size().nr()), copy)
+}
+
+struct Tracer {
+    trace: Trace,
+}
+
+impl Tracer {
+    fn new(direction: Direction) -> Self {
+        Tracer { trace: Trace { direction, entries: vec![] } }
+    }
+
+    fn state(&mut self, round: usize, step: Step, state: &State) {
+        let mut value = [0u8; 4 * Nb];
+        state.write_block(&mut value);
+
+        self.trace.entries.push(TraceEntry { round, step, value });
+    }
+
+    /// Records a Round Key, added in the given round.
+    fn round_key(&mut self, round: usize, value: [u8; 4 * Nb]) {
+        self.trace.entries.push(TraceEntry { round, step: Step::RoundKey, value });
+    }
+
+    fn finish(self) -> Trace {
+        self.trace
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::Aes;
+    use aes::generate::generate_bytes_for_length;
+    use hex::hex_string_to_bytes;
+    use mode::BlockCipher;
+
+    fn appendix_c_1() -> (Key, Vec<u8>) {
+        (Key::new(&hex_string_to_bytes("000102030405060708090a0b0c0d0e0f")),
+         hex_string_to_bytes("00112233445566778899aabbccddeeff"))
+    }
+
+    #[test]
+    fn trace_encrypt_fips_197_appendix_c_1() {
+        let (key, raw) = appendix_c_1();
+
+        let trace = trace_encrypt(&key, &raw).to_string();
+        let lines: Vec<&str> = trace.lines().collect();
+
+        assert_eq!(&lines[..9], &[
+            "CIPHER (ENCRYPT):",
+            "round[ 0].input   00112233445566778899aabbccddeeff",
+            "round[ 0].k_sch   000102030405060708090a0b0c0d0e0f",
+            "round[ 1].start   00102030405060708090a0b0c0d0e0f0",
+            "round[ 1].s_box   63cab7040953d051cd60e0e7ba70e18c",
+            "round[ 1].s_row   6353e08c0960e104cd70b751bacad0e7",
+            "round[ 1].m_col   5f72641557f5bc92f7be3b291db9f91a",
+            "round[ 1].k_sch   d6aa74fdd2af72fadaa678f1d6ab76fe",
+            "round[ 2].start   89d810e8855ace682d1843d8cb128fe4",
+        ]);
+        assert_eq!(&lines[lines.len() - 5..], &[
+            "round[10].start   bd6e7c3df2b5779e0b61216e8b10b689",
+            "round[10].s_box   7a9f102789d5f50b2beffd9f3dca4ea7",
+            "round[10].s_row   7ad5fda789ef4e272bca100b3d9ff59f",
+            "round[10].k_sch   13111d7fe3944a17f307a78b4d2b30c5",
+            "round[10].output  69c4e0d86a7b0430d8cdb78070b4c55a",
+        ]);
+        // 2 lines for round 0, 5 for rounds 1 to 9, 5 for the last round
+        assert_eq!(lines.len(), 1 + 2 + 9 * 5 + 5);
+    }
+
+    #[test]
+    fn trace_decrypt_fips_197_appendix_c_1() {
+        let (key, raw) = appendix_c_1();
+        let cipher = hex_string_to_bytes("69c4e0d86a7b0430d8cdb78070b4c55a");
+
+        let trace = trace_decrypt(&key, &cipher).to_string();
+        let lines: Vec<&str> = trace.lines().collect();
+
+        assert_eq!(&lines[..8], &[
+            "INVERSE CIPHER (DECRYPT):",
+            "round[ 0].iinput  69c4e0d86a7b0430d8cdb78070b4c55a",
+            "round[ 0].ik_sch  13111d7fe3944a17f307a78b4d2b30c5",
+            "round[ 1].istart  7ad5fda789ef4e272bca100b3d9ff59f",
+            "round[ 1].is_row  7a9f102789d5f50b2beffd9f3dca4ea7",
+            "round[ 1].is_box  bd6e7c3df2b5779e0b61216e8b10b689",
+            "round[ 1].ik_sch  549932d1f08557681093ed9cbe2c974e",
+            "round[ 1].ik_add  e9f74eec023020f61bf2ccf2353c21c7",
+        ]);
+        assert_eq!(lines.last(), Some(&"round[10].ioutput 00112233445566778899aabbccddeeff"));
+        assert_eq!(&trace_decrypt(&key, &cipher).output()[..], &raw[..]);
+    }
+
+    #[test]
+    fn inverse_cipher_retraces_the_cipher() {
+        for key_length in [16, 24, 32].iter() {
+            let key = Key::new(&generate_bytes_for_length(*key_length));
+            let raw = generate_bytes_for_length(16);
+            let encryption = trace_encrypt(&key, &raw);
+            let decryption = trace_decrypt(&key, &encryption.output());
+            let nr = key.size().nr();
+
+            for round in 1..=nr {
+                // each inverse transformation undoes its transformation of round nr + 1 - round
+                assert_eq!(decryption.value(round, Step::Start),
+                           encryption.value(nr + 1 - round, Step::ShiftRows));
+                assert_eq!(decryption.value(round, Step::SubBytes),
+                           encryption.value(nr + 1 - round, Step::Start));
+            }
+            assert_eq!(decryption.round_keys().iter().rev().collect::<Vec<_>>(),
+                       encryption.round_keys().iter().collect::<Vec<_>>());
+            assert_eq!(&decryption.output()[..], &raw[..]);
+
+            let mut cipher = raw.clone();
+            Aes::new(&key).encrypt_block(&mut cipher);
+            assert_eq!(&encryption.output()[..], &cipher[..]);
+        }
+    }
+
+    #[test]
+    fn appendix_b_table_shows_the_state_matrices() {
+        let key = Key::new(&hex_string_to_bytes("2b7e151628aed2a6abf7158809cf4f3c"));
+        let raw = hex_string_to_bytes("3243f6a8885a308d313198a2e0370734");
+
+        let table = trace_encrypt(&key, &raw).appendix_b_table();
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(&lines[..12], &[
+            "Round   Start of     After        After        After        Round Key",
+            "Number  Round        SubBytes     ShiftRows    MixColumns   Value",
+            "input   32 88 31 e0                                         2b 28 ab 09",
+            "        43 5a 31 37                                         7e ae f7 cf",
+            "        f6 30 98 07                                         15 d2 15 4f",
+            "        a8 8d a2 34                                         16 a6 88 3c",
+            "",
+            "1       19 a0 9a e9  d4 e0 b8 1e  d4 e0 b8 1e  04 e0 48 28  a0 88 23 2a",
+            "        3d f4 c6 f8  27 bf b4 41  bf b4 41 27  66 cb f8 06  fa 54 a3 6c",
+            "        e3 e2 8d 48  11 98 5d 52  5d 52 11 98  81 19 d3 26  fe 2c 39 76",
+            "        be 2b 2a 08  ae f1 e5 30  30 ae f1 e5  e5 9a 7a 4c  17 b1 39 05",
+            "",
+        ]);
+        assert_eq!(&lines[lines.len() - 5..lines.len() - 1], &[
+            "output  39 02 dc 19",
+            "        25 dc 11 6a",
+            "        84 09 85 0b",
+            "        1d fb 97 32",
+        ]);
+    }
+}
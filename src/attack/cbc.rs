@@ -1,31 +1,40 @@
-use aes::{Key, Iv, BlockCipherMode, AESEncryptionOptions, Padding};
+use aes::{BlockCipherMode, AESEncryptionOptions, Padding, AesNi};
 use aes;
 use attack::CipherWithIvAndKey;
 use aes::generate::generate_bytes_for_length;
-
-pub fn cbc_bitflip<O>(
-    oracle: O,
-    key: &Key,
-    iv: &Iv) -> Vec<u8>
+use mode;
+use mode::BlockCipher;
+use xor;
+
+/// Bytes the bitflip oracle prepends to its input.
+const PREFIX: &str = "comment1=cooking%20MCs;userdata=";
+
+/// Makes the oracle's cipher decrypt to a plaintext containing ";admin=true;", although the
+/// oracle escapes those characters. The block before the payload is sacrificed: flipping its
+/// cipher bytes flips the same bytes of the payload once decrypted. The payload must fit in a
+/// block for the flips to stay out of it.
+pub fn cbc_bitflip<O, C: BlockCipher>(oracle: O, block_cipher: &C, iv: &[u8]) -> Vec<u8>
     where O: Fn(&[u8]) -> Vec<u8> {
-    let block_size = 16;
-
-    let trigger = &vec![0x01; block_size];
-    let sought_for = &[
+    let block_size = block_cipher.block_size();
+    let payload = &[
         &[0xff][..],
         "admin".as_bytes(),
         &[0xff][..],
         "true".as_bytes(),
-        &[0xff][..],
-        &vec![0x01; 4][..]].concat();
+        &[0xff][..]].concat();
+    if payload.len() > block_size {
+        panic!("The payload does not fit in a {}-byte block.", block_size);
+    }
 
-    let result = oracle(&[&trigger[..], &sought_for[..]].concat());
+    // the input starts with bytes completing the prefix's last block, then the sacrificed block
+    let alignment = (block_size - PREFIX.len() % block_size) % block_size;
+    let flipped_block_start = PREFIX.len() + alignment;
+    let payload_start = flipped_block_start + block_size;
+    let sought_for = &[&payload[..], &vec![0x01; block_size - payload.len()][..]].concat();
 
-    let mut xor_block = vec![];
+    let result = oracle(&[&vec![0x01; alignment + block_size][..], &sought_for[..]].concat());
 
-    for byte in result[2 * block_size..2 * block_size + block_size].iter() {
-        xor_block.push(*byte);
-    }
+    let mut xor_block = result[flipped_block_start..payload_start].to_vec();
 
     let letter_changes = vec![
         (0, ';'),
@@ -36,14 +45,14 @@ pub fn cbc_bitflip<O>(
         for i in 0..=255 {
             let manipulated_byte = ((xor_block[*pos as usize] as u32 + i as u32) % 256u32) as u8;
             let current_cipher = [
-                &result[..2 * block_size + *pos],
+                &result[..flipped_block_start + *pos],
                 &[manipulated_byte][..],
-                &result[(2 * block_size) + *pos + 1..],
+                &result[flipped_block_start + *pos + 1..],
             ].concat();
 
-            let text = aes::decrypt_aes_128(&current_cipher, key, &BlockCipherMode::CBC(iv));
+            let text = mode::cbc_decrypt(block_cipher, iv, &current_cipher);
 
-            if text[3 * block_size + *pos] == *change as u8 {
+            if text[payload_start + *pos] == *change as u8 {
                 xor_block[*pos] = manipulated_byte;
                 break;
             }
@@ -52,9 +61,9 @@ pub fn cbc_bitflip<O>(
 
 
     [
-        &result[..2 * block_size],
+        &result[..flipped_block_start],
         &xor_block[..],
-        &result[3 * block_size..],
+        &result[payload_start..],
     ].concat()
 }
 
@@ -62,20 +71,49 @@ pub fn build_cbc_bitflip_oracle<'a>(
     key: &'a aes::Key,
     block_cipher_mode: &'a BlockCipherMode<'a>,
 ) -> impl Fn(&[u8]) -> Vec<u8> + 'a {
+    let block_cipher = AesNi::new(key);
+
     move |crafted_input: &[u8]| -> Vec<u8> {
         let encoded_input = prepend_and_append(&crafted_input);
 
-        aes::encrypt_aes_128(
+        aes::encrypt(
+            &block_cipher,
             &encoded_input,
-            &key,
-            &AESEncryptionOptions::new(block_cipher_mode, &Padding::None),
-        )
+            &AESEncryptionOptions::new(block_cipher_mode, &Padding::PKCS7),
+        ).unwrap()
     }
 }
 
-pub fn cbc_padding_attack<O>(original_cipher: &[u8], padding_oracle: O) -> Vec<u8>
-    where O: Fn(&[u8]) -> bool {
-    let block_size = 16;
+/// Builds a bitflip oracle over any block cipher used in CBC mode, PKCS#7 padding its input.
+pub fn build_block_cipher_cbc_bitflip_oracle<C: BlockCipher>(block_cipher: C, iv: &[u8])
+                                                             -> impl Fn(&[u8]) -> Vec<u8> {
+    let iv = iv.to_vec();
+
+    move |crafted_input: &[u8]| -> Vec<u8> {
+        let encoded_input = prepend_and_append(&crafted_input);
+        let padded_input = Padding::PKCS7.pad(&encoded_input, block_cipher.block_size());
+
+        mode::cbc_encrypt(&block_cipher, &iv, &padded_input)
+    }
+}
+
+/// Decrypts every block but the first of a CBC cipher, using an oracle telling whether a cipher
+/// decrypts to a plaintext validly padded with the given padding rule. Prepending the iv to the
+/// cipher also decrypts the first block.
+///
+/// The rule must check the padding bytes themselves, so ISO 10126 (where only the last byte is
+/// checked) and zero padding (always valid) can't be attacked.
+pub fn cbc_padding_attack<O>(
+    original_cipher: &[u8],
+    block_size: usize,
+    padding: &Padding,
+    padding_oracle: O,
+) -> Vec<u8> where O: Fn(&[u8]) -> bool {
+    match padding {
+        Padding::PKCS7 | Padding::AnsiX923 | Padding::Iso7816 => {}
+        _ => panic!("The oracle does not leak anything with {:?} padding.", padding),
+    }
+
     let mut result = Vec::new();
 
     for i in 0usize..(original_cipher.len() as f32 / block_size as f32).floor() as usize - 1 {
@@ -83,23 +121,26 @@ pub fn cbc_padding_attack<O>(original_cipher: &[u8], padding_oracle: O) -> Vec<u
         let last_block = &original_cipher[(i * block_size) + block_size..(i * block_size) +
             (block_size * 2)];
 
-        let mut block_result = Vec::new();
-        let mut intermediate_block = Vec::new();
+        // decryption of the last block, before it is xored with the penultimate block
+        let mut intermediate_block = vec![0u8; block_size];
 
         let rand_bytes = &generate_bytes_for_length(block_size)[..];
 
-        let mut found = false;
-        for padding_length in 1u8..=16u8 {
-            let xored_intermediate_block = &intermediate_block.iter()
-                .map(|v| v ^ padding_length)
-                .rev()
+        for padding_length in 1..=block_size {
+            let position = block_size - padding_length;
+            // the padding of that length, which the guessed byte must start for the oracle
+            // to accept the plaintext
+            let pad = &padding.pad(&vec![0u8; position], block_size)[position..];
+            let forced_bytes = &intermediate_block[position + 1..].iter()
+                .zip(pad[1..].iter())
+                .map(|(intermediate, pad_byte)| intermediate ^ pad_byte)
                 .collect::<Vec<u8>>()[..];
 
             for padding_byte in 0u8..=255u8 {
                 let modified_penultimate_block = &[
-                    &rand_bytes[..block_size - padding_length as usize],
+                    &rand_bytes[..position],
                     &[padding_byte][..],
-                    &xored_intermediate_block[..]
+                    &forced_bytes[..]
                 ].concat();
 
                 assert_eq!(modified_penultimate_block.len(), block_size);
@@ -109,57 +150,111 @@ pub fn cbc_padding_attack<O>(original_cipher: &[u8], padding_oracle: O) -> Vec<u
                     &last_block
                 ].concat();
 
-                let padding_is_valid = padding_oracle(&modified_cipher);
+                let padding_is_valid = padding_oracle(&modified_cipher)
+                    && !is_other_padding(&modified_cipher, position, &padding_oracle);
                 if padding_is_valid {
-                    intermediate_block.push(padding_byte ^ padding_length);
-                    block_result.push(
-                        penultimate_block[block_size - padding_length as usize] ^
-                            *intermediate_block.last().unwrap());
+                    intermediate_block[position] = padding_byte ^ pad[0];
                     break;
                 }
             }
         }
 
-        let mut ordered_block_result = &mut block_result.iter().cloned().rev().collect::<Vec<u8>>();
-        result.append(ordered_block_result);
+        let mut block_result = xor::fixed_xor(&intermediate_block, penultimate_block);
+        result.append(&mut block_result);
     }
 
     result
 }
 
-pub fn build_cbc_padding_oracle<'a>(key: &'a aes::Key, iv: &'a aes::Iv,
-) -> impl Fn(&[u8]) -> bool + 'a {
-    move |cipher: &[u8]| -> bool {
-        let cipher_with_iv_and_key = CipherWithIvAndKey {
-            cipher: cipher.to_vec(),
-            key,
-            iv,
-        };
+/// The plaintext's bytes before the guessed one may happen to make another padding valid (e.g.
+/// \x02\x02 when looking for a one-byte PKCS#7 padding). Changing those bytes invalidates such a
+/// padding, but not the sought for one.
+fn is_other_padding<O>(modified_cipher: &[u8], position: usize, padding_oracle: &O) -> bool
+    where O: Fn(&[u8]) -> bool {
+    if position == 0 {
+        return false;
+    }
+
+    let mut changed_cipher = modified_cipher.to_vec();
+    for byte in changed_cipher[..position].iter_mut() {
+        *byte ^= 0x01;
+    }
+
+    !padding_oracle(&changed_cipher)
+}
+
+pub fn build_cbc_padding_oracle(key: &aes::Key, iv: &aes::Iv) -> impl Fn(&[u8]) -> bool {
+    build_block_cipher_cbc_padding_oracle(AesNi::new(key), &iv.to_bytes(), &Padding::PKCS7)
+}
+
+/// Builds an oracle enforcing the given padding rule over any block cipher used in CBC mode.
+pub fn build_block_cipher_cbc_padding_oracle<C: BlockCipher>(
+    block_cipher: C,
+    iv: &[u8],
+    padding: &Padding,
+) -> impl Fn(&[u8]) -> bool {
+    let iv = iv.to_vec();
+    let padding = *padding;
 
-        check_cipher_padding(&cipher_with_iv_and_key)
+    move |cipher: &[u8]| -> bool {
+        has_valid_padding(&block_cipher, cipher, &iv, &padding)
     }
 }
 
 /// models the server's consumption of an encrypted session token, as if it was a cookie
 pub fn check_cipher_padding(cipher_with_iv_and_key: &CipherWithIvAndKey) -> bool {
-    let deciphered = aes::decrypt_aes_128(
+    has_valid_padding(
+        &AesNi::new(cipher_with_iv_and_key.key),
         &cipher_with_iv_and_key.cipher,
-        &cipher_with_iv_and_key.key,
-        &BlockCipherMode::CBC(cipher_with_iv_and_key.iv),
-    );
+        &cipher_with_iv_and_key.iv.to_bytes(),
+        &Padding::PKCS7,
+    )
+}
 
-    let block_size = 16;
+fn has_valid_padding<C: BlockCipher>(block_cipher: &C, cipher: &[u8], iv: &[u8], padding: &Padding)
+                                     -> bool {
+    let deciphered = mode::cbc_decrypt(block_cipher, iv, cipher);
 
-    match aes::validate_pkcs7_pad(&deciphered, block_size) {
-        Err(PaddingError) => false,
-        Ok(_) => {
-            true
-        }
-    }
+    padding.validate(&deciphered, block_cipher.block_size()).is_ok()
+}
+
+/// Forges a CBC-MAC tag from two messages and their tags: the second message, whose first block is
+/// xored with the first tag, continues the CBC chain of the first one. The concatenation thus has
+/// the second tag, without the key being involved.
+pub fn forge_cbc_mac_by_concatenation(
+    message: &[u8],
+    tag: &[u8],
+    other_message: &[u8],
+    other_tag: &[u8],
+) -> (Vec<u8>, Vec<u8>) {
+    let block_size = tag.len();
+    let chained_first_block = xor::fixed_xor(&other_message[..block_size], tag);
+
+    let forged_message = [
+        message,
+        &chained_first_block[..],
+        &other_message[block_size..],
+    ].concat();
+
+    (forged_message, other_tag.to_vec())
+}
+
+/// Forges a CBC-MAC tag when the verifier takes the iv from the sender: the first block of the
+/// message is only ever xored with the iv, so changing both alike keeps the tag. Returns the
+/// message with the chosen first block, and the iv to send along with it.
+pub fn forge_cbc_mac_with_iv(message: &[u8], iv: &[u8], first_block: &[u8])
+                             -> (Vec<u8>, Vec<u8>) {
+    let block_size = iv.len();
+    let difference = xor::fixed_xor(&message[..block_size], first_block);
+
+    let forged_message = [first_block, &message[block_size..]].concat();
+    let forged_iv = xor::fixed_xor(iv, &difference);
+
+    (forged_message, forged_iv)
 }
 
 fn prepend_and_append(input: &[u8]) -> Vec<u8> {
-    let prefix = "comment1=cooking%20MCs;userdata=".as_bytes();
+    let prefix = PREFIX.as_bytes();
     let suffix = ";comment2=%20like%20a%20pound%20of%20bacon".as_bytes();
 
     let mut sanitized_input = Vec::with_capacity(input.len());
@@ -172,4 +267,118 @@ fn prepend_and_append(input: &[u8]) -> Vec<u8> {
     }
 
     [&prefix[..], &sanitized_input[..], &suffix[..]].concat()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::Aes;
+    use aes::t_table::TTableAes;
+    use mode::toy::ToyCipher;
+    use mode::mac;
+
+    #[test]
+    fn cbc_bitflip_with_t_table_aes() {
+        let key = &aes::generate::generate_aes_128_key();
+        let iv = &generate_bytes_for_length(16);
+        let oracle = build_block_cipher_cbc_bitflip_oracle(TTableAes::new(key), iv);
+
+        let cipher = cbc_bitflip(&oracle, &TTableAes::new(key), iv);
+
+        let text = mode::cbc_decrypt(&TTableAes::new(key), iv, &cipher);
+        assert!(String::from_utf8_lossy(&text).contains(";admin=true;"));
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit in a 8-byte block")]
+    fn cbc_bitflip_needs_the_payload_to_fit_in_a_block() {
+        let block_cipher = ToyCipher::new(0xfeedfacecafebeef);
+        let iv = &generate_bytes_for_length(8);
+        let oracle = build_block_cipher_cbc_bitflip_oracle(ToyCipher::new(0xfeedfacecafebeef), iv);
+
+        cbc_bitflip(&oracle, &block_cipher, iv);
+    }
+
+    #[test]
+    fn cbc_padding_attack_with_toy_cipher() {
+        let block_cipher = ToyCipher::new(0xfeedfacecafebeef);
+        let block_size = block_cipher.block_size();
+        let iv = &generate_bytes_for_length(block_size);
+        let padded_raw = &aes::pkcs7_pad(
+            "an 8-byte block cipher is also vulnerable".as_bytes(),
+            block_size,
+        );
+        let cipher = &mode::cbc_encrypt(&block_cipher, iv, padded_raw);
+        let oracle = build_block_cipher_cbc_padding_oracle(block_cipher, iv, &Padding::PKCS7);
+
+        let deciphered = cbc_padding_attack(
+            &[&iv[..], &cipher[..]].concat(),
+            block_size,
+            &Padding::PKCS7,
+            oracle,
+        );
+
+        assert_eq!(&deciphered, padded_raw);
+    }
+
+    #[test]
+    fn cbc_padding_attack_with_other_paddings() {
+        let raw = "padding oracles are not specific to PKCS#7".as_bytes();
+
+        for padding in [Padding::AnsiX923, Padding::Iso7816].iter() {
+            let block_cipher = Aes::new(&aes::generate::generate_aes_128_key());
+            let iv = &generate_bytes_for_length(16);
+            let padded_raw = &padding.pad(raw, 16);
+            let cipher = &mode::cbc_encrypt(&block_cipher, iv, padded_raw);
+            let oracle = build_block_cipher_cbc_padding_oracle(block_cipher, iv, padding);
+
+            let deciphered = cbc_padding_attack(
+                &[&iv[..], &cipher[..]].concat(),
+                16,
+                padding,
+                oracle,
+            );
+
+            assert_eq!(&deciphered, padded_raw);
+        }
+    }
+
+    #[test]
+    fn forge_cbc_mac_by_concatenation_test() {
+        let block_cipher = Aes::new(&aes::generate::generate_aes_128_key());
+        let message = "from=alice&to=bob&amount=10&memo".as_bytes();
+        let other_message = "&to=mallory&amount=1000000000000".as_bytes();
+        let tag = mac::cbc_mac(&block_cipher, message);
+        let other_tag = mac::cbc_mac(&block_cipher, other_message);
+
+        let (forged_message, forged_tag) = forge_cbc_mac_by_concatenation(
+            message, &tag, other_message, &other_tag,
+        );
+
+        assert!(forged_message.ends_with(&other_message[16..]));
+        assert_eq!(mac::cbc_mac(&block_cipher, &forged_message), forged_tag);
+        // CMAC's last block subkey breaks the chain
+        let cmac_tag = mac::cmac(&block_cipher, message);
+        let other_cmac_tag = mac::cmac(&block_cipher, other_message);
+        let (forged_message, forged_tag) = forge_cbc_mac_by_concatenation(
+            message, &cmac_tag, other_message, &other_cmac_tag,
+        );
+        assert!(!mac::verify_cmac(&block_cipher, &forged_message, &forged_tag));
+    }
+
+    #[test]
+    fn forge_cbc_mac_with_iv_test() {
+        let block_cipher = Aes::new(&aes::generate::generate_aes_128_key());
+        let iv = &generate_bytes_for_length(16);
+        let message = "amount=00000010&from=alice&to=bob".as_bytes();
+        let padded_message = &aes::pkcs7_pad(message, 16);
+        let tag = mac::cbc_mac_with_iv(&block_cipher, iv, padded_message);
+
+        let (forged_message, forged_iv) = forge_cbc_mac_with_iv(
+            padded_message, iv, "amount=99999999&".as_bytes(),
+        );
+
+        assert!(forged_message.starts_with("amount=99999999&from=alice&to=bob".as_bytes()));
+        assert_eq!(mac::cbc_mac_with_iv(&block_cipher, &forged_iv, &forged_message), tag);
+    }
+}
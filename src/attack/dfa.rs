@@ -0,0 +1,249 @@
+/// Resources used:
+/// https://link.springer.com/chapter/10.1007/978-3-540-45238-6_7
+/// (Piret and Quisquater, A Differential Fault Attack Technique against SPN Structures, with
+/// Application to the AES and Khazad)
+/// https://en.wikipedia.org/wiki/Differential_fault_analysis
+
+use std::collections::HashSet;
+use rand::Rng;
+use aes::{Key, KeySize, ReducedRoundAes, Transformation, INVERSE_S_BOX, inverse_key_expansion};
+use aes::generate::generate_bytes_for_length;
+use aes::math::multiply_in_g;
+
+/// Round before whose MixColumns the faults are injected.
+const FAULTY_ROUND: usize = 9;
+
+/// Columns of the MixColumns matrix: a difference f in row i of a column becomes
+/// MIX_COLUMNS[i][r] • f in its row r.
+const MIX_COLUMNS: [[u8; 4]; 4] = [
+    [0x02, 0x01, 0x01, 0x03],
+    [0x03, 0x02, 0x01, 0x01],
+    [0x01, 0x03, 0x02, 0x01],
+    [0x01, 0x01, 0x03, 0x02],
+];
+
+/// Most plaintexts the attack encrypts before giving up.
+const MAX_PAIRS: usize = 64;
+
+/// Correct and faulty ciphers of the same plaintext.
+pub type CipherPair = (Vec<u8>, Vec<u8>);
+
+/// Recovers the key of AES-128 from an oracle encrypting plaintexts twice, once correctly and once
+/// with a fault on a byte of the State before the round 9 MixColumns. Each new pair narrows the
+/// candidates left by the previous ones.
+pub fn differential_fault_attack<O>(oracle: O) -> Key where O: Fn(&[u8]) -> CipherPair {
+    let mut candidates = KeyCandidates::new();
+
+    for _ in 0..MAX_PAIRS {
+        let (correct, faulty) = oracle(&generate_bytes_for_length(16));
+        candidates.add_pair(&correct, &faulty);
+
+        if let Some(last_round_key) = candidates.last_round_key() {
+            return inverse_key_expansion(&last_round_key, 10, KeySize::AES128).key();
+        }
+    }
+
+    panic!("The oracle does not fault AES-128 before the round {} MixColumns.", FAULTY_ROUND);
+}
+
+/// Recovers the Round Key of the last round from pairs of correct and faulty ciphers, when they
+/// single out one key. A fault in a byte of a column spreads through MixColumns to the 4 bytes of
+/// the column, which the last round, without MixColumns, takes to 4 bytes of the cipher. For
+/// each of the 4 x 255 possible faults, the 4 key bytes of those cipher bytes are the ones which,
+/// undoing the last round, give the differences MixColumns makes of the fault. About 2 pairs
+/// faulting each column leave a single candidate. Returns None while a column is ambiguous.
+pub fn recover_last_round_key(pairs: &[CipherPair]) -> Option<[u8; 16]> {
+    let mut candidates = KeyCandidates::new();
+    for (correct, faulty) in pairs.iter() {
+        candidates.add_pair(correct, faulty);
+    }
+
+    candidates.last_round_key()
+}
+
+/// Candidates for the 4 key bytes of each column, None until a pair faults the column.
+struct KeyCandidates {
+    columns: [Option<HashSet<[u8; 4]>>; 4],
+}
+
+impl KeyCandidates {
+    fn new() -> Self {
+        KeyCandidates { columns: [None, None, None, None] }
+    }
+
+    /// Keeps the candidates of the faulted column which also explain the pair.
+    fn add_pair(&mut self, correct: &[u8], faulty: &[u8]) {
+        let column = match faulted_column(correct, faulty) {
+            Some(column) => column,
+            // not a single byte fault before the round 9 MixColumns
+            None => return,
+        };
+
+        let pair_candidates = column_key_candidates(correct, faulty, column);
+        self.columns[column] = Some(match self.columns[column].take() {
+            Some(column_candidates) => &column_candidates & &pair_candidates,
+            None => pair_candidates,
+        });
+    }
+
+    fn last_round_key(&self) -> Option<[u8; 16]> {
+        let mut last_round_key = [0u8; 16];
+        for (column, column_candidates) in self.columns.iter().enumerate() {
+            let column_candidates = column_candidates.as_ref()?;
+            if column_candidates.len() != 1 {
+                return None;
+            }
+
+            let key_bytes = column_candidates.iter().next().unwrap();
+            for (row, key_byte) in key_bytes.iter().enumerate() {
+                last_round_key[cipher_position(column, row)] = *key_byte;
+            }
+        }
+
+        Some(last_round_key)
+    }
+}
+
+/// Position in the cipher of the byte in the given row of a column after the round 9
+/// MixColumns: the last ShiftRows moves it to column c - r.
+fn cipher_position(column: usize, row: usize) -> usize {
+    4 * ((column + 4 - row) % 4) + row
+}
+
+/// Column faulted before the round 9 MixColumns, if the ciphers differ in its 4 bytes and only
+/// in them.
+fn faulted_column(correct: &[u8], faulty: &[u8]) -> Option<usize> {
+    let differing: Vec<usize> = (0..16).filter(|i| correct[*i] != faulty[*i]).collect();
+
+    (0..4).find(|column| {
+        let mut positions: Vec<usize> = (0..4).map(|row| cipher_position(*column, row)).collect();
+        positions.sort();
+
+        positions == differing
+    })
+}
+
+/// Key bytes of the cipher positions of the column which explain the pair for some fault, in the
+/// order of the rows of the column.
+fn column_key_candidates(correct: &[u8], faulty: &[u8], column: usize) -> HashSet<[u8; 4]> {
+    // for each row, the key bytes giving each difference before the last SubBytes
+    let mut keys_by_difference = vec![vec![vec![]; 256]; 4];
+    for (row, row_keys) in keys_by_difference.iter_mut().enumerate() {
+        let position = cipher_position(column, row);
+        for key_byte in 0..=255u8 {
+            let difference = INVERSE_S_BOX[(correct[position] ^ key_byte) as usize]
+                ^ INVERSE_S_BOX[(faulty[position] ^ key_byte) as usize];
+            row_keys[difference as usize].push(key_byte);
+        }
+    }
+
+    let mut candidates = HashSet::new();
+    // the coefficients a fault in each row gets in the rows of the column
+    for coefficients in MIX_COLUMNS.iter() {
+        for fault in 1..=255u8 {
+            let keys: Vec<&Vec<u8>> = coefficients.iter()
+                .zip(keys_by_difference.iter())
+                .map(|(coefficient, row_keys)| {
+                    &row_keys[multiply_in_g(*coefficient, fault) as usize]
+                })
+                .collect();
+
+            for k0 in keys[0].iter() {
+                for k1 in keys[1].iter() {
+                    for k2 in keys[2].iter() {
+                        for k3 in keys[3].iter() {
+                            candidates.insert([*k0, *k1, *k2, *k3]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    candidates
+}
+
+/// Builds the oracle encrypting a plaintext with AES-128 under the key, once correctly and once
+/// with a random fault xored into a random byte of the State before the round 9 MixColumns, as
+/// a glitch on an embedded device would.
+pub fn build_fault_injection_oracle(key: &Key) -> impl Fn(&[u8]) -> CipherPair {
+    assert_eq!(key.size(), KeySize::AES128);
+    let block_cipher = ReducedRoundAes::new(key, 10);
+
+    move |raw: &[u8]| -> CipherPair {
+        let mut rng = rand::thread_rng();
+        let (row, column) = (rng.gen_range(0, 4), rng.gen_range(0, 4));
+        let fault = rng.gen_range(1, 256) as u8;
+
+        let mut correct = raw.to_vec();
+        block_cipher.encrypt_block_with_hook(&mut correct, |_, _, _| {});
+        let mut faulty = raw.to_vec();
+        block_cipher.encrypt_block_with_hook(&mut faulty, |round, transformation, state| {
+            if round == FAULTY_ROUND && transformation == Transformation::ShiftRows {
+                let byte = state.byte(row, column);
+                state.set_byte(row, column, byte ^ fault);
+            }
+        });
+
+        (correct, faulty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::generate::generate_aes_128_key;
+
+    #[test]
+    fn differential_fault_attack_recovers_a_random_key() {
+        let key = generate_aes_128_key();
+
+        let recovered_key = differential_fault_attack(build_fault_injection_oracle(&key));
+
+        assert_eq!(recovered_key, key);
+    }
+
+    #[test]
+    fn faults_reach_4_bytes_of_the_cipher() {
+        let key = generate_aes_128_key();
+        let oracle = build_fault_injection_oracle(&key);
+
+        for _ in 0..20 {
+            let (correct, faulty) = oracle(&generate_bytes_for_length(16));
+
+            assert!(faulted_column(&correct, &faulty).is_some());
+        }
+    }
+
+    #[test]
+    fn recover_last_round_key_needs_every_column() {
+        let key = generate_aes_128_key();
+        let oracle = build_fault_injection_oracle(&key);
+        let mut pairs: Vec<CipherPair> = vec![];
+
+        // only pairs faulting the first column
+        while pairs.len() < 3 {
+            let (correct, faulty) = oracle(&generate_bytes_for_length(16));
+            if faulted_column(&correct, &faulty) == Some(0) {
+                pairs.push((correct, faulty));
+            }
+        }
+
+        assert_eq!(recover_last_round_key(&[]), None);
+        assert_eq!(recover_last_round_key(&pairs), None);
+    }
+
+    #[test]
+    fn recover_last_round_key_ignores_other_faults() {
+        let key = generate_aes_128_key();
+        let oracle = build_fault_injection_oracle(&key);
+        let mut pairs = vec![(vec![0u8; 16], vec![1u8; 16])];
+
+        while recover_last_round_key(&pairs).is_none() {
+            pairs.push(oracle(&generate_bytes_for_length(16)));
+        }
+
+        let last_round_key = ReducedRoundAes::new(&key, 10).round_key(10);
+        assert_eq!(recover_last_round_key(&pairs), Some(last_round_key));
+    }
+}
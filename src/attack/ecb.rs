@@ -1,4 +1,4 @@
-use aes::{Key, AESEncryptionOptions, Padding, BlockCipherMode, Iv};
+use aes::{Key, AESEncryptionOptions, Padding, BlockCipherMode, Iv, AesNi};
 use ::{aes, human};
 use std::ops::Range;
 use std::collections::{HashMap, HashSet};
@@ -14,7 +14,7 @@ pub fn byte_at_a_time_ecb_simple_decryption<O>(
     let block_size = detect_oracle_block_size(&oracle, placeholder_byte);
 
     let repeated_bytes = vec![placeholder_byte; block_size * 8];
-    confirm_oracle_mode(&oracle, &repeated_bytes, &BlockCipherMode::ECB);
+    confirm_oracle_mode(&oracle, &repeated_bytes, block_size, &BlockCipherMode::ECB);
 
     let mut known_characters = Vec::with_capacity(
         oracle(&prefix).len() - (controlled_bytes_block_start_index * block_size)
@@ -54,20 +54,27 @@ pub fn byte_at_a_time_ecb_simple_decryption<O>(
     known_characters
 }
 
-fn confirm_oracle_mode<O>(oracle: &O, bytes: &[u8], mode: &BlockCipherMode) -> () where O:
-Fn(&[u8]) -> Vec<u8> {
+fn confirm_oracle_mode<O>(oracle: &O, bytes: &[u8], block_size: usize, mode: &BlockCipherMode)
+                         -> () where O: Fn(&[u8]) -> Vec<u8> {
     let cipher = oracle(&bytes);
 
     let iv = &aes::Iv::empty();
-    let block_cipher_mode = detect_block_cipher_mode(&cipher, iv);
+    let block_cipher_mode = detect_block_cipher_mode_for_block_size(&cipher, block_size, iv);
     if &block_cipher_mode != mode {
         panic!("Wrong block cipher mode.");
     }
 }
 
-/// Detectes the block cipher mode of a cipher.
+/// Detectes the block cipher mode of an AES cipher. A single cipher can only tell ECB apart, use
+/// `detect_oracle_block_cipher_mode_family` to recognize the stream-like modes with chosen plaintext.
 pub fn detect_block_cipher_mode<'a>(cipher: &[u8], iv: &'a Iv) -> BlockCipherMode<'a> {
-    let chunks = cipher.chunks(16);
+    detect_block_cipher_mode_for_block_size(cipher, 16, iv)
+}
+
+/// Detectes the block cipher mode of a cipher made of blocks of the given size.
+pub fn detect_block_cipher_mode_for_block_size<'a>(cipher: &[u8], block_size: usize, iv: &'a Iv)
+                                                   -> BlockCipherMode<'a> {
+    let chunks = cipher.chunks(block_size);
     let chunks_count = chunks.len();
 
     if chunks_count < 2 {
@@ -83,6 +90,71 @@ pub fn detect_block_cipher_mode<'a>(cipher: &[u8], iv: &'a Iv) -> BlockCipherMod
     }
 }
 
+/// Family of block cipher modes, as far as it can be told from chosen plaintexts.
+#[derive(PartialEq, Debug)]
+pub enum ModeFamily {
+    ECB,
+    CBC,
+    /// Cipher Feedback, whatever the segment size.
+    CFB,
+    /// Modes xoring the plaintext with a keystream which does not depend on it (OFB, CTR).
+    Stream,
+}
+
+/// Detects the block cipher mode family of an oracle encrypting chosen plaintexts under a fixed key
+/// and iv, possibly surrounded by bytes out of our control.
+///
+/// Repeated plaintext blocks give repeated cipher blocks in ECB only. Otherwise, flipping bits of
+/// one plaintext byte flips the very same bits of the cipher in the stream-like modes: the cipher
+/// is left untouched elsewhere with a keystream, while the change spreads to the following
+/// segments in CFB. In CBC, the whole cipher block changes instead.
+pub fn detect_oracle_block_cipher_mode_family<O>(oracle: O, block_size: usize) -> ModeFamily
+    where O: Fn(&[u8]) -> Vec<u8> {
+    let input = vec![b'A'; 4 * block_size];
+    let cipher = oracle(&input);
+
+    let blocks = cipher.chunks(block_size).collect::<Vec<&[u8]>>();
+    let unique_blocks = blocks.iter().collect::<HashSet<&&[u8]>>();
+    if unique_blocks.len() < blocks.len() {
+        return ModeFamily::ECB;
+    }
+
+    // only the lowest bit is flipped, as in CFB-1 the following bits of the byte are already
+    // encrypted under the changed feedback. Two adjacent bytes are flipped in turn: in CBC, the
+    // changes start at a block boundary rather than at adjacent cipher bytes. They are flipped at
+    // the start of the input, so that at least a whole segment follows them in CFB-128 even
+    // without a suffix
+    let mut first_changes = Vec::with_capacity(2);
+    let mut spreads = false;
+    for flipped_index in [0, 1].iter() {
+        let mut flipped_input = input.clone();
+        flipped_input[*flipped_index] ^= 0x01;
+        let flipped_cipher = oracle(&flipped_input);
+
+        let differing_indices = cipher.iter()
+            .zip(flipped_cipher.iter())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(i, _)| i)
+            .collect::<Vec<usize>>();
+
+        match differing_indices.first() {
+            Some(&first) => first_changes.push((first, cipher[first] ^ flipped_cipher[first])),
+            None => panic!("Oracle ignores its input."),
+        }
+        spreads |= differing_indices.len() > 1;
+    }
+
+    let keeps_flipped_bits = first_changes[1].0 == first_changes[0].0 + 1
+        && first_changes.iter().all(|(_, change)| *change == 0x01);
+
+    match (keeps_flipped_bits, spreads) {
+        (true, false) => ModeFamily::Stream,
+        (true, true) => ModeFamily::CFB,
+        (false, _) => ModeFamily::CBC,
+    }
+}
+
 fn detect_oracle_block_size<O>(oracle: &O, placeholder_byte: u8) -> usize where O: Fn(&[u8])
     -> Vec<u8> {
     for bytes_count in 1..64 * 8 {
@@ -150,7 +222,7 @@ pub fn ecb_cut_and_paste(key: &Key) -> Vec<u8> {
         let last_email_bytes = &profile_bytes[block_size..block_size + (initial_email_bytes.len() -
             first_email_bytes.len())];
         let admin_role = "admin".to_string();
-        let crafted_admin_block = aes::pkcs7_pad(admin_role.as_bytes(), block_size as u8);
+        let crafted_admin_block = aes::pkcs7_pad(admin_role.as_bytes(), block_size);
 
         [first_email_bytes, &crafted_admin_block[..], last_email_bytes].concat()
     };
@@ -160,7 +232,7 @@ pub fn ecb_cut_and_paste(key: &Key) -> Vec<u8> {
         crafted_profile.as_bytes(),
         &key,
         &AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::PKCS7),
-    );
+    ).unwrap();
 
     let email_cipher_block = &cipher_to_create_admin_block[email_block_range];
 
@@ -185,7 +257,7 @@ pub fn ecb_cut_and_paste(key: &Key) -> Vec<u8> {
         crafted_profile_to_replace_role.as_bytes(),
         &key,
         &AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::PKCS7),
-    );
+    ).unwrap();
 
     let mut i = cipher_to_replace_role.len() - 1;
     for byte in email_cipher_block.iter().rev() {
@@ -213,7 +285,7 @@ pub fn byte_at_a_time_ecb_harder_decryption<O>(oracle: O) -> Vec<u8> where O: Fn
     let block_size = detect_oracle_block_size(&oracle, placeholder_byte);
     let repeated_bytes = vec![placeholder_byte; block_size * 8];
 
-    confirm_oracle_mode(&oracle, &repeated_bytes, &BlockCipherMode::ECB);
+    confirm_oracle_mode(&oracle, &repeated_bytes, block_size, &BlockCipherMode::ECB);
 
     let original_cipher = oracle(&vec![]);
     let original_cipher_blocks = original_cipher
@@ -272,14 +344,16 @@ pub fn byte_at_a_time_ecb_harder_decryption<O>(oracle: O) -> Vec<u8> where O: Fn
 pub fn build_byte_at_a_time_simple_oracle<'a>(
     unknown_string: &'a Vec<u8>,
     key: &'a Key) -> impl Fn(&[u8]) -> Vec<u8> + 'a {
+    let block_cipher = AesNi::new(key);
+
     move |crafted_input: &[u8]| -> Vec<u8> {
         let input = &[&crafted_input[..], &unknown_string[..]].concat();
 
-        aes::encrypt_aes_128(
+        aes::encrypt(
+            &block_cipher,
             &input,
-            &key,
             &AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::PKCS7),
-        )
+        ).unwrap()
     }
 }
 
@@ -287,6 +361,8 @@ pub fn build_byte_at_a_time_harder_oracle<'a>(
     random_prefix: &'a Vec<u8>,
     unknown_string: &'a Vec<u8>,
     key: &'a Key) -> impl Fn(&[u8]) -> Vec<u8> + 'a {
+    let block_cipher = AesNi::new(key);
+
     move |crafted_input: &[u8]| -> Vec<u8> {
         let input = &[
             &random_prefix[..],
@@ -294,17 +370,18 @@ pub fn build_byte_at_a_time_harder_oracle<'a>(
             &unknown_string[..]
         ].concat();
 
-        aes::encrypt_aes_128(
+        aes::encrypt(
+            &block_cipher,
             &input,
-            &key,
             &AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::PKCS7),
-        )
+        ).unwrap()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aes::Aes;
 
     #[test]
     fn profile_for_test() {
@@ -314,4 +391,42 @@ mod tests {
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn detect_oracle_block_cipher_mode_family_test() {
+        let key = &aes::generate::generate_aes_128_key();
+        let iv = &aes::generate::generate_aes_128_cbc_iv();
+        let nonce = &[0x42; 8];
+        let test_cases = vec![
+            (BlockCipherMode::ECB, ModeFamily::ECB),
+            (BlockCipherMode::CBC(iv), ModeFamily::CBC),
+            (BlockCipherMode::CTR(nonce), ModeFamily::Stream),
+            (BlockCipherMode::OFB(iv), ModeFamily::Stream),
+            (BlockCipherMode::CFB1(iv), ModeFamily::CFB),
+            (BlockCipherMode::CFB8(iv), ModeFamily::CFB),
+            (BlockCipherMode::CFB128(iv), ModeFamily::CFB),
+        ];
+
+        for (mode, expected_family) in test_cases.iter() {
+            let block_cipher = Aes::new(key);
+            let prefix = aes::generate::generate_bytes_for_length(7);
+            let oracle = |input: &[u8]| aes::encrypt(
+                &block_cipher,
+                &[&prefix[..], input, "and a suffix".as_bytes()].concat(),
+                &AESEncryptionOptions::new(mode, &Padding::PKCS7),
+            ).unwrap();
+
+            assert_eq!(&detect_oracle_block_cipher_mode_family(oracle, 16), expected_family);
+        }
+
+        // without a suffix nor a padding, nothing follows the input in the cipher
+        let block_cipher = Aes::new(key);
+        let mode = &BlockCipherMode::CFB128(iv);
+        let oracle = |input: &[u8]| aes::encrypt(
+            &block_cipher,
+            input,
+            &AESEncryptionOptions::new(mode, &Padding::None),
+        ).unwrap();
+        assert_eq!(detect_oracle_block_cipher_mode_family(oracle, 16), ModeFamily::CFB);
+    }
 }
\ No newline at end of file
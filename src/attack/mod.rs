@@ -2,7 +2,9 @@ use ::aes::{Iv, Key};
 
 pub mod ctr;
 pub mod cbc;
+pub mod dfa;
 pub mod ecb;
+pub mod square;
 
 pub struct CipherWithIvAndKey<'a> {
     pub cipher: Vec<u8>,
@@ -0,0 +1,144 @@
+/// Resources used:
+/// https://www.davidwong.fr/blockbreakers/square.html
+/// https://en.wikipedia.org/wiki/Integral_cryptanalysis
+
+use aes::{Key, KeySize, ReducedRoundAes, INVERSE_S_BOX, inverse_key_expansion};
+use mode::BlockCipher;
+
+/// Number of rounds of the AES-128 the attack breaks.
+const ROUNDS: usize = 4;
+
+/// Most Λ-sets the attack encrypts before giving up: against 4 rounds, a handful leaves a single
+/// candidate per byte, while fewer rounds keep every guess balanced on every Λ-set.
+const MAX_LAMBDA_SETS: u8 = 16;
+
+/// Recovers the key of 4-round AES-128, given an oracle encrypting chosen blocks under it.
+pub fn square_attack<O>(oracle: O) -> Key where O: Fn(&[u8]) -> Vec<u8> {
+    let last_round_key = recover_last_round_key(&oracle);
+    let key = inverse_key_expansion(&last_round_key, ROUNDS, KeySize::AES128).key();
+
+    // a wrong guess surviving every Λ-set would give a key the oracle does not encrypt with
+    let block = &[0u8; 16];
+    if build_four_round_aes_oracle(&key)(block) != oracle(block) {
+        panic!("The oracle does not encrypt with 4-round AES-128.");
+    }
+
+    key
+}
+
+/// Recovers the Round Key of the last round from Λ-sets: 256 plaintexts whose first byte takes
+/// every value, the others being constant. After 3 rounds, every byte of the States of a Λ-set
+/// xors to zero. The last round has no MixColumns, so each byte of that Round Key is guessed on
+/// its own, by undoing AddRoundKey and SubBytes on that byte of the ciphers and checking that
+/// the xor is still zero. A wrong guess passes with probability 1/256, a few Λ-sets rule out
+/// every one.
+pub fn recover_last_round_key<O>(oracle: &O) -> [u8; 16] where O: Fn(&[u8]) -> Vec<u8> {
+    let mut candidates: Vec<Vec<u8>> = vec![(0..=255).collect(); 16];
+
+    for constant in 0..MAX_LAMBDA_SETS {
+        if candidates.iter().all(|position_candidates| position_candidates.len() == 1) {
+            break;
+        }
+        let ciphers = encrypt_lambda_set(oracle, constant);
+
+        for (position, position_candidates) in candidates.iter_mut().enumerate() {
+            position_candidates.retain(|guess| is_balanced(&ciphers, position, *guess));
+            if position_candidates.is_empty() {
+                panic!("The oracle does not encrypt with 4-round AES-128.");
+            }
+        }
+    }
+    if candidates.iter().any(|position_candidates| position_candidates.len() > 1) {
+        panic!("The oracle does not encrypt with 4-round AES-128.");
+    }
+
+    let mut last_round_key = [0u8; 16];
+    for (byte, position_candidates) in last_round_key.iter_mut().zip(candidates.iter()) {
+        *byte = position_candidates[0];
+    }
+
+    last_round_key
+}
+
+/// Ciphers of the Λ-set whose inactive bytes all hold the given constant.
+fn encrypt_lambda_set<O>(oracle: &O, constant: u8) -> Vec<Vec<u8>>
+    where O: Fn(&[u8]) -> Vec<u8> {
+    (0..=255)
+        .map(|active_byte| {
+            let mut block = [constant; 16];
+            block[0] = active_byte;
+
+            oracle(&block)
+        })
+        .collect()
+}
+
+/// Whether the byte of the States before the last round, recovered from the ciphers with the
+/// guessed Round Key byte, xors to zero.
+fn is_balanced(ciphers: &[Vec<u8>], position: usize, guess: u8) -> bool {
+    ciphers.iter()
+        .fold(0u8, |sum, cipher| sum ^ INVERSE_S_BOX[(cipher[position] ^ guess) as usize]) == 0
+}
+
+/// Builds the oracle encrypting blocks with 4-round AES-128 under the key.
+pub fn build_four_round_aes_oracle(key: &Key) -> impl Fn(&[u8]) -> Vec<u8> {
+    let block_cipher = ReducedRoundAes::new(key, ROUNDS);
+
+    move |block: &[u8]| -> Vec<u8> {
+        let mut cipher = block.to_vec();
+        block_cipher.encrypt_block(&mut cipher);
+
+        cipher
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::generate::generate_aes_128_key;
+
+    #[test]
+    fn square_attack_recovers_a_random_key() {
+        let key = generate_aes_128_key();
+
+        let recovered_key = square_attack(build_four_round_aes_oracle(&key));
+
+        assert_eq!(recovered_key, key);
+    }
+
+    #[test]
+    fn recover_last_round_key_test() {
+        let key = generate_aes_128_key();
+        let oracle = build_four_round_aes_oracle(&key);
+
+        let last_round_key = recover_last_round_key(&oracle);
+
+        assert_eq!(last_round_key, ReducedRoundAes::new(&key, 4).round_key(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "does not encrypt with 4-round AES-128")]
+    fn square_attack_fails_against_2_rounds() {
+        let block_cipher = ReducedRoundAes::new(&generate_aes_128_key(), 2);
+
+        square_attack(|block: &[u8]| {
+            let mut cipher = block.to_vec();
+            block_cipher.encrypt_block(&mut cipher);
+
+            cipher
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "does not encrypt with 4-round AES-128")]
+    fn square_attack_fails_against_5_rounds() {
+        let block_cipher = ReducedRoundAes::new(&generate_aes_128_key(), 5);
+
+        square_attack(|block: &[u8]| {
+            let mut cipher = block.to_vec();
+            block_cipher.encrypt_block(&mut cipher);
+
+            cipher
+        });
+    }
+}
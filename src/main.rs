@@ -5,6 +5,7 @@ extern crate rand;
 mod human;
 mod hex;
 mod aes;
+mod mode;
 mod file;
 mod xor;
 mod attack;
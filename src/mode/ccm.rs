@@ -0,0 +1,343 @@
+/// Resources used:
+/// https://nvlpubs.nist.gov/nistpubs/Legacy/SP/nistspecialpublication800-38c.pdf
+/// https://tools.ietf.org/html/rfc3610
+
+use mode;
+use mode::{AuthenticationError, BlockCipher};
+use mode::counter::CounterBlock;
+use mode::in_place::ctr_in_place;
+use mode::mac::cbc_mac;
+
+/// Tag lengths, in bytes, allowed by SP 800-38C (Sec. A.1).
+pub const TAG_LENGTHS: [usize; 7] = [4, 6, 8, 10, 12, 14, 16];
+
+/// Counter with CBC-MAC: the tag is the CBC-MAC of a first block describing the nonce and the
+/// lengths, then of the formatted additional data and bytes. The bytes and the tag are then
+/// encrypted in CTR mode. The nonce is 7 to 13 bytes long, the shorter it is, the longer the
+/// sealed bytes can be. Returns the cipher followed by the tag.
+pub fn ccm_seal<C: BlockCipher>(
+    block_cipher: &C,
+    nonce: &[u8],
+    additional_data: &[u8],
+    raw_bytes: &[u8],
+    tag_length: usize,
+) -> Vec<u8> {
+    assert!(TAG_LENGTHS.contains(&tag_length), "Invalid CCM tag length.");
+
+    let ccm = Ccm::new(block_cipher, nonce);
+    let tag = ccm.tag(additional_data, raw_bytes, tag_length);
+
+    [ccm.ctr(raw_bytes), ccm.encrypt_tag(&tag)].concat()
+}
+
+/// Decrypts sealed bytes, the cipher followed by its tag, once the tag has been verified in
+/// constant time. The sealed bytes and the nonce come from the sender, so their lengths are
+/// checked rather than asserted.
+pub fn ccm_open<C: BlockCipher>(
+    block_cipher: &C,
+    nonce: &[u8],
+    additional_data: &[u8],
+    sealed: &[u8],
+    tag_length: usize,
+) -> Result<Vec<u8>, AuthenticationError> {
+    if sealed.len() < tag_length {
+        return Err(AuthenticationError::InvalidLength);
+    }
+    let (cipher, encrypted_tag) = sealed.split_at(sealed.len() - tag_length);
+    check_open_lengths(nonce, cipher.len(), tag_length)?;
+
+    let ccm = Ccm::new(block_cipher, nonce);
+    let raw_bytes = ccm.ctr(cipher);
+
+    let expected_tag = ccm.encrypt_tag(&ccm.tag(additional_data, &raw_bytes, tag_length));
+    if !mode::constant_time_eq(&expected_tag, encrypted_tag) {
+        return Err(AuthenticationError::TagMismatch);
+    }
+
+    Ok(raw_bytes)
+}
+
+/// Same as `ccm_seal`, the cipher taking the place of the bytes in the buffer. Returns the
+/// encrypted tag. The CBC-MAC is still computed over a formatted copy of the bytes.
+pub fn ccm_seal_in_place<C: BlockCipher>(
+    block_cipher: &C,
+    nonce: &[u8],
+    additional_data: &[u8],
+    buffer: &mut [u8],
+    tag_length: usize,
+) -> Vec<u8> {
+    assert!(TAG_LENGTHS.contains(&tag_length), "Invalid CCM tag length.");
+
+    let ccm = Ccm::new(block_cipher, nonce);
+    let tag = ccm.tag(additional_data, buffer, tag_length);
+    ctr_in_place(block_cipher, &ccm.counter_block().with_initial_counter(1), buffer);
+
+    ccm.encrypt_tag(&tag)
+}
+
+/// Same as `ccm_open`, the bytes taking the place of the cipher in the buffer, the encrypted tag
+/// being given on its own. The tag can only be verified once the bytes are decrypted: when it
+/// doesn't match, the buffer is encrypted back.
+pub fn ccm_open_in_place<C: BlockCipher>(
+    block_cipher: &C,
+    nonce: &[u8],
+    additional_data: &[u8],
+    buffer: &mut [u8],
+    encrypted_tag: &[u8],
+) -> Result<(), AuthenticationError> {
+    check_open_lengths(nonce, buffer.len(), encrypted_tag.len())?;
+
+    let ccm = Ccm::new(block_cipher, nonce);
+    let counter_block = ccm.counter_block().with_initial_counter(1);
+    ctr_in_place(block_cipher, &counter_block, buffer);
+
+    let expected_tag = ccm.encrypt_tag(&ccm.tag(additional_data, buffer, encrypted_tag.len()));
+    if !mode::constant_time_eq(&expected_tag, encrypted_tag) {
+        ctr_in_place(block_cipher, &counter_block, buffer);
+        return Err(AuthenticationError::TagMismatch);
+    }
+
+    Ok(())
+}
+
+fn check_open_lengths(nonce: &[u8], cipher_length: usize, tag_length: usize)
+                      -> Result<(), AuthenticationError> {
+    if !TAG_LENGTHS.contains(&tag_length) {
+        Err(AuthenticationError::InvalidTagLength)
+    } else if !is_valid_nonce_length(nonce.len()) {
+        Err(AuthenticationError::InvalidNonceLength)
+    } else if !fits_length_field(nonce.len(), cipher_length) {
+        Err(AuthenticationError::InvalidLength)
+    } else {
+        Ok(())
+    }
+}
+
+fn is_valid_nonce_length(nonce_length: usize) -> bool {
+    nonce_length >= 7 && nonce_length <= 13
+}
+
+/// Whether the length fits the length field the nonce leaves room for, 15 - its length bytes.
+fn fits_length_field(nonce_length: usize, length: usize) -> bool {
+    let q = 15 - nonce_length;
+
+    q >= 8 || (length as u64) < 1 << (8 * q)
+}
+
+struct Ccm<'a, C: BlockCipher + 'a> {
+    block_cipher: &'a C,
+    nonce: Vec<u8>,
+    /// Length in bytes of the message length field, which is also the length of the counter.
+    length_field_length: usize,
+}
+
+impl<'a, C: BlockCipher> Ccm<'a, C> {
+    fn new(block_cipher: &'a C, nonce: &[u8]) -> Self {
+        assert_eq!(block_cipher.block_size(), 16, "CCM requires a 128-bit block cipher.");
+        assert!(is_valid_nonce_length(nonce.len()), "CCM nonces are 7 to 13 bytes long.");
+
+        Ccm {
+            block_cipher,
+            nonce: nonce.to_vec(),
+            length_field_length: 15 - nonce.len(),
+        }
+    }
+
+    /// Counter blocks: flags holding the length of the counter, the nonce, then the counter. The
+    /// counter 0 encrypts the tag, the bytes start at counter 1.
+    fn counter_block(&self) -> CounterBlock {
+        let flags = (self.length_field_length - 1) as u8;
+
+        CounterBlock::big_endian(&[&[flags][..], &self.nonce[..]].concat())
+    }
+
+    fn ctr(&self, bytes: &[u8]) -> Vec<u8> {
+        mode::ctr(self.block_cipher, &self.counter_block().with_initial_counter(1), bytes)
+    }
+
+    fn encrypt_tag(&self, tag: &[u8]) -> Vec<u8> {
+        mode::ctr(self.block_cipher, &self.counter_block(), tag)
+    }
+
+    /// CBC-MAC of the formatted input of SP 800-38C, Sec. A.2, truncated to the tag length.
+    fn tag(&self, additional_data: &[u8], raw_bytes: &[u8], tag_length: usize) -> Vec<u8> {
+        let q = self.length_field_length;
+        assert!(fits_length_field(self.nonce.len(), raw_bytes.len()),
+                "Bytes too long for the length of the CCM nonce.");
+
+        let flags = (!additional_data.is_empty() as u8) << 6
+            | ((tag_length as u8 - 2) / 2) << 3
+            | (q as u8 - 1);
+        let first_block = [
+            &[flags][..],
+            &self.nonce[..],
+            &(raw_bytes.len() as u64).to_be_bytes()[8 - q..],
+        ].concat();
+
+        let mut formatted = first_block;
+        if !additional_data.is_empty() {
+            formatted.extend(encode_additional_data_length(additional_data.len()));
+            formatted.extend_from_slice(additional_data);
+            pad_to_block(&mut formatted);
+        }
+        formatted.extend_from_slice(raw_bytes);
+        pad_to_block(&mut formatted);
+
+        let mut tag = cbc_mac(self.block_cipher, &formatted);
+        tag.truncate(tag_length);
+
+        tag
+    }
+}
+
+/// Length of the additional data, on 2 bytes when it is below 2^16 - 2^8, else on 4 or 8 bytes
+/// after a 0xfffe or 0xffff marker.
+fn encode_additional_data_length(length: usize) -> Vec<u8> {
+    let length = length as u64;
+
+    if length < (1 << 16) - (1 << 8) {
+        (length as u16).to_be_bytes().to_vec()
+    } else if length < 1 << 32 {
+        [&[0xff, 0xfe][..], &(length as u32).to_be_bytes()[..]].concat()
+    } else {
+        [&[0xff, 0xff][..], &length.to_be_bytes()[..]].concat()
+    }
+}
+
+fn pad_to_block(bytes: &mut Vec<u8>) {
+    let padded_length = (bytes.len() + 15) / 16 * 16;
+    bytes.resize(padded_length, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::{Aes, Key};
+    use hex::hex_string_to_bytes;
+
+    fn sp_800_38c_cipher() -> Aes {
+        Aes::new(&Key::new(&hex_string_to_bytes("404142434445464748494a4b4c4d4e4f")))
+    }
+
+    #[test]
+    fn ccm_sp_800_38c_examples() {
+        // (nonce, additional data, raw bytes, sealed bytes) of examples 1 to 3 of Appendix C
+        let test_cases = vec![
+            ("10111213141516", "0001020304050607", "20212223", "7162015b4dac255d"),
+            ("1011121314151617", "000102030405060708090a0b0c0d0e0f",
+             "202122232425262728292a2b2c2d2e2f", "d2a1f0e051ea5f62081a7792073d593d1fc64fbfaccd"),
+            ("101112131415161718191a1b", "000102030405060708090a0b0c0d0e0f10111213",
+             "202122232425262728292a2b2c2d2e2f3031323334353637",
+             "e3b201a9f5b71a7a9b1ceaeccd97e70b6176aad9a4428aa5484392fbc1b09951"),
+        ];
+
+        for (nonce, additional_data, raw, expected_sealed) in test_cases.iter() {
+            let (nonce, additional_data) = (hex_string_to_bytes(nonce),
+                                            hex_string_to_bytes(additional_data));
+            let raw = hex_string_to_bytes(raw);
+            let tag_length = expected_sealed.len() / 2 - raw.len();
+
+            let sealed = ccm_seal(&sp_800_38c_cipher(), &nonce, &additional_data, &raw,
+                                  tag_length);
+            let opened = ccm_open(&sp_800_38c_cipher(), &nonce, &additional_data, &sealed,
+                                  tag_length);
+
+            assert_eq!(sealed, hex_string_to_bytes(expected_sealed));
+            assert_eq!(opened, Ok(raw));
+        }
+    }
+
+    #[test]
+    fn ccm_with_long_additional_data_sp_800_38c_example_4() {
+        let nonce = hex_string_to_bytes("101112131415161718191a1b1c");
+        let additional_data = (0..65536).map(|i| i as u8).collect::<Vec<u8>>();
+        let raw = hex_string_to_bytes(
+            "202122232425262728292a2b2c2d2e2f303132333435363738393a3b3c3d3e3f"
+        );
+
+        let sealed = ccm_seal(&sp_800_38c_cipher(), &nonce, &additional_data, &raw, 14);
+
+        assert_eq!(sealed, hex_string_to_bytes(
+            "69915dad1e84c6376a68c2967e4dab615ae0fd1faec44cc484828529463ccf72\
+             b4ac6bec93e8598e7f0dadbcea5b"
+        ));
+    }
+
+    #[test]
+    fn ccm_rfc_3610_packet_vector_1() {
+        let block_cipher = Aes::new(&Key::new(&hex_string_to_bytes(
+            "c0c1c2c3c4c5c6c7c8c9cacbcccdcecf"
+        )));
+        let nonce = hex_string_to_bytes("00000003020100a0a1a2a3a4a5");
+        let additional_data = (0..8).collect::<Vec<u8>>();
+        let raw = (8..31).collect::<Vec<u8>>();
+
+        let sealed = ccm_seal(&block_cipher, &nonce, &additional_data, &raw, 8);
+
+        assert_eq!(sealed, hex_string_to_bytes(
+            "588c979a61c663d2f066d0c2c0f989806d5f6b61dac38417e8d12cfdf926e0"
+        ));
+    }
+
+    #[test]
+    fn ccm_open_rejects_altered_bytes() {
+        let block_cipher = sp_800_38c_cipher();
+        let nonce = &[0x17; 7];
+        let sealed = ccm_seal(&block_cipher, nonce, b"header", b"attack at dawn", 16);
+        let mut altered = sealed.clone();
+        altered[3] ^= 0x01;
+
+        assert_eq!(ccm_open(&block_cipher, nonce, b"header", &altered, 16),
+                   Err(AuthenticationError::TagMismatch));
+        assert_eq!(ccm_open(&block_cipher, nonce, b"footer", &sealed, 16),
+                   Err(AuthenticationError::TagMismatch));
+        assert_eq!(ccm_open(&block_cipher, &[0x17; 8], b"header", &sealed, 16),
+                   Err(AuthenticationError::TagMismatch));
+        assert_eq!(ccm_open(&block_cipher, nonce, b"header", &sealed, 5),
+                   Err(AuthenticationError::InvalidTagLength));
+        assert_eq!(ccm_open(&block_cipher, nonce, b"header", &sealed[..10], 16),
+                   Err(AuthenticationError::InvalidLength));
+        assert_eq!(ccm_open(&block_cipher, nonce, b"header", &sealed, 16),
+                   Ok(b"attack at dawn".to_vec()));
+    }
+
+    #[test]
+    fn ccm_open_rejects_what_the_nonce_does_not_allow() {
+        let block_cipher = sp_800_38c_cipher();
+        let sealed = &vec![0u8; 65536 + 16];
+        let buffer = &mut vec![0u8; 65536];
+
+        assert_eq!(ccm_open(&block_cipher, &[0x17; 6], b"", &sealed[..32], 16),
+                   Err(AuthenticationError::InvalidNonceLength));
+        assert_eq!(ccm_open(&block_cipher, &[0x17; 14], b"", &sealed[..32], 16),
+                   Err(AuthenticationError::InvalidNonceLength));
+        assert_eq!(ccm_open_in_place(&block_cipher, &[0x17; 14], b"", buffer, &[0; 16]),
+                   Err(AuthenticationError::InvalidNonceLength));
+        // a 13-byte nonce leaves 2 bytes for the length
+        assert_eq!(ccm_open(&block_cipher, &[0x17; 13], b"", sealed, 16),
+                   Err(AuthenticationError::InvalidLength));
+        assert_eq!(ccm_open_in_place(&block_cipher, &[0x17; 13], b"", buffer, &[0; 16]),
+                   Err(AuthenticationError::InvalidLength));
+        assert_eq!(ccm_open(&block_cipher, &[0x17; 12], b"", sealed, 16),
+                   Err(AuthenticationError::TagMismatch));
+    }
+
+    #[test]
+    fn ccm_in_place_matches_ccm() {
+        let block_cipher = sp_800_38c_cipher();
+        let nonce = &[0x17; 13];
+        let raw = &(0..37).collect::<Vec<u8>>();
+        let sealed = ccm_seal(&block_cipher, nonce, b"header", raw, 10);
+        let buffer = &mut raw.clone();
+
+        let encrypted_tag = ccm_seal_in_place(&block_cipher, nonce, b"header", buffer, 10);
+
+        assert_eq!(&[&buffer[..], &encrypted_tag[..]].concat(), &sealed);
+        assert_eq!(ccm_open_in_place(&block_cipher, nonce, b"footer", buffer, &encrypted_tag),
+                   Err(AuthenticationError::TagMismatch));
+        assert_eq!(buffer[..], sealed[..37]);
+        assert_eq!(ccm_open_in_place(&block_cipher, nonce, b"header", buffer, &encrypted_tag),
+                   Ok(()));
+        assert_eq!(buffer, raw);
+    }
+}
@@ -0,0 +1,128 @@
+/// Resources used:
+/// https://cryptopals.com/sets/3/challenges/18
+/// https://tools.ietf.org/html/rfc3686#section-4
+/// https://nvlpubs.nist.gov/nistpubs/Legacy/SP/nistspecialpublication800-38a.pdf (Appendix B)
+
+/// Byte order in which the counter is written in the counter block.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CounterEndianness {
+    Little,
+    Big,
+}
+
+/// Layout of the counter blocks encrypted in CTR mode: the nonce, followed by a counter filling
+/// the rest of the block. The counter of the n-th block is the initial counter plus n, wrapping
+/// around when it overflows the bytes it is given.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CounterBlock {
+    nonce: Vec<u8>,
+    endianness: CounterEndianness,
+    initial_counter: u128,
+}
+
+impl CounterBlock {
+    /// Little-endian counter following the nonce, as specified by cryptopals (with an 8-byte
+    /// nonce, the counter is a 64-bit little-endian integer).
+    pub fn little_endian(nonce: &[u8]) -> Self {
+        CounterBlock::new(nonce, CounterEndianness::Little)
+    }
+
+    /// Big-endian counter following the nonce. With a 12-byte nonce (the 4-byte nonce then 8-byte
+    /// iv of RFC 3686) and an initial counter of 1, this is the RFC 3686 layout. With an empty
+    /// nonce, the whole block is the counter, incremented as in SP 800-38A.
+    pub fn big_endian(nonce: &[u8]) -> Self {
+        CounterBlock::new(nonce, CounterEndianness::Big)
+    }
+
+    fn new(nonce: &[u8], endianness: CounterEndianness) -> Self {
+        CounterBlock {
+            nonce: nonce.to_vec(),
+            endianness,
+            initial_counter: 0,
+        }
+    }
+
+    pub fn with_initial_counter(mut self, initial_counter: u128) -> Self {
+        self.initial_counter = initial_counter;
+
+        self
+    }
+
+    /// Counter block of the block at the given index of the keystream.
+    pub fn block(&self, block_index: u64, block_size: usize) -> Vec<u8> {
+        let mut block = vec![0u8; block_size];
+        self.write_block(block_index, &mut block);
+
+        block
+    }
+
+    /// Writes the counter block of the block at the given index over a block, without allocating.
+    pub fn write_block(&self, block_index: u64, block: &mut [u8]) {
+        assert!(self.nonce.len() < block.len());
+        let counter_length = block.len() - self.nonce.len();
+        assert!(counter_length <= 16, "Counter can't be longer than 128 bits.");
+
+        let mut counter = self.initial_counter.wrapping_add(block_index as u128);
+        if counter_length < 16 {
+            counter &= (1u128 << (8 * counter_length)) - 1;
+        }
+
+        let (nonce, counter_bytes) = block.split_at_mut(self.nonce.len());
+        nonce.copy_from_slice(&self.nonce);
+        match self.endianness {
+            CounterEndianness::Little => {
+                counter_bytes.copy_from_slice(&counter.to_le_bytes()[..counter_length])
+            }
+            CounterEndianness::Big => {
+                counter_bytes.copy_from_slice(&counter.to_be_bytes()[16 - counter_length..])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn little_endian_counter_block_test() {
+        let counter_block = CounterBlock::little_endian(&[0xaa; 8]);
+
+        assert_eq!(counter_block.block(0, 16), [&[0xaa; 8][..], &[0x00; 8][..]].concat());
+        assert_eq!(counter_block.block(1, 16),
+                   [&[0xaa; 8][..], &[0x01][..], &[0x00; 7][..]].concat());
+        // the counter does not overflow after 256 blocks
+        assert_eq!(counter_block.block(256, 16),
+                   [&[0xaa; 8][..], &[0x00, 0x01][..], &[0x00; 6][..]].concat());
+        assert_eq!(counter_block.block(u64::max_value(), 16),
+                   [&[0xaa; 8][..], &[0xff; 8][..]].concat());
+    }
+
+    #[test]
+    fn big_endian_counter_block_test() {
+        let counter_block = CounterBlock::big_endian(&[0xaa; 12]).with_initial_counter(1);
+
+        assert_eq!(counter_block.block(0, 16), [&[0xaa; 12][..], &[0, 0, 0, 1][..]].concat());
+        assert_eq!(counter_block.block(0x1ff, 16),
+                   [&[0xaa; 12][..], &[0, 0, 0x02, 0x00][..]].concat());
+    }
+
+    #[test]
+    fn counter_wraps_around_within_its_bytes() {
+        let counter_block = CounterBlock::big_endian(&[0xaa; 12])
+            .with_initial_counter(0xffff_ffff);
+
+        assert_eq!(counter_block.block(1, 16), [&[0xaa; 12][..], &[0x00; 4][..]].concat());
+    }
+
+    #[test]
+    fn whole_block_counter_test() {
+        let counter_block = CounterBlock::big_endian(&[])
+            .with_initial_counter(0xf0f1f2f3f4f5f6f7f8f9fafbfcfdfeff);
+
+        assert_eq!(counter_block.block(1, 16), vec![
+            0xf0, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7,
+            0xf8, 0xf9, 0xfa, 0xfb, 0xfc, 0xfd, 0xff, 0x00
+        ]);
+    }
+}
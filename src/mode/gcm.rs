@@ -0,0 +1,356 @@
+/// Resources used:
+/// https://nvlpubs.nist.gov/nistpubs/Legacy/SP/nistspecialpublication800-38d.pdf
+/// https://csrc.nist.rip/groups/ST/toolkit/BCM/documents/proposedmodes/gcm/gcm-spec.pdf
+
+use aes::math::Gf128;
+use mode;
+use mode::{AuthenticationError, BlockCipher};
+use mode::counter::CounterBlock;
+use mode::in_place::ctr_in_place;
+
+/// Tag lengths, in bytes, allowed by SP 800-38D (Sec. 5.2.1.2).
+pub const TAG_LENGTHS: [usize; 7] = [16, 15, 14, 13, 12, 8, 4];
+
+/// Galois/Counter Mode: the bytes are encrypted in CTR mode, then the additional authenticated
+/// data and the cipher are authenticated with GHASH, a polynomial evaluated in GF(2^128) at the
+/// hash subkey H. Returns the cipher and a tag of the given length in bytes.
+pub fn gcm_encrypt<C: BlockCipher>(
+    block_cipher: &C,
+    iv: &[u8],
+    additional_data: &[u8],
+    raw_bytes: &[u8],
+    tag_length: usize,
+) -> (Vec<u8>, Vec<u8>) {
+    assert!(TAG_LENGTHS.contains(&tag_length), "Invalid GCM tag length.");
+
+    let gcm = Gcm::new(block_cipher, iv);
+    let cipher = gcm.gctr(raw_bytes);
+    let tag = gcm.tag(additional_data, &cipher, tag_length);
+
+    (cipher, tag)
+}
+
+/// Decrypts a GCM cipher, once its tag has been verified. The comparison of the tags takes the
+/// same time wherever they differ.
+pub fn gcm_decrypt<C: BlockCipher>(
+    block_cipher: &C,
+    iv: &[u8],
+    additional_data: &[u8],
+    cipher: &[u8],
+    tag: &[u8],
+) -> Result<Vec<u8>, AuthenticationError> {
+    if !TAG_LENGTHS.contains(&tag.len()) {
+        return Err(AuthenticationError::InvalidTagLength);
+    }
+
+    let gcm = Gcm::new(block_cipher, iv);
+    let expected_tag = gcm.tag(additional_data, cipher, tag.len());
+    if !mode::constant_time_eq(&expected_tag, tag) {
+        return Err(AuthenticationError::TagMismatch);
+    }
+
+    Ok(gcm.gctr(cipher))
+}
+
+/// Same as `gcm_encrypt`, the cipher taking the place of the bytes in the buffer instead of being
+/// copied. Returns the tag.
+pub fn gcm_encrypt_in_place<C: BlockCipher>(
+    block_cipher: &C,
+    iv: &[u8],
+    additional_data: &[u8],
+    buffer: &mut [u8],
+    tag_length: usize,
+) -> Vec<u8> {
+    assert!(TAG_LENGTHS.contains(&tag_length), "Invalid GCM tag length.");
+
+    let gcm = Gcm::new(block_cipher, iv);
+    ctr_in_place(block_cipher, &gcm.counter_block(), buffer);
+
+    gcm.tag(additional_data, buffer, tag_length)
+}
+
+/// Same as `gcm_decrypt`, the bytes taking the place of the cipher in the buffer. The buffer is
+/// left untouched when the tag doesn't match.
+pub fn gcm_decrypt_in_place<C: BlockCipher>(
+    block_cipher: &C,
+    iv: &[u8],
+    additional_data: &[u8],
+    buffer: &mut [u8],
+    tag: &[u8],
+) -> Result<(), AuthenticationError> {
+    if !TAG_LENGTHS.contains(&tag.len()) {
+        return Err(AuthenticationError::InvalidTagLength);
+    }
+
+    let gcm = Gcm::new(block_cipher, iv);
+    let expected_tag = gcm.tag(additional_data, buffer, tag.len());
+    if !mode::constant_time_eq(&expected_tag, tag) {
+        return Err(AuthenticationError::TagMismatch);
+    }
+
+    ctr_in_place(block_cipher, &gcm.counter_block(), buffer);
+
+    Ok(())
+}
+
+struct Gcm<'a, C: BlockCipher + 'a> {
+    block_cipher: &'a C,
+    hash_subkey: Gf128,
+    /// Pre-counter block J0.
+    pre_counter_block: Vec<u8>,
+}
+
+impl<'a, C: BlockCipher> Gcm<'a, C> {
+    fn new(block_cipher: &'a C, iv: &[u8]) -> Self {
+        assert_eq!(block_cipher.block_size(), 16, "GCM requires a 128-bit block cipher.");
+        assert!(!iv.is_empty(), "GCM requires a non-empty iv.");
+
+        let mut hash_subkey = vec![0u8; 16];
+        block_cipher.encrypt_block(&mut hash_subkey);
+        let hash_subkey = Gf128::from_block(&hash_subkey);
+
+        // a 96-bit iv is followed by a 32-bit counter starting at 1, any other iv is hashed
+        let pre_counter_block = if iv.len() == 12 {
+            [iv, &[0, 0, 0, 1][..]].concat()
+        } else {
+            let length_block = [&[0u8; 8][..], &(8 * iv.len() as u64).to_be_bytes()[..]].concat();
+            ghash(&hash_subkey, &[iv, &length_block]).to_block().to_vec()
+        };
+
+        Gcm {
+            block_cipher,
+            hash_subkey,
+            pre_counter_block,
+        }
+    }
+
+    /// Counter blocks of GCTR, starting from inc32(J0): only the last 32 bits of the counter block
+    /// are incremented.
+    fn counter_block(&self) -> CounterBlock {
+        let mut counter = [0u8; 4];
+        counter.copy_from_slice(&self.pre_counter_block[12..]);
+
+        CounterBlock::big_endian(&self.pre_counter_block[..12])
+            .with_initial_counter(u32::from_be_bytes(counter) as u128 + 1)
+    }
+
+    fn gctr(&self, bytes: &[u8]) -> Vec<u8> {
+        mode::ctr(self.block_cipher, &self.counter_block(), bytes)
+    }
+
+    fn tag(&self, additional_data: &[u8], cipher: &[u8], tag_length: usize) -> Vec<u8> {
+        let length_block = [
+            &(8 * additional_data.len() as u64).to_be_bytes()[..],
+            &(8 * cipher.len() as u64).to_be_bytes()[..],
+        ].concat();
+        let s = ghash(&self.hash_subkey, &[additional_data, cipher, &length_block]);
+
+        let mut tag = self.pre_counter_block.clone();
+        self.block_cipher.encrypt_block(&mut tag);
+        for (tag_byte, s_byte) in tag.iter_mut().zip(s.to_block().iter()) {
+            *tag_byte ^= s_byte;
+        }
+        tag.truncate(tag_length);
+
+        tag
+    }
+}
+
+/// GHASH of the concatenation of the inputs, each of them padded with zeros to a multiple of the
+/// block size.
+fn ghash(hash_subkey: &Gf128, inputs: &[&[u8]]) -> Gf128 {
+    let mut y = Gf128::zero();
+
+    for input in inputs.iter() {
+        for chunk in input.chunks(16) {
+            let mut block = [0u8; 16];
+            block[..chunk.len()].copy_from_slice(chunk);
+            y = y.add(&Gf128::from_block(&block)).multiply(hash_subkey);
+        }
+    }
+
+    y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::{Aes, Key};
+    use hex::hex_string_to_bytes;
+
+    struct TestCase {
+        key: &'static str,
+        iv: &'static str,
+        raw: &'static str,
+        additional_data: &'static str,
+        cipher: &'static str,
+        tag: &'static str,
+    }
+
+    const RAW: &str = "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+                       1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b391aafd255";
+    const RAW_60: &str = "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+                          1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39";
+    const ADDITIONAL_DATA: &str = "feedfacedeadbeeffeedfacedeadbeefabaddad2";
+
+    /// Test cases 1 to 6 and 16 of the GCM specification.
+    fn test_cases() -> Vec<TestCase> {
+        vec![
+            TestCase {
+                key: "00000000000000000000000000000000",
+                iv: "000000000000000000000000",
+                raw: "",
+                additional_data: "",
+                cipher: "",
+                tag: "58e2fccefa7e3061367f1d57a4e7455a",
+            },
+            TestCase {
+                key: "00000000000000000000000000000000",
+                iv: "000000000000000000000000",
+                raw: "00000000000000000000000000000000",
+                additional_data: "",
+                cipher: "0388dace60b6a392f328c2b971b2fe78",
+                tag: "ab6e47d42cec13bdf53a67b21257bddf",
+            },
+            TestCase {
+                key: "feffe9928665731c6d6a8f9467308308",
+                iv: "cafebabefacedbaddecaf888",
+                raw: RAW,
+                additional_data: "",
+                cipher: "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+                         21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091473f5985",
+                tag: "4d5c2af327cd64a62cf35abd2ba6fab4",
+            },
+            TestCase {
+                key: "feffe9928665731c6d6a8f9467308308",
+                iv: "cafebabefacedbaddecaf888",
+                raw: RAW_60,
+                additional_data: ADDITIONAL_DATA,
+                cipher: "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+                         21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091",
+                tag: "5bc94fbc3221a5db94fae95ae7121a47",
+            },
+            TestCase {
+                key: "feffe9928665731c6d6a8f9467308308",
+                iv: "cafebabefacedbad",
+                raw: RAW_60,
+                additional_data: ADDITIONAL_DATA,
+                cipher: "61353b4c2806934a777ff51fa22a4755699b2a714fcdc6f83766e5f97b6c7423\
+                         73806900e49f24b22b097544d4896b424989b5e1ebac0f07c23f4598",
+                tag: "3612d2e79e3b0785561be14aaca2fccb",
+            },
+            TestCase {
+                key: "feffe9928665731c6d6a8f9467308308",
+                iv: "9313225df88406e555909c5aff5269aa6a7a9538534f7da1e4c303d2a318a728\
+                     c3c0c95156809539fcf0e2429a6b525416aedbf5a0de6a57a637b39b",
+                raw: RAW_60,
+                additional_data: ADDITIONAL_DATA,
+                cipher: "8ce24998625615b603a033aca13fb894be9112a5c3a211a8ba262a3cca7e2ca7\
+                         01e4a9a4fba43c90ccdcb281d48c7c6fd62875d2aca417034c34aee5",
+                tag: "619cc5aefffe0bfa462af43c1699d050",
+            },
+            TestCase {
+                key: "feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308",
+                iv: "cafebabefacedbaddecaf888",
+                raw: RAW_60,
+                additional_data: ADDITIONAL_DATA,
+                cipher: "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa\
+                         8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662",
+                tag: "76fc6ece0f4e1768cddf8853bb2d551b",
+            },
+        ]
+    }
+
+    #[test]
+    fn gcm_specification_test_cases() {
+        for case in test_cases().iter() {
+            let block_cipher = Aes::new(&Key::new(&hex_string_to_bytes(case.key)));
+            let iv = &hex_string_to_bytes(case.iv);
+            let raw = &hex_string_to_bytes(case.raw);
+            let additional_data = &hex_string_to_bytes(case.additional_data);
+
+            let (cipher, tag) = gcm_encrypt(&block_cipher, iv, additional_data, raw, 16);
+            let deciphered = gcm_decrypt(&block_cipher, iv, additional_data, &cipher, &tag);
+
+            assert_eq!(cipher, hex_string_to_bytes(case.cipher));
+            assert_eq!(tag, hex_string_to_bytes(case.tag));
+            assert_eq!(&deciphered.unwrap(), raw);
+        }
+    }
+
+    #[test]
+    fn gcm_truncated_tag_test() {
+        let case = &test_cases()[3];
+        let block_cipher = Aes::new(&Key::new(&hex_string_to_bytes(case.key)));
+        let iv = &hex_string_to_bytes(case.iv);
+        let additional_data = &hex_string_to_bytes(case.additional_data);
+
+        let (cipher, tag) = gcm_encrypt(
+            &block_cipher, iv, additional_data, &hex_string_to_bytes(case.raw), 12,
+        );
+
+        assert_eq!(tag, hex_string_to_bytes(case.tag)[..12].to_vec());
+        assert!(gcm_decrypt(&block_cipher, iv, additional_data, &cipher, &tag).is_ok());
+        assert_eq!(gcm_decrypt(&block_cipher, iv, additional_data, &cipher, &tag[..11]),
+                   Err(AuthenticationError::InvalidTagLength));
+    }
+
+    #[test]
+    fn gcm_rejects_tampering() {
+        let case = &test_cases()[3];
+        let block_cipher = Aes::new(&Key::new(&hex_string_to_bytes(case.key)));
+        let iv = &hex_string_to_bytes(case.iv);
+        let additional_data = &hex_string_to_bytes(case.additional_data);
+        let cipher = &hex_string_to_bytes(case.cipher);
+        let tag = &hex_string_to_bytes(case.tag);
+
+        let mut flipped_cipher = cipher.clone();
+        flipped_cipher[0] ^= 0x01;
+        let mut flipped_additional_data = additional_data.clone();
+        flipped_additional_data[0] ^= 0x01;
+        let mut flipped_tag = tag.clone();
+        flipped_tag[15] ^= 0x01;
+
+        assert_eq!(gcm_decrypt(&block_cipher, iv, additional_data, &flipped_cipher, tag),
+                   Err(AuthenticationError::TagMismatch));
+        assert_eq!(gcm_decrypt(&block_cipher, iv, &flipped_additional_data, cipher, tag),
+                   Err(AuthenticationError::TagMismatch));
+        assert_eq!(gcm_decrypt(&block_cipher, iv, additional_data, cipher, &flipped_tag),
+                   Err(AuthenticationError::TagMismatch));
+    }
+
+    #[test]
+    fn gcm_in_place_matches_gcm() {
+        for case in test_cases().iter() {
+            let block_cipher = Aes::new(&Key::new(&hex_string_to_bytes(case.key)));
+            let iv = &hex_string_to_bytes(case.iv);
+            let raw = &hex_string_to_bytes(case.raw);
+            let additional_data = &hex_string_to_bytes(case.additional_data);
+            let buffer = &mut raw.clone();
+
+            let tag = gcm_encrypt_in_place(&block_cipher, iv, additional_data, buffer, 16);
+
+            assert_eq!(buffer, &hex_string_to_bytes(case.cipher));
+            assert_eq!(tag, hex_string_to_bytes(case.tag));
+            assert_eq!(gcm_decrypt_in_place(&block_cipher, iv, additional_data, buffer, &tag),
+                       Ok(()));
+            assert_eq!(buffer, raw);
+        }
+    }
+
+    #[test]
+    fn gcm_in_place_leaves_the_cipher_when_the_tag_does_not_match() {
+        let case = &test_cases()[3];
+        let block_cipher = Aes::new(&Key::new(&hex_string_to_bytes(case.key)));
+        let iv = &hex_string_to_bytes(case.iv);
+        let additional_data = &hex_string_to_bytes(case.additional_data);
+        let cipher = &hex_string_to_bytes(case.cipher);
+        let mut flipped_tag = hex_string_to_bytes(case.tag);
+        flipped_tag[0] ^= 0x01;
+        let buffer = &mut cipher.clone();
+
+        assert_eq!(gcm_decrypt_in_place(&block_cipher, iv, additional_data, buffer, &flipped_tag),
+                   Err(AuthenticationError::TagMismatch));
+        assert_eq!(buffer, cipher);
+    }
+}
@@ -0,0 +1,361 @@
+/// Resources used:
+/// https://nvlpubs.nist.gov/nistpubs/Legacy/SP/nistspecialpublication800-38a.pdf
+/// https://csrc.nist.gov/publications/detail/sp/800-38a/addendum/final
+
+use mode::{BlockCipher, CiphertextStealing, shift_in_bit, swaps_last_blocks};
+use mode::counter::CounterBlock;
+
+/// Largest block the in-place modes keep on the stack.
+const MAX_BLOCK_SIZE: usize = 32;
+
+/// Block sized scratch space on the stack, so that the modes themselves never allocate. The block
+/// cipher still may: `Aes` copies every block into a State on the stack and back, `BitslicedAes`
+/// bitslices its batches into vectors.
+fn scratch_block<C: BlockCipher>(block_cipher: &C) -> [u8; MAX_BLOCK_SIZE] {
+    assert!(block_cipher.block_size() <= MAX_BLOCK_SIZE,
+            "In-place modes support blocks of up to {} bytes.", MAX_BLOCK_SIZE);
+
+    [0u8; MAX_BLOCK_SIZE]
+}
+
+pub(crate) fn xor_in_place(bytes: &mut [u8], other: &[u8]) {
+    for (byte, other_byte) in bytes.iter_mut().zip(other.iter()) {
+        *byte ^= other_byte;
+    }
+}
+
+/// Same as `mode::ecb_encrypt`, over a whole number of blocks.
+pub fn ecb_encrypt_in_place<C: BlockCipher>(block_cipher: &C, buffer: &mut [u8]) {
+    assert_eq!(buffer.len() % block_cipher.block_size(), 0, "ECB requires whole blocks.");
+
+    block_cipher.encrypt_blocks(buffer);
+}
+
+pub fn ecb_decrypt_in_place<C: BlockCipher>(block_cipher: &C, buffer: &mut [u8]) {
+    assert_eq!(buffer.len() % block_cipher.block_size(), 0, "ECB requires whole blocks.");
+
+    block_cipher.decrypt_blocks(buffer);
+}
+
+/// Same as `mode::cbc_encrypt`, over a whole number of blocks.
+pub fn cbc_encrypt_in_place<C: BlockCipher>(block_cipher: &C, iv: &[u8], buffer: &mut [u8]) {
+    let block_size = block_cipher.block_size();
+    assert_eq!(iv.len(), block_size);
+    assert_eq!(buffer.len() % block_size, 0, "CBC requires whole blocks.");
+
+    for i in 0..buffer.len() / block_size {
+        let (previous_blocks, blocks) = buffer.split_at_mut(i * block_size);
+        let block = &mut blocks[..block_size];
+        match i {
+            0 => xor_in_place(block, iv),
+            _ => xor_in_place(block, &previous_blocks[previous_blocks.len() - block_size..]),
+        }
+        block_cipher.encrypt_block(block);
+    }
+}
+
+/// Same as `mode::cbc_decrypt`, over a whole number of blocks. The blocks are decrypted from the
+/// last one, so that the cipher block each of them is xored with is still in the buffer.
+pub fn cbc_decrypt_in_place<C: BlockCipher>(block_cipher: &C, iv: &[u8], buffer: &mut [u8]) {
+    let block_size = block_cipher.block_size();
+    assert_eq!(iv.len(), block_size);
+    assert_eq!(buffer.len() % block_size, 0, "CBC requires whole blocks.");
+
+    for i in (0..buffer.len() / block_size).rev() {
+        let (previous_blocks, blocks) = buffer.split_at_mut(i * block_size);
+        let block = &mut blocks[..block_size];
+        block_cipher.decrypt_block(block);
+        match i {
+            0 => xor_in_place(block, iv),
+            _ => xor_in_place(block, &previous_blocks[previous_blocks.len() - block_size..]),
+        }
+    }
+}
+
+/// Same as `mode::cbc_cs_encrypt`: the last two blocks go through the stack.
+pub fn cbc_cs_encrypt_in_place<C: BlockCipher>(
+    block_cipher: &C,
+    iv: &[u8],
+    variant: CiphertextStealing,
+    buffer: &mut [u8],
+) {
+    let block_size = block_cipher.block_size();
+    assert!(buffer.len() >= block_size, "Ciphertext stealing requires at least one block.");
+
+    let n = (buffer.len() + block_size - 1) / block_size;
+    let whole_length = (n - 1) * block_size;
+    if n == 1 {
+        return cbc_encrypt_in_place(block_cipher, iv, buffer);
+    }
+
+    let last_length = buffer.len() - whole_length;
+    cbc_encrypt_in_place(block_cipher, iv, &mut buffer[..whole_length]);
+
+    let (mut second_to_last_block, mut last_block) =
+        (scratch_block(block_cipher), scratch_block(block_cipher));
+    let (second_to_last_block, last_block) =
+        (&mut second_to_last_block[..block_size], &mut last_block[..block_size]);
+    second_to_last_block.copy_from_slice(&buffer[whole_length - block_size..whole_length]);
+    // the last block filled with zeros
+    last_block.copy_from_slice(second_to_last_block);
+    xor_in_place(last_block, &buffer[whole_length..]);
+    block_cipher.encrypt_block(last_block);
+
+    let tail = &mut buffer[whole_length - block_size..];
+    if swaps_last_blocks(variant, last_length, block_size) {
+        tail[..block_size].copy_from_slice(last_block);
+        tail[block_size..].copy_from_slice(&second_to_last_block[..last_length]);
+    } else {
+        tail[last_length..].copy_from_slice(last_block);
+    }
+}
+
+/// Same as `mode::cbc_cs_decrypt`: the last two blocks are decrypted first, through the stack,
+/// then the others in place.
+pub fn cbc_cs_decrypt_in_place<C: BlockCipher>(
+    block_cipher: &C,
+    iv: &[u8],
+    variant: CiphertextStealing,
+    buffer: &mut [u8],
+) {
+    let block_size = block_cipher.block_size();
+    assert!(buffer.len() >= block_size, "Ciphertext stealing requires at least one block.");
+
+    let n = (buffer.len() + block_size - 1) / block_size;
+    if n == 1 {
+        return cbc_decrypt_in_place(block_cipher, iv, buffer);
+    }
+
+    let last_length = buffer.len() - (n - 1) * block_size;
+    let (head, tail) = buffer.split_at_mut((n - 2) * block_size);
+    let (mut second_to_last_block, mut last_block) =
+        (scratch_block(block_cipher), scratch_block(block_cipher));
+    let (second_to_last_block, last_block) =
+        (&mut second_to_last_block[..block_size], &mut last_block[..block_size]);
+    if swaps_last_blocks(variant, last_length, block_size) {
+        last_block.copy_from_slice(&tail[..block_size]);
+        second_to_last_block[..last_length].copy_from_slice(&tail[block_size..]);
+    } else {
+        second_to_last_block[..last_length].copy_from_slice(&tail[..last_length]);
+        last_block.copy_from_slice(&tail[last_length..]);
+    }
+
+    // the decrypted last block is the last plaintext block, filled with zeros, xored with the
+    // second to last cipher block: its left out bytes show through the zeros
+    block_cipher.decrypt_block(last_block);
+    second_to_last_block[last_length..].copy_from_slice(&last_block[last_length..]);
+    xor_in_place(&mut last_block[..last_length], second_to_last_block);
+
+    block_cipher.decrypt_block(second_to_last_block);
+    match head.len() {
+        0 => xor_in_place(second_to_last_block, iv),
+        _ => xor_in_place(second_to_last_block, &head[head.len() - block_size..]),
+    }
+
+    tail[..block_size].copy_from_slice(second_to_last_block);
+    tail[block_size..].copy_from_slice(&last_block[..last_length]);
+    cbc_decrypt_in_place(block_cipher, iv, head);
+}
+
+/// Same as `mode::ofb`, encrypting as well as decrypting.
+pub fn ofb_in_place<C: BlockCipher>(block_cipher: &C, iv: &[u8], buffer: &mut [u8]) {
+    let block_size = block_cipher.block_size();
+    assert_eq!(iv.len(), block_size);
+
+    let mut output_block = scratch_block(block_cipher);
+    let output_block = &mut output_block[..block_size];
+    output_block.copy_from_slice(iv);
+
+    for chunk in buffer.chunks_mut(block_size) {
+        block_cipher.encrypt_block(output_block);
+        xor_in_place(chunk, output_block);
+    }
+}
+
+/// Same as `mode::cfb_encrypt`, with a segment size of 1 bit or a whole number of bytes.
+pub fn cfb_encrypt_in_place<C: BlockCipher>(
+    block_cipher: &C,
+    iv: &[u8],
+    segment_size: usize,
+    buffer: &mut [u8],
+) {
+    cfb_in_place(block_cipher, iv, segment_size, buffer, true)
+}
+
+pub fn cfb_decrypt_in_place<C: BlockCipher>(
+    block_cipher: &C,
+    iv: &[u8],
+    segment_size: usize,
+    buffer: &mut [u8],
+) {
+    cfb_in_place(block_cipher, iv, segment_size, buffer, false)
+}
+
+fn cfb_in_place<C: BlockCipher>(
+    block_cipher: &C,
+    iv: &[u8],
+    segment_size: usize,
+    buffer: &mut [u8],
+    encrypting: bool,
+) {
+    let block_size = block_cipher.block_size();
+    assert_eq!(iv.len(), block_size);
+    assert!(segment_size == 1 || (segment_size % 8 == 0 && segment_size <= 8 * block_size),
+            "Segment size must be 1 bit or a whole number of bytes up to the block size.");
+
+    let (mut input_block, mut output_block) =
+        (scratch_block(block_cipher), scratch_block(block_cipher));
+    let (input_block, output_block) =
+        (&mut input_block[..block_size], &mut output_block[..block_size]);
+    input_block.copy_from_slice(iv);
+
+    if segment_size == 1 {
+        for byte in buffer.iter_mut() {
+            let mut result_byte = 0u8;
+            for bit_index in (0..8).rev() {
+                output_block.copy_from_slice(input_block);
+                block_cipher.encrypt_block(output_block);
+
+                let bit = (*byte >> bit_index) & 1;
+                let result_bit = bit ^ (output_block[0] >> 7);
+                shift_in_bit(input_block, if encrypting { result_bit } else { bit });
+                result_byte |= result_bit << bit_index;
+            }
+            *byte = result_byte;
+        }
+    } else {
+        for segment in buffer.chunks_mut(segment_size / 8) {
+            output_block.copy_from_slice(input_block);
+            block_cipher.encrypt_block(output_block);
+
+            // the cipher segment is shifted into the input block, before or after the xor
+            let kept_length = block_size - segment.len();
+            input_block.copy_within(segment.len().., 0);
+            if !encrypting {
+                input_block[kept_length..].copy_from_slice(segment);
+            }
+            xor_in_place(segment, output_block);
+            if encrypting {
+                input_block[kept_length..].copy_from_slice(segment);
+            }
+        }
+    }
+}
+
+/// Same as `mode::ctr`, encrypting as well as decrypting.
+pub fn ctr_in_place<C: BlockCipher>(
+    block_cipher: &C,
+    counter_block: &CounterBlock,
+    buffer: &mut [u8],
+) {
+    let block_size = block_cipher.block_size();
+    let mut keystream_block = scratch_block(block_cipher);
+    let keystream_block = &mut keystream_block[..block_size];
+
+    for (i, chunk) in buffer.chunks_mut(block_size).enumerate() {
+        counter_block.write_block(i as u64, keystream_block);
+        block_cipher.encrypt_block(keystream_block);
+        xor_in_place(chunk, keystream_block);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::{Aes, Key};
+    use aes::generate::generate_bytes_for_length;
+    use mode;
+    use mode::toy::ToyCipher;
+
+    const VARIANTS: [CiphertextStealing; 3] =
+        [CiphertextStealing::CS1, CiphertextStealing::CS2, CiphertextStealing::CS3];
+
+    #[test]
+    fn block_modes_match_the_allocating_ones() {
+        let block_cipher = Aes::new(&Key::new(&generate_bytes_for_length(16)));
+        let iv = &generate_bytes_for_length(16);
+
+        for blocks_count in 0..4 {
+            let bytes = &generate_bytes_for_length(16 * blocks_count);
+            let buffer = &mut bytes.clone();
+
+            ecb_encrypt_in_place(&block_cipher, buffer);
+            assert_eq!(buffer, &mode::ecb_encrypt(&block_cipher, bytes));
+            ecb_decrypt_in_place(&block_cipher, buffer);
+            assert_eq!(buffer, bytes);
+
+            cbc_encrypt_in_place(&block_cipher, iv, buffer);
+            assert_eq!(buffer, &mode::cbc_encrypt(&block_cipher, iv, bytes));
+            cbc_decrypt_in_place(&block_cipher, iv, buffer);
+            assert_eq!(buffer, bytes);
+        }
+    }
+
+    #[test]
+    fn ciphertext_stealing_matches_the_allocating_one() {
+        let block_cipher = Aes::new(&Key::new(&generate_bytes_for_length(16)));
+        let iv = &generate_bytes_for_length(16);
+
+        for length in 16..=50 {
+            let bytes = &generate_bytes_for_length(length);
+
+            for variant in VARIANTS.iter() {
+                let buffer = &mut bytes.clone();
+
+                cbc_cs_encrypt_in_place(&block_cipher, iv, *variant, buffer);
+                assert_eq!(buffer, &mode::cbc_cs_encrypt(&block_cipher, iv, *variant, bytes));
+                cbc_cs_decrypt_in_place(&block_cipher, iv, *variant, buffer);
+                assert_eq!(buffer, bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn stream_modes_match_the_allocating_ones() {
+        let block_cipher = Aes::new(&Key::new(&generate_bytes_for_length(16)));
+        let iv = &generate_bytes_for_length(16);
+        let counter_block = CounterBlock::big_endian(&generate_bytes_for_length(12));
+
+        for length in [0, 1, 15, 16, 17, 50].iter() {
+            let bytes = &generate_bytes_for_length(*length);
+            let buffer = &mut bytes.clone();
+
+            ofb_in_place(&block_cipher, iv, buffer);
+            assert_eq!(buffer, &mode::ofb(&block_cipher, iv, bytes));
+            ofb_in_place(&block_cipher, iv, buffer);
+            assert_eq!(buffer, bytes);
+
+            ctr_in_place(&block_cipher, &counter_block, buffer);
+            assert_eq!(buffer, &mode::ctr(&block_cipher, &counter_block, bytes));
+            ctr_in_place(&block_cipher, &counter_block, buffer);
+            assert_eq!(buffer, bytes);
+
+            for segment_size in [1, 8, 32, 128].iter() {
+                cfb_encrypt_in_place(&block_cipher, iv, *segment_size, buffer);
+                assert_eq!(buffer, &mode::cfb_encrypt(&block_cipher, iv, *segment_size, bytes));
+                cfb_decrypt_in_place(&block_cipher, iv, *segment_size, buffer);
+                assert_eq!(buffer, bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn in_place_modes_with_8_byte_blocks() {
+        let block_cipher = ToyCipher::new(0x0123_4567);
+        let iv = &[0x42; 8];
+        let bytes = &generate_bytes_for_length(8 * 5 + 3);
+
+        for variant in VARIANTS.iter() {
+            let buffer = &mut bytes.clone();
+
+            cbc_cs_encrypt_in_place(&block_cipher, iv, *variant, buffer);
+            assert_eq!(buffer, &mode::cbc_cs_encrypt(&block_cipher, iv, *variant, bytes));
+            cbc_cs_decrypt_in_place(&block_cipher, iv, *variant, buffer);
+            assert_eq!(buffer, bytes);
+        }
+
+        let buffer = &mut bytes.clone();
+        cfb_encrypt_in_place(&block_cipher, iv, 16, buffer);
+        assert_eq!(buffer, &mode::cfb_encrypt(&block_cipher, iv, 16, bytes));
+    }
+}
@@ -0,0 +1,362 @@
+/// Resources used:
+/// https://doc.rust-lang.org/std/io/index.html
+/// https://doc.rust-lang.org/std/io/struct.BufWriter.html
+
+use std::io;
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use mode::BlockCipher;
+use mode::stream::{Decryptor, Encryptor};
+
+/// Size of the reads from the inner reader.
+const READ_SIZE: usize = 4096;
+
+/// Writer encrypting the bytes written to it before passing them on to the inner writer. A
+/// partial block is held back until more bytes complete it, or until the writer is finished,
+/// which pads and writes the last block. Dropping the writer finishes it, ignoring errors, so
+/// prefer calling `finish`. The cipher the inner writer fails to take is held back too, and
+/// written first on the next write or flush, so that an error doesn't break the chain of blocks.
+pub struct EncryptingWriter<'a, C: BlockCipher + 'a, W: Write> {
+    encryptor: Option<Encryptor<'a, C>>,
+    inner: Option<W>,
+    /// Cipher not yet taken by the inner writer.
+    pending: Vec<u8>,
+}
+
+impl<'a, C: BlockCipher + 'a, W: Write> EncryptingWriter<'a, C, W> {
+    pub fn new(encryptor: Encryptor<'a, C>, inner: W) -> Self {
+        EncryptingWriter { encryptor: Some(encryptor), inner: Some(inner), pending: vec![] }
+    }
+
+    /// Encrypts and writes the last block, padded, then hands back the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_last_block()?;
+
+        Ok(self.inner.take().unwrap())
+    }
+
+    fn write_last_block(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        if let Some(encryptor) = self.encryptor.take() {
+            self.pending = encryptor.finalize();
+            self.write_pending()?;
+            self.inner.as_mut().unwrap().flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the pending cipher, keeping whatever the inner writer doesn't take.
+    fn write_pending(&mut self) -> io::Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+
+        while !self.pending.is_empty() {
+            match inner.write(&self.pending) {
+                Ok(0) => return Err(io::Error::new(ErrorKind::WriteZero,
+                                                   "The inner writer takes no more cipher.")),
+                Ok(length) => {
+                    self.pending.drain(..length);
+                }
+                Err(ref error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, C: BlockCipher + 'a, W: Write> Write for EncryptingWriter<'a, C, W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.write_pending()?;
+
+        self.pending = self.encryptor.as_mut().unwrap().update(bytes);
+        // the bytes are taken once encrypted: failing to pass their cipher on shows on the next
+        // write or flush
+        let _ = self.write_pending();
+
+        Ok(bytes.len())
+    }
+
+    /// Writes the pending cipher and flushes the inner writer. A partial block can't be encrypted
+    /// yet, and stays held back.
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<'a, C: BlockCipher + 'a, W: Write> Drop for EncryptingWriter<'a, C, W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.write_last_block();
+        }
+    }
+}
+
+/// Reader decrypting the cipher read from the inner reader. The padding is checked and removed
+/// once the inner reader reaches its end: an invalid padding or a partial last block is then an
+/// `InvalidData` error.
+pub struct DecryptingReader<'a, C: BlockCipher + 'a, R: Read> {
+    /// None once the last block has been decrypted.
+    decryptor: Option<Decryptor<'a, C>>,
+    inner: R,
+    /// Decrypted bytes, of which the first consumed ones have already been read.
+    deciphered: Vec<u8>,
+    consumed: usize,
+}
+
+impl<'a, C: BlockCipher + 'a, R: Read> DecryptingReader<'a, C, R> {
+    pub fn new(decryptor: Decryptor<'a, C>, inner: R) -> Self {
+        DecryptingReader { decryptor: Some(decryptor), inner, deciphered: vec![], consumed: 0 }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Decrypts the next bytes of the inner reader. Returns false at the end of the cipher.
+    fn fill(&mut self) -> io::Result<bool> {
+        let mut cipher = [0u8; READ_SIZE];
+        let length = match self.decryptor {
+            Some(_) => self.inner.read(&mut cipher)?,
+            None => return Ok(false),
+        };
+
+        self.consumed = 0;
+        self.deciphered = if length > 0 {
+            self.decryptor.as_mut().unwrap().update(&cipher[..length])
+        } else if self.decryptor.as_ref().unwrap().is_seekable() {
+            // the keystream has no end, a seek may still move back into the cipher
+            return Ok(false);
+        } else {
+            self.decryptor.take().unwrap().finalize()
+                .map_err(|error| io::Error::new(ErrorKind::InvalidData, format!("{:?}", error)))?
+        };
+
+        Ok(true)
+    }
+}
+
+impl<'a, C: BlockCipher + 'a, R: Read> Read for DecryptingReader<'a, C, R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        // a read of the inner reader may decrypt nothing, when it only completes part of a block
+        while self.consumed == self.deciphered.len() {
+            if !self.fill()? {
+                return Ok(0);
+            }
+        }
+
+        let unread = &self.deciphered[self.consumed..];
+        let length = unread.len().min(buffer.len());
+        buffer[..length].copy_from_slice(&unread[..length]);
+        self.consumed += length;
+
+        Ok(length)
+    }
+}
+
+/// Random access into a CTR cipher, whose keystream can be generated from any offset. The inner
+/// reader must start with the cipher, so that positions in it are offsets of the keystream. Block
+/// modes can't seek, and return an `Unsupported` error.
+impl<'a, C: BlockCipher + 'a, R: Read + Seek> Seek for DecryptingReader<'a, C, R> {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        match self.decryptor {
+            Some(ref decryptor) if decryptor.is_seekable() => {}
+            _ => return Err(io::Error::new(ErrorKind::Unsupported, "Only CTR mode can seek.")),
+        }
+
+        let offset = match position {
+            // the inner reader is ahead of the reader by the bytes decrypted but not yet read
+            SeekFrom::Current(offset) => {
+                let unread = (self.deciphered.len() - self.consumed) as i64;
+                self.inner.seek(SeekFrom::Current(offset - unread))?
+            }
+            _ => self.inner.seek(position)?,
+        };
+
+        self.decryptor.as_mut().unwrap().seek(offset);
+        self.deciphered.clear();
+        self.consumed = 0;
+
+        Ok(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::ops::Range;
+    use aes::{Aes, DecryptError, Key, Padding, PaddingError, pkcs7_pad};
+    use aes::generate::generate_bytes_for_length;
+    use mode;
+    use mode::counter::CounterBlock;
+
+    /// Reader handing out at most a few bytes at a time, as sockets do.
+    struct TrickleReader<'a> {
+        bytes: &'a [u8],
+    }
+
+    impl<'a> Read for TrickleReader<'a> {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            let length = self.bytes.len().min(buffer.len()).min(7);
+            buffer[..length].copy_from_slice(&self.bytes[..length]);
+            self.bytes = &self.bytes[length..];
+
+            Ok(length)
+        }
+    }
+
+    /// Writer taking at most 10 bytes at a time, which fails on some of the calls to `write`.
+    struct FailingWriter<'a> {
+        bytes: &'a mut Vec<u8>,
+        calls: usize,
+        failing_calls: Range<usize>,
+    }
+
+    impl<'a> Write for FailingWriter<'a> {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.calls += 1;
+            if self.failing_calls.contains(&(self.calls - 1)) {
+                return Err(io::Error::new(ErrorKind::Other, "The disk is full."));
+            }
+
+            let length = bytes.len().min(10);
+            self.bytes.extend_from_slice(&bytes[..length]);
+
+            Ok(length)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn encrypting_writer_pads_on_finish() {
+        let block_cipher = &Aes::new(&Key::new_from_string("YELLOW SUBMARINE"));
+        let iv = &generate_bytes_for_length(16);
+        let raw = &generate_bytes_for_length(1000);
+        let encryptor = Encryptor::cbc(block_cipher, iv, &Padding::PKCS7);
+        let mut writer = EncryptingWriter::new(encryptor, vec![]);
+
+        for part in raw.chunks(13) {
+            writer.write_all(part).unwrap();
+        }
+        let cipher = writer.finish().unwrap();
+
+        assert_eq!(cipher, mode::cbc_encrypt(block_cipher, iv, &pkcs7_pad(raw, 16)));
+    }
+
+    #[test]
+    fn encrypting_writer_pads_on_drop() {
+        let block_cipher = &Aes::new(&Key::new_from_string("YELLOW SUBMARINE"));
+        let raw = b"dropped before a whole block";
+        let mut cipher = vec![];
+
+        {
+            let encryptor = Encryptor::ecb(block_cipher, &Padding::PKCS7);
+            let mut writer = EncryptingWriter::new(encryptor, &mut cipher);
+            writer.write_all(raw).unwrap();
+            writer.flush().unwrap();
+        }
+
+        assert_eq!(cipher, mode::ecb_encrypt(block_cipher, &pkcs7_pad(raw, 16)));
+    }
+
+    #[test]
+    fn encrypting_writer_keeps_the_cipher_the_inner_writer_fails_to_take() {
+        let block_cipher = &Aes::new(&Key::new_from_string("YELLOW SUBMARINE"));
+        let iv = &generate_bytes_for_length(16);
+        let raw = &generate_bytes_for_length(200);
+        let mut cipher = vec![];
+        let mut errors = 0;
+
+        {
+            let inner = FailingWriter { bytes: &mut cipher, calls: 0, failing_calls: 2..4 };
+            let encryptor = Encryptor::cbc(block_cipher, iv, &Padding::PKCS7);
+            let mut writer = EncryptingWriter::new(encryptor, inner);
+
+            for part in raw.chunks(13) {
+                loop {
+                    match writer.write(part) {
+                        Ok(length) => break assert_eq!(length, part.len()),
+                        Err(_) => errors += 1,
+                    }
+                }
+            }
+            writer.finish().unwrap();
+        }
+
+        assert_eq!(errors, 1);
+        assert_eq!(cipher, mode::cbc_encrypt(block_cipher, iv, &pkcs7_pad(raw, 16)));
+    }
+
+    #[test]
+    fn decrypting_reader_round_trips_through_partial_reads() {
+        let block_cipher = &Aes::new(&Key::new_from_string("YELLOW SUBMARINE"));
+        let iv = &generate_bytes_for_length(16);
+        let raw = &generate_bytes_for_length(5000);
+        let cipher = &mode::cbc_encrypt(block_cipher, iv, &pkcs7_pad(raw, 16));
+
+        let decryptor = Decryptor::cbc(block_cipher, iv, &Padding::PKCS7);
+        let mut reader = DecryptingReader::new(decryptor, TrickleReader { bytes: cipher });
+        let mut deciphered = vec![];
+        reader.read_to_end(&mut deciphered).unwrap();
+
+        assert_eq!(&deciphered, raw);
+        assert_eq!(reader.read(&mut [0; 16]).unwrap(), 0);
+    }
+
+    #[test]
+    fn decrypting_reader_rejects_invalid_padding() {
+        let block_cipher = &Aes::new(&Key::new_from_string("YELLOW SUBMARINE"));
+        let cipher = mode::ecb_encrypt(block_cipher, &[&[0x05u8; 31][..], &[0x04][..]].concat());
+
+        let decryptor = Decryptor::ecb(block_cipher, &Padding::PKCS7);
+        let mut reader = DecryptingReader::new(decryptor, &cipher[..]);
+        let error = reader.read_to_end(&mut vec![]).unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(error.to_string(),
+                   format!("{:?}", DecryptError::Padding(PaddingError::PaddingNotConsistent)));
+    }
+
+    #[test]
+    fn ctr_reader_seeks_anywhere_in_the_cipher() {
+        let block_cipher = &Aes::new(&Key::new_from_string("YELLOW SUBMARINE"));
+        let counter_block = &CounterBlock::little_endian(&[0x17; 8]);
+        let raw = &generate_bytes_for_length(10_000);
+        let cipher = mode::ctr(block_cipher, counter_block, raw);
+
+        let decryptor = Decryptor::ctr(block_cipher, counter_block);
+        let mut reader = DecryptingReader::new(decryptor, Cursor::new(cipher));
+        let mut part = [0u8; 50];
+
+        assert_eq!(reader.seek(SeekFrom::Start(5003)).unwrap(), 5003);
+        reader.read_exact(&mut part).unwrap();
+        assert_eq!(&part[..], &raw[5003..5053]);
+
+        // the reader decrypted more than was read
+        assert_eq!(reader.seek(SeekFrom::Current(-20)).unwrap(), 5033);
+        reader.read_exact(&mut part).unwrap();
+        assert_eq!(&part[..], &raw[5033..5083]);
+
+        // back from the end of the cipher
+        let mut end = vec![];
+        reader.read_to_end(&mut end).unwrap();
+        assert_eq!(reader.seek(SeekFrom::End(-50)).unwrap(), 9950);
+        reader.read_exact(&mut part).unwrap();
+        assert_eq!(&part[..], &raw[9950..]);
+    }
+
+    #[test]
+    fn block_mode_readers_can_not_seek() {
+        let block_cipher = &Aes::new(&Key::new_from_string("YELLOW SUBMARINE"));
+        let decryptor = Decryptor::ecb(block_cipher, &Padding::None);
+        let mut reader = DecryptingReader::new(decryptor, Cursor::new(vec![0u8; 32]));
+
+        assert_eq!(reader.seek(SeekFrom::Start(16)).unwrap_err().kind(), ErrorKind::Unsupported);
+    }
+}
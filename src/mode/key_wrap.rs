@@ -0,0 +1,411 @@
+/// Resources used:
+/// https://tools.ietf.org/html/rfc3394
+/// https://tools.ietf.org/html/rfc5649
+/// https://nvlpubs.nist.gov/nistpubs/SpecialPublications/NIST.SP.800-38F.pdf
+
+use mode;
+use mode::BlockCipher;
+
+/// Initial value of RFC 3394, Sec. 2.2.3.1, checked on unwrapping.
+const DEFAULT_IV: [u8; 8] = [0xa6; 8];
+
+/// First half of the alternative initial value of RFC 5649, followed by the key data length.
+const ALTERNATIVE_IV_PREFIX: [u8; 4] = [0xa6, 0x59, 0x59, 0xa6];
+
+/// Reasons why wrapped key data is refused.
+#[derive(Debug, PartialEq)]
+pub enum UnwrapError {
+    /// Wrapped key data is a whole number of 64-bit blocks, at least 3 of them (2 with padding).
+    InvalidLength,
+    /// The unwrapped initial value is not the expected one: the data was altered or wrapped under
+    /// another key.
+    IntegrityCheckFailed,
+    /// The length and the padding bytes of padded key data are inconsistent.
+    InvalidPadding,
+}
+
+/// Wraps key data of at least two 64-bit blocks (RFC 3394). Each of the 6 passes over the blocks
+/// encrypts the integrity register with the next block, so that every bit of the output depends on
+/// every bit of the input.
+pub fn wrap_key<C: BlockCipher>(block_cipher: &C, key_data: &[u8]) -> Vec<u8> {
+    assert!(key_data.len() >= 16 && key_data.len() % 8 == 0,
+            "Key data must be at least two 64-bit blocks.");
+
+    wrap(block_cipher, &DEFAULT_IV, key_data)
+}
+
+pub fn unwrap_key<C: BlockCipher>(block_cipher: &C, wrapped: &[u8])
+                                  -> Result<Vec<u8>, UnwrapError> {
+    if wrapped.len() < 24 || wrapped.len() % 8 != 0 {
+        return Err(UnwrapError::InvalidLength);
+    }
+
+    let (iv, key_data) = unwrap(block_cipher, wrapped);
+    if !mode::constant_time_eq(&iv, &DEFAULT_IV) {
+        return Err(UnwrapError::IntegrityCheckFailed);
+    }
+
+    Ok(key_data)
+}
+
+/// Wraps key data of any non-zero length (RFC 5649): the data is padded with zeros to 64-bit
+/// blocks, its length being kept in the initial value. A single padded block is simply encrypted
+/// along with the initial value.
+pub fn wrap_key_with_padding<C: BlockCipher>(block_cipher: &C, key_data: &[u8]) -> Vec<u8> {
+    assert!(!key_data.is_empty() && key_data.len() <= u32::max_value() as usize,
+            "Key data must be between 1 byte and 2^32 - 1 bytes long.");
+
+    let iv = &alternative_iv(key_data.len());
+    let mut padded_key_data = key_data.to_vec();
+    padded_key_data.resize((key_data.len() + 7) / 8 * 8, 0);
+
+    if padded_key_data.len() == 8 {
+        let mut block = [&iv[..], &padded_key_data[..]].concat();
+        block_cipher.encrypt_block(&mut block);
+
+        block
+    } else {
+        wrap(block_cipher, iv, &padded_key_data)
+    }
+}
+
+pub fn unwrap_key_with_padding<C: BlockCipher>(block_cipher: &C, wrapped: &[u8])
+                                               -> Result<Vec<u8>, UnwrapError> {
+    if wrapped.len() < 16 || wrapped.len() % 8 != 0 {
+        return Err(UnwrapError::InvalidLength);
+    }
+
+    let (iv, padded_key_data) = if wrapped.len() == 16 {
+        let mut block = wrapped.to_vec();
+        block_cipher.decrypt_block(&mut block);
+        let key_data = block.split_off(8);
+
+        (block, key_data)
+    } else {
+        unwrap(block_cipher, wrapped)
+    };
+
+    let key_data_length = check_alternative_iv(&iv, &padded_key_data)?;
+
+    Ok(padded_key_data[..key_data_length].to_vec())
+}
+
+/// Same as `wrap_key`, over the key data at the start of the buffer, which must have 8 more bytes
+/// of room for the integrity block. Returns the length of the wrapped key data, found at the start
+/// of the buffer.
+pub fn wrap_key_in_place<C: BlockCipher>(block_cipher: &C, buffer: &mut [u8], length: usize)
+                                         -> usize {
+    assert!(length >= 16 && length % 8 == 0, "Key data must be at least two 64-bit blocks.");
+    assert!(buffer.len() >= length + 8, "The buffer has no room for the integrity block.");
+
+    let wrapped = &mut buffer[..length + 8];
+    wrapped.copy_within(..length, 8);
+    wrapped[..8].copy_from_slice(&DEFAULT_IV);
+    wrap_in_place(block_cipher, wrapped);
+
+    length + 8
+}
+
+/// Same as `unwrap_key`, the key data taking the start of the buffer. Returns its length. The
+/// buffer is left as it was when the wrapped key data is refused.
+pub fn unwrap_key_in_place<C: BlockCipher>(block_cipher: &C, buffer: &mut [u8])
+                                           -> Result<usize, UnwrapError> {
+    if buffer.len() < 24 || buffer.len() % 8 != 0 {
+        return Err(UnwrapError::InvalidLength);
+    }
+
+    unwrap_in_place(block_cipher, buffer);
+    if !mode::constant_time_eq(&buffer[..8], &DEFAULT_IV) {
+        wrap_in_place(block_cipher, buffer);
+        return Err(UnwrapError::IntegrityCheckFailed);
+    }
+
+    buffer.copy_within(8.., 0);
+
+    Ok(buffer.len() - 8)
+}
+
+/// Same as `wrap_key_with_padding`, over the key data at the start of the buffer, which must have
+/// room for the integrity block and the padding: 8 bytes, plus up to 7. Returns the length of the
+/// wrapped key data, found at the start of the buffer.
+pub fn wrap_key_with_padding_in_place<C: BlockCipher>(
+    block_cipher: &C,
+    buffer: &mut [u8],
+    length: usize,
+) -> usize {
+    assert!(length > 0 && length <= u32::max_value() as usize,
+            "Key data must be between 1 byte and 2^32 - 1 bytes long.");
+    let wrapped_length = (length + 7) / 8 * 8 + 8;
+    assert!(buffer.len() >= wrapped_length,
+            "The buffer has no room for the integrity block and the padding.");
+
+    let wrapped = &mut buffer[..wrapped_length];
+    wrapped.copy_within(..length, 8);
+    for byte in wrapped[length + 8..].iter_mut() {
+        *byte = 0;
+    }
+    wrapped[..8].copy_from_slice(&alternative_iv(length));
+
+    if wrapped_length == 16 {
+        block_cipher.encrypt_block(wrapped);
+    } else {
+        wrap_in_place(block_cipher, wrapped);
+    }
+
+    wrapped_length
+}
+
+/// Same as `unwrap_key_with_padding`, the key data taking the start of the buffer. Returns its
+/// length. The buffer is left as it was when the wrapped key data is refused.
+pub fn unwrap_key_with_padding_in_place<C: BlockCipher>(block_cipher: &C, buffer: &mut [u8])
+                                                        -> Result<usize, UnwrapError> {
+    if buffer.len() < 16 || buffer.len() % 8 != 0 {
+        return Err(UnwrapError::InvalidLength);
+    }
+
+    if buffer.len() == 16 {
+        block_cipher.decrypt_block(buffer);
+    } else {
+        unwrap_in_place(block_cipher, buffer);
+    }
+
+    let key_data_length = check_alternative_iv(&buffer[..8], &buffer[8..]);
+    if let Err(error) = key_data_length {
+        if buffer.len() == 16 {
+            block_cipher.encrypt_block(buffer);
+        } else {
+            wrap_in_place(block_cipher, buffer);
+        }
+        return Err(error);
+    }
+
+    buffer.copy_within(8.., 0);
+
+    key_data_length
+}
+
+fn alternative_iv(key_data_length: usize) -> Vec<u8> {
+    [&ALTERNATIVE_IV_PREFIX[..], &(key_data_length as u32).to_be_bytes()[..]].concat()
+}
+
+/// Length of the key data the alternative initial value holds, once checked against the padded
+/// key data.
+fn check_alternative_iv(iv: &[u8], padded_key_data: &[u8]) -> Result<usize, UnwrapError> {
+    if !mode::constant_time_eq(&iv[..4], &ALTERNATIVE_IV_PREFIX) {
+        return Err(UnwrapError::IntegrityCheckFailed);
+    }
+
+    let key_data_length = u32::from_be_bytes([iv[4], iv[5], iv[6], iv[7]]) as usize;
+    let padding_length = padded_key_data.len().wrapping_sub(key_data_length);
+    if padding_length >= 8 || padded_key_data[key_data_length..].iter().any(|byte| *byte != 0) {
+        return Err(UnwrapError::InvalidPadding);
+    }
+
+    Ok(key_data_length)
+}
+
+/// Wrapping process W of SP 800-38F, over 64-bit semiblocks.
+fn wrap<C: BlockCipher>(block_cipher: &C, iv: &[u8], key_data: &[u8]) -> Vec<u8> {
+    let mut wrapped = [iv, key_data].concat();
+    wrap_in_place(block_cipher, &mut wrapped);
+
+    wrapped
+}
+
+/// Unwrapping process W^-1 of SP 800-38F, returning the initial value and the key data.
+fn unwrap<C: BlockCipher>(block_cipher: &C, wrapped: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut iv = wrapped.to_vec();
+    unwrap_in_place(block_cipher, &mut iv);
+    let key_data = iv.split_off(8);
+
+    (iv, key_data)
+}
+
+/// Wrapping process W over the buffer, the initial value followed by the key data. The register A
+/// is kept in the first semiblock of the buffer, each block going through the stack.
+fn wrap_in_place<C: BlockCipher>(block_cipher: &C, buffer: &mut [u8]) {
+    assert_eq!(block_cipher.block_size(), 16, "Key wrap requires a 128-bit block cipher.");
+
+    let n = buffer.len() / 8 - 1;
+    let mut b = [0u8; 16];
+    for j in 0..6 {
+        for i in 0..n {
+            let (a, r) = buffer.split_at_mut(8);
+            let r_i = &mut r[8 * i..8 * i + 8];
+            b[..8].copy_from_slice(a);
+            b[8..].copy_from_slice(r_i);
+            block_cipher.encrypt_block(&mut b);
+
+            a.copy_from_slice(&b[..8]);
+            xor_with_counter(a, (n * j + i + 1) as u64);
+            r_i.copy_from_slice(&b[8..]);
+        }
+    }
+}
+
+/// Unwrapping process W^-1 over the buffer, which then holds the initial value followed by the
+/// key data.
+fn unwrap_in_place<C: BlockCipher>(block_cipher: &C, buffer: &mut [u8]) {
+    assert_eq!(block_cipher.block_size(), 16, "Key wrap requires a 128-bit block cipher.");
+
+    let n = buffer.len() / 8 - 1;
+    let mut b = [0u8; 16];
+    for j in (0..6).rev() {
+        for i in (0..n).rev() {
+            let (a, r) = buffer.split_at_mut(8);
+            let r_i = &mut r[8 * i..8 * i + 8];
+            xor_with_counter(a, (n * j + i + 1) as u64);
+            b[..8].copy_from_slice(a);
+            b[8..].copy_from_slice(r_i);
+            block_cipher.decrypt_block(&mut b);
+
+            a.copy_from_slice(&b[..8]);
+            r_i.copy_from_slice(&b[8..]);
+        }
+    }
+}
+
+fn xor_with_counter(semiblock: &mut [u8], t: u64) {
+    for (byte, t_byte) in semiblock.iter_mut().zip(t.to_be_bytes().iter()) {
+        *byte ^= t_byte;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::{Aes, Key};
+    use hex::hex_string_to_bytes;
+
+    #[test]
+    fn wrap_key_rfc_3394_test_vectors() {
+        let kek = &hex_string_to_bytes(
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"
+        );
+        let key_data = &hex_string_to_bytes(
+            "00112233445566778899aabbccddeeff000102030405060708090a0b0c0d0e0f"
+        );
+        // (KEK length, key data length, wrapped key data) of RFC 3394, Sec. 4
+        let test_cases = vec![
+            (16, 16, "1fa68b0a8112b447aef34bd8fb5a7b829d3e862371d2cfe5"),
+            (24, 16, "96778b25ae6ca435f92b5b97c050aed2468ab8a17ad84e5d"),
+            (32, 16, "64e8c3f9ce0f5ba263e9777905818a2a93c8191e7d6e8ae7"),
+            (24, 24, "031d33264e15d33268f24ec260743edce1c6c7ddee725a936ba814915c6762d2"),
+            (32, 24, "a8f9bc1612c68b3ff6e6f4fbe30e71e4769c8b80a32cb8958cd5d17d6b254da1"),
+            (32, 32, "28c9f404c4b810f4cbccb35cfb87f8263f5786e2d80ed326cbc7f0e71a99f43b\
+                      fb988b9b7a02dd21"),
+        ];
+
+        for (kek_length, key_data_length, expected_wrapped) in test_cases.iter() {
+            let block_cipher = Aes::new(&Key::new(&kek[..*kek_length]));
+            let key_data = &key_data[..*key_data_length];
+
+            let wrapped = wrap_key(&block_cipher, key_data);
+            let unwrapped = unwrap_key(&block_cipher, &wrapped);
+
+            assert_eq!(wrapped, hex_string_to_bytes(expected_wrapped));
+            assert_eq!(unwrapped, Ok(key_data.to_vec()));
+        }
+    }
+
+    #[test]
+    fn wrap_key_with_padding_rfc_5649_test_vectors() {
+        let block_cipher = Aes::new(&Key::new(&hex_string_to_bytes(
+            "5840df6e29b02af1ab493b705bf16ea1ae8338f4dcc176a8"
+        )));
+        let test_cases = vec![
+            ("c37b7e6492584340bed12207808941155068f738",
+             "138bdeaa9b8fa7fc61f97742e72248ee5ae6ae5360d1ae6a5f54f373fa543b6a"),
+            ("466f7250617369", "afbeb0f07dfbf5419200f2ccb50bb24f"),
+        ];
+
+        for (key_data, expected_wrapped) in test_cases.iter() {
+            let key_data = &hex_string_to_bytes(key_data);
+
+            let wrapped = wrap_key_with_padding(&block_cipher, key_data);
+            let unwrapped = unwrap_key_with_padding(&block_cipher, &wrapped);
+
+            assert_eq!(wrapped, hex_string_to_bytes(expected_wrapped));
+            assert_eq!(unwrapped, Ok(key_data.to_vec()));
+        }
+    }
+
+    #[test]
+    fn in_place_key_wrap_matches_key_wrap() {
+        let block_cipher = Aes::new(&Key::new(&[0x42; 16]));
+
+        for length in [1, 7, 8, 9, 16, 24, 31, 40].iter() {
+            let key_data = &(0..*length as u8).collect::<Vec<u8>>();
+            let buffer = &mut [&key_data[..], &[0xff; 15][..]].concat();
+
+            let wrapped_length = wrap_key_with_padding_in_place(&block_cipher, buffer, *length);
+            let wrapped = &mut buffer[..wrapped_length];
+            assert_eq!(wrapped, &wrap_key_with_padding(&block_cipher, key_data)[..]);
+            assert_eq!(unwrap_key_with_padding_in_place(&block_cipher, wrapped), Ok(*length));
+            assert_eq!(&wrapped[..*length], &key_data[..]);
+
+            if length % 8 == 0 && *length >= 16 {
+                let buffer = &mut [&key_data[..], &[0xff; 8][..]].concat();
+
+                assert_eq!(wrap_key_in_place(&block_cipher, buffer, *length), length + 8);
+                assert_eq!(buffer, &wrap_key(&block_cipher, key_data));
+                assert_eq!(unwrap_key_in_place(&block_cipher, buffer), Ok(*length));
+                assert_eq!(&buffer[..*length], &key_data[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn unwrap_key_in_place_leaves_refused_key_data() {
+        let block_cipher = Aes::new(&Key::new(&[0x42; 16]));
+        let wrapped = wrap_key(&block_cipher, &[0x17; 32]);
+        let buffer = &mut wrapped.clone();
+        let padded_wrapped = wrap_key_with_padding(&block_cipher, &[0x17; 5]);
+        let padded_buffer = &mut padded_wrapped.clone();
+
+        assert_eq!(unwrap_key_with_padding_in_place(&block_cipher, buffer),
+                   Err(UnwrapError::IntegrityCheckFailed));
+        assert_eq!(buffer, &wrapped);
+        assert_eq!(unwrap_key_in_place(&Aes::new(&Key::new(&[0x43; 16])), buffer),
+                   Err(UnwrapError::IntegrityCheckFailed));
+        assert_eq!(buffer, &wrapped);
+        assert_eq!(unwrap_key_with_padding_in_place(&Aes::new(&Key::new(&[0x43; 16])),
+                                                    padded_buffer),
+                   Err(UnwrapError::IntegrityCheckFailed));
+        assert_eq!(padded_buffer, &padded_wrapped);
+    }
+
+    #[test]
+    fn unwrap_key_errors() {
+        let block_cipher = Aes::new(&Key::new(&[0x42; 16]));
+        let wrapped = wrap_key(&block_cipher, &[0x17; 32]);
+        let mut altered = wrapped.clone();
+        altered[30] ^= 0x01;
+
+        assert_eq!(unwrap_key(&block_cipher, &wrapped[..16]), Err(UnwrapError::InvalidLength));
+        assert_eq!(unwrap_key(&block_cipher, &wrapped[..39]), Err(UnwrapError::InvalidLength));
+        assert_eq!(unwrap_key(&block_cipher, &altered), Err(UnwrapError::IntegrityCheckFailed));
+        assert_eq!(unwrap_key(&Aes::new(&Key::new(&[0x43; 16])), &wrapped),
+                   Err(UnwrapError::IntegrityCheckFailed));
+        // padded and unpadded wrapping don't use the same initial value
+        assert_eq!(unwrap_key_with_padding(&block_cipher, &wrapped),
+                   Err(UnwrapError::IntegrityCheckFailed));
+    }
+
+    #[test]
+    fn unwrap_key_with_padding_rejects_inconsistent_padding() {
+        let block_cipher = Aes::new(&Key::new(&[0x42; 16]));
+        // claims 5 bytes of key data but has non-zero padding
+        let mut block = [&alternative_iv(5)[..], &[0x17; 8][..]].concat();
+        block_cipher.encrypt_block(&mut block);
+        // claims 12 bytes of key data while 24 are wrapped
+        let too_long = wrap(&block_cipher, &alternative_iv(12), &[0; 24]);
+
+        assert_eq!(unwrap_key_with_padding(&block_cipher, &block),
+                   Err(UnwrapError::InvalidPadding));
+        assert_eq!(unwrap_key_with_padding(&block_cipher, &too_long),
+                   Err(UnwrapError::InvalidPadding));
+        assert_eq!(unwrap_key_with_padding(&block_cipher, &block[..8]),
+                   Err(UnwrapError::InvalidLength));
+    }
+}
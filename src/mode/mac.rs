@@ -0,0 +1,145 @@
+/// Resources used:
+/// https://tools.ietf.org/html/rfc4493
+/// https://nvlpubs.nist.gov/nistpubs/SpecialPublications/NIST.SP.800-38B.pdf
+/// https://en.wikipedia.org/wiki/CBC-MAC
+
+use aes::math::double_in_gf_128;
+use mode;
+use mode::BlockCipher;
+use xor;
+
+/// CBC-MAC: the last block of the CBC encryption of the message under a zero iv. It is only secure
+/// for messages of a single, fixed number of blocks (see `attack::cbc` for forgeries otherwise).
+pub fn cbc_mac<C: BlockCipher>(block_cipher: &C, message: &[u8]) -> Vec<u8> {
+    cbc_mac_with_iv(block_cipher, &vec![0u8; block_cipher.block_size()], message)
+}
+
+/// CBC-MAC under a chosen iv, which a verifier must never take from the sender.
+pub fn cbc_mac_with_iv<C: BlockCipher>(block_cipher: &C, iv: &[u8], message: &[u8]) -> Vec<u8> {
+    let block_size = block_cipher.block_size();
+    assert!(!message.is_empty() && message.len() % block_size == 0,
+            "CBC-MAC requires a whole, non-zero number of blocks.");
+
+    let cipher = mode::cbc_encrypt(block_cipher, iv, message);
+
+    cipher[cipher.len() - block_size..].to_vec()
+}
+
+/// CMAC (also OMAC1): CBC-MAC whose last block is first xored with a subkey, K1 when the block is
+/// complete, K2 when it is padded with a one bit then zeros. Messages of any length are
+/// authenticated.
+pub fn cmac<C: BlockCipher>(block_cipher: &C, message: &[u8]) -> Vec<u8> {
+    let block_size = block_cipher.block_size();
+    assert_eq!(block_size, 16, "CMAC is only defined here for 128-bit block ciphers.");
+    let (k1, k2) = generate_subkeys(block_cipher);
+
+    let is_complete = !message.is_empty() && message.len() % block_size == 0;
+    let last_block_start = if is_complete {
+        message.len() - block_size
+    } else {
+        message.len() - message.len() % block_size
+    };
+    let (head, last_block) = message.split_at(last_block_start);
+
+    let last_block = if is_complete {
+        xor::fixed_xor(last_block, &k1)
+    } else {
+        let mut padded_block = last_block.to_vec();
+        padded_block.push(0x80);
+        padded_block.resize(block_size, 0);
+
+        xor::fixed_xor(&padded_block, &k2)
+    };
+
+    cbc_mac(block_cipher, &[head, &last_block[..]].concat())
+}
+
+/// Checks a CMAC tag, possibly truncated, in constant time.
+pub fn verify_cmac<C: BlockCipher>(block_cipher: &C, message: &[u8], tag: &[u8]) -> bool {
+    let expected_tag = cmac(block_cipher, message);
+
+    !tag.is_empty() && tag.len() <= expected_tag.len()
+        && mode::constant_time_eq(&expected_tag[..tag.len()], tag)
+}
+
+/// Subkeys K1 and K2, the encryption of the zero block doubled once and twice in GF(2^128).
+fn generate_subkeys<C: BlockCipher>(block_cipher: &C) -> (Vec<u8>, Vec<u8>) {
+    let mut l = vec![0u8; block_cipher.block_size()];
+    block_cipher.encrypt_block(&mut l);
+
+    let k1 = double_in_gf_128(&l);
+    let k2 = double_in_gf_128(&k1);
+
+    (k1, k2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::{Aes, Key};
+    use hex::hex_string_to_bytes;
+
+    fn rfc_4493_cipher_and_message() -> (Aes, Vec<u8>) {
+        let key = Key::new(&hex_string_to_bytes("2b7e151628aed2a6abf7158809cf4f3c"));
+        let message = hex_string_to_bytes(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+             30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710"
+        );
+
+        (Aes::new(&key), message)
+    }
+
+    #[test]
+    fn generate_subkeys_rfc_4493_test_case() {
+        let (block_cipher, _) = rfc_4493_cipher_and_message();
+
+        let (k1, k2) = generate_subkeys(&block_cipher);
+
+        assert_eq!(k1, hex_string_to_bytes("fbeed618357133667c85e08f7236a8de"));
+        assert_eq!(k2, hex_string_to_bytes("f7ddac306ae266ccf90bc11ee46d513b"));
+    }
+
+    #[test]
+    fn cmac_rfc_4493_test_cases() {
+        let (block_cipher, message) = rfc_4493_cipher_and_message();
+        let test_cases = vec![
+            (0, "bb1d6929e95937287fa37d129b756746"),
+            (16, "070a16b46b4d4144f79bdd9dd04a287c"),
+            (40, "dfa66747de9ae63030ca32611497c827"),
+            (64, "51f0bebf7e3b9d92fc49741779363cfe"),
+        ];
+
+        for (length, expected_tag) in test_cases.iter() {
+            let tag = cmac(&block_cipher, &message[..*length]);
+
+            assert_eq!(tag, hex_string_to_bytes(expected_tag));
+            assert!(verify_cmac(&block_cipher, &message[..*length], &tag));
+            assert!(verify_cmac(&block_cipher, &message[..*length], &tag[..8]));
+        }
+    }
+
+    #[test]
+    fn verify_cmac_rejects_other_messages_and_tags() {
+        let (block_cipher, message) = rfc_4493_cipher_and_message();
+        let tag = cmac(&block_cipher, &message);
+        let mut flipped_tag = tag.clone();
+        flipped_tag[0] ^= 0x01;
+
+        assert!(!verify_cmac(&block_cipher, &message[..63], &tag));
+        assert!(!verify_cmac(&block_cipher, &message, &flipped_tag));
+        assert!(!verify_cmac(&block_cipher, &message, &[]));
+    }
+
+    #[test]
+    fn cbc_mac_is_the_last_cbc_block() {
+        let (block_cipher, message) = rfc_4493_cipher_and_message();
+
+        let one_block_tag = cbc_mac(&block_cipher, &message[..16]);
+
+        // under a zero iv, the first block is simply encrypted (SP 800-38A, F.1.1)
+        assert_eq!(one_block_tag, hex_string_to_bytes("3ad77bb40d7a3660a89ecaf32466ef97"));
+        // last cipher block of SP 800-38A, F.2.1
+        assert_eq!(cbc_mac_with_iv(&block_cipher, &(0..16).collect::<Vec<u8>>(), &message),
+                   hex_string_to_bytes("3ff1caa1681fac09120eca307586e1a7"));
+    }
+}
@@ -0,0 +1,624 @@
+/// Resources used:
+/// https://en.wikipedia.org/wiki/Block_cipher_mode_of_operation
+/// https://nvlpubs.nist.gov/nistpubs/Legacy/SP/nistspecialpublication800-38a.pdf
+
+use xor;
+use self::counter::CounterBlock;
+
+pub mod ccm;
+pub mod counter;
+pub mod gcm;
+pub mod in_place;
+pub mod io;
+pub mod key_wrap;
+pub mod mac;
+pub mod parallel;
+pub mod siv;
+pub mod stream;
+pub mod xts;
+#[cfg(test)]
+pub mod toy;
+
+/// A block cipher, i.e. a keyed permutation over blocks of a fixed size, on which the modes of
+/// operation are built.
+pub trait BlockCipher {
+    /// Size of the blocks, in bytes, the cipher operates on.
+    fn block_size(&self) -> usize;
+
+    /// Encrypts a single block in place.
+    fn encrypt_block(&self, block: &mut [u8]);
+
+    /// Decrypts a single block in place.
+    fn decrypt_block(&self, block: &mut [u8]);
+
+    /// Encrypts consecutive blocks in place, independently of each other. Ciphers processing
+    /// several blocks at once override it.
+    fn encrypt_blocks(&self, blocks: &mut [u8]) {
+        for block in blocks.chunks_mut(self.block_size()) {
+            self.encrypt_block(block);
+        }
+    }
+
+    fn decrypt_blocks(&self, blocks: &mut [u8]) {
+        for block in blocks.chunks_mut(self.block_size()) {
+            self.decrypt_block(block);
+        }
+    }
+}
+
+/// Reasons why an authenticated mode refuses to decrypt a cipher.
+#[derive(PartialEq, Debug)]
+pub enum AuthenticationError {
+    InvalidTagLength,
+    /// The nonce has a length the mode can't take.
+    InvalidNonceLength,
+    /// The cipher is shorter than its tag, or longer than the mode allows.
+    InvalidLength,
+    TagMismatch,
+}
+
+/// Compares two byte strings in a time depending only on their lengths, so that a forger can't
+/// learn how many leading bytes of a tag were right.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b.iter()).fold(0u8, |difference, (x, y)| difference | (x ^ y)) == 0
+}
+
+/// Electronic Codebook: every block is encrypted independently of the others.
+pub fn ecb_encrypt<C: BlockCipher>(block_cipher: &C, bytes: &[u8]) -> Vec<u8> {
+    let mut blocks = bytes_to_blocks(bytes, block_cipher.block_size()).concat();
+    block_cipher.encrypt_blocks(&mut blocks);
+
+    blocks
+}
+
+pub fn ecb_decrypt<C: BlockCipher>(block_cipher: &C, cipher: &[u8]) -> Vec<u8> {
+    let mut blocks = bytes_to_blocks(cipher, block_cipher.block_size()).concat();
+    block_cipher.decrypt_blocks(&mut blocks);
+
+    blocks
+}
+
+/// Cipher Block Chaining: every block is xored with the previous cipher block (or the iv, for the
+/// first block) before being encrypted.
+pub fn cbc_encrypt<C: BlockCipher>(block_cipher: &C, iv: &[u8], bytes: &[u8]) -> Vec<u8> {
+    assert_eq!(iv.len(), block_cipher.block_size());
+
+    let mut blocks = bytes_to_blocks(bytes, block_cipher.block_size());
+    let mut previous_block = iv.to_vec();
+
+    for block in blocks.iter_mut() {
+        *block = xor::fixed_xor(block, &previous_block);
+        block_cipher.encrypt_block(block);
+        previous_block = block.to_vec();
+    }
+
+    blocks.concat()
+}
+
+pub fn cbc_decrypt<C: BlockCipher>(block_cipher: &C, iv: &[u8], cipher: &[u8]) -> Vec<u8> {
+    assert_eq!(iv.len(), block_cipher.block_size());
+
+    let mut blocks = bytes_to_blocks(cipher, block_cipher.block_size());
+    let mut previous_block = iv.to_vec();
+
+    for block in blocks.iter_mut() {
+        let cipher_block = block.to_vec();
+        block_cipher.decrypt_block(block);
+        *block = xor::fixed_xor(block, &previous_block);
+        previous_block = cipher_block;
+    }
+
+    blocks.concat()
+}
+
+/// Variants of CBC with ciphertext stealing (SP 800-38A Addendum), differing in the order of the
+/// last two cipher blocks.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CiphertextStealing {
+    /// The partial second to last block comes first.
+    CS1,
+    /// The last two blocks are swapped only when the last plaintext block is partial.
+    CS2,
+    /// The last two blocks are always swapped, as in Kerberos (RFC 3962).
+    CS3,
+}
+
+/// CBC with ciphertext stealing: the last partial block is encrypted after being filled with
+/// zeros, and the bytes of the second to last cipher block taken by those zeros are left out, as
+/// decryption can recover them. The cipher is as long as the bytes, which must fill at least one
+/// block.
+pub fn cbc_cs_encrypt<C: BlockCipher>(
+    block_cipher: &C,
+    iv: &[u8],
+    variant: CiphertextStealing,
+    bytes: &[u8],
+) -> Vec<u8> {
+    let block_size = block_cipher.block_size();
+    assert!(bytes.len() >= block_size, "Ciphertext stealing requires at least one block.");
+
+    let mut blocks = bytes_to_blocks(&cbc_encrypt(block_cipher, iv, bytes), block_size);
+    let n = blocks.len();
+    if n == 1 {
+        return blocks.concat();
+    }
+
+    let last_length = bytes.len() - (n - 1) * block_size;
+    blocks[n - 2].truncate(last_length);
+    if swaps_last_blocks(variant, last_length, block_size) {
+        blocks.swap(n - 2, n - 1);
+    }
+
+    blocks.concat()
+}
+
+pub fn cbc_cs_decrypt<C: BlockCipher>(
+    block_cipher: &C,
+    iv: &[u8],
+    variant: CiphertextStealing,
+    cipher: &[u8],
+) -> Vec<u8> {
+    let block_size = block_cipher.block_size();
+    assert!(cipher.len() >= block_size, "Ciphertext stealing requires at least one block.");
+
+    let n = (cipher.len() + block_size - 1) / block_size;
+    if n == 1 {
+        return cbc_decrypt(block_cipher, iv, cipher);
+    }
+
+    let last_length = cipher.len() - (n - 1) * block_size;
+    let (head, tail) = cipher.split_at((n - 2) * block_size);
+    let (partial_block, last_block) = if swaps_last_blocks(variant, last_length, block_size) {
+        (&tail[block_size..], &tail[..block_size])
+    } else {
+        (&tail[..last_length], &tail[last_length..])
+    };
+
+    // the left out bytes of the second to last block are the ones the zeros of the last
+    // plaintext block were xored with
+    let mut stolen_bytes = last_block.to_vec();
+    block_cipher.decrypt_block(&mut stolen_bytes);
+    let second_to_last_block = [partial_block, &stolen_bytes[last_length..]].concat();
+
+    let mut deciphered = cbc_decrypt(
+        block_cipher,
+        iv,
+        &[head, &second_to_last_block[..], last_block].concat(),
+    );
+    deciphered.truncate(cipher.len());
+
+    deciphered
+}
+
+fn swaps_last_blocks(variant: CiphertextStealing, last_length: usize, block_size: usize) -> bool {
+    match variant {
+        CiphertextStealing::CS1 => false,
+        CiphertextStealing::CS2 => last_length < block_size,
+        CiphertextStealing::CS3 => true,
+    }
+}
+
+/// Output Feedback: the iv is repeatedly encrypted, producing a keystream which is xored with the
+/// bytes. Encryption and decryption are therefore the same operation.
+pub fn ofb<C: BlockCipher>(block_cipher: &C, iv: &[u8], bytes: &[u8]) -> Vec<u8> {
+    assert_eq!(iv.len(), block_cipher.block_size());
+
+    let mut output_block = iv.to_vec();
+    let mut keystream = Vec::with_capacity(bytes.len() + block_cipher.block_size());
+    while keystream.len() < bytes.len() {
+        block_cipher.encrypt_block(&mut output_block);
+        keystream.extend_from_slice(&output_block);
+    }
+
+    xor::fixed_xor(bytes, &keystream[..bytes.len()])
+}
+
+/// Cipher Feedback: the input block, initially the iv, is encrypted, and its first segment_size
+/// bits are xored with the next segment of the bytes. The resulting cipher segment is then shifted
+/// into the input block. The segment size is in bits, either 1 or a whole number of bytes up to
+/// the block size (CFB-1, CFB-8, ..., CFB-128 for AES).
+pub fn cfb_encrypt<C: BlockCipher>(block_cipher: &C, iv: &[u8], segment_size: usize, bytes: &[u8])
+                                   -> Vec<u8> {
+    cfb(block_cipher, iv, segment_size, bytes, true)
+}
+
+pub fn cfb_decrypt<C: BlockCipher>(block_cipher: &C, iv: &[u8], segment_size: usize, cipher: &[u8])
+                                   -> Vec<u8> {
+    cfb(block_cipher, iv, segment_size, cipher, false)
+}
+
+fn cfb<C: BlockCipher>(
+    block_cipher: &C,
+    iv: &[u8],
+    segment_size: usize,
+    bytes: &[u8],
+    encrypting: bool,
+) -> Vec<u8> {
+    let block_size = block_cipher.block_size();
+    assert_eq!(iv.len(), block_size);
+    assert!(segment_size == 1 || (segment_size % 8 == 0 && segment_size <= 8 * block_size),
+            "Segment size must be 1 bit or a whole number of bytes up to the block size.");
+
+    let mut input_block = iv.to_vec();
+    let mut result = Vec::with_capacity(bytes.len());
+
+    if segment_size == 1 {
+        for byte in bytes.iter() {
+            let mut result_byte = 0u8;
+            for bit_index in (0..8).rev() {
+                let mut output_block = input_block.to_vec();
+                block_cipher.encrypt_block(&mut output_block);
+
+                let bit = (byte >> bit_index) & 1;
+                let result_bit = bit ^ (output_block[0] >> 7);
+                shift_in_bit(&mut input_block, if encrypting { result_bit } else { bit });
+                result_byte |= result_bit << bit_index;
+            }
+            result.push(result_byte);
+        }
+    } else {
+        for segment in bytes.chunks(segment_size / 8) {
+            let mut output_block = input_block.to_vec();
+            block_cipher.encrypt_block(&mut output_block);
+
+            let mut result_segment = xor::fixed_xor(segment, &output_block[..segment.len()]);
+            let cipher_segment = if encrypting { &result_segment } else { segment };
+            input_block = [&input_block[cipher_segment.len()..], &cipher_segment[..]].concat();
+            result.append(&mut result_segment);
+        }
+    }
+
+    result
+}
+
+/// Shifts a register of bytes one bit to the left, the bit coming in from the right.
+fn shift_in_bit(register: &mut [u8], bit: u8) {
+    for i in 0..register.len() {
+        let next_bit = if i + 1 < register.len() { register[i + 1] >> 7 } else { bit };
+        register[i] = (register[i] << 1) | next_bit;
+    }
+}
+
+/// Counter: the cipher encrypts successive counter blocks, producing a keystream which is xored
+/// with the bytes. Encryption and decryption are therefore the same operation.
+pub fn ctr<C: BlockCipher>(block_cipher: &C, counter_block: &CounterBlock, bytes: &[u8])
+                          -> Vec<u8> {
+    ctr_at(block_cipher, counter_block, 0, bytes)
+}
+
+/// Counter mode over bytes found at the given offset of the keystream, so that any range of a
+/// cipher can be decrypted without generating the keystream which precedes it.
+pub fn ctr_at<C: BlockCipher>(
+    block_cipher: &C,
+    counter_block: &CounterBlock,
+    offset: u64,
+    bytes: &[u8],
+) -> Vec<u8> {
+    let keystream = generate_keystream(block_cipher, counter_block, offset, bytes.len());
+
+    xor::fixed_xor(bytes, &keystream)
+}
+
+/// Generates length bytes of keystream, starting at the given offset of the keystream.
+fn generate_keystream<C: BlockCipher>(
+    block_cipher: &C,
+    counter_block: &CounterBlock,
+    offset: u64,
+    length: usize,
+) -> Vec<u8> {
+    let block_size = block_cipher.block_size();
+    let first_block_index = offset / block_size as u64;
+    let skipped_length = (offset % block_size as u64) as usize;
+    let blocks_count = (skipped_length + length + block_size - 1) / block_size;
+
+    let mut keystream = Vec::with_capacity(blocks_count * block_size);
+    for i in 0..blocks_count as u64 {
+        keystream.append(&mut counter_block.block(first_block_index + i, block_size));
+    }
+    block_cipher.encrypt_blocks(&mut keystream);
+
+    keystream[skipped_length..skipped_length + length].to_vec()
+}
+
+/// Splits bytes into blocks of the given size. The last block is filled with zeros when the bytes
+/// do not align to the block size.
+pub fn bytes_to_blocks(bytes: &[u8], block_size: usize) -> Vec<Vec<u8>> {
+    bytes.chunks(block_size)
+        .map(|chunk| {
+            let mut block = vec![0u8; block_size];
+            block[..chunk.len()].copy_from_slice(chunk);
+
+            block
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::{Aes, Key};
+    use aes::generate::generate_bytes_for_length;
+    use hex::hex_string_to_bytes;
+    use mode::toy::ToyCipher;
+
+    #[test]
+    fn bytes_to_blocks_test() {
+        let bytes: &[u8] = &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        let expected_blocks = vec![
+            vec![1, 2, 3, 4],
+            vec![5, 6, 7, 8],
+            vec![9, 10, 0, 0],
+        ];
+
+        let actual_blocks = bytes_to_blocks(bytes, 4);
+
+        assert_eq!(actual_blocks, expected_blocks);
+    }
+
+    #[test]
+    fn generate_keystream_test() {
+        let block_cipher = ToyCipher::new(0x0123456789abcdef);
+        let counter_block = &CounterBlock::little_endian(&[0xde, 0xad, 0xbe, 0xef]);
+        let first_blocks = ecb_encrypt(&block_cipher, &[
+            counter_block.block(0, 8),
+            counter_block.block(1, 8),
+            counter_block.block(2, 8),
+        ].concat());
+
+        let keystream = |offset, length| {
+            generate_keystream(&block_cipher, counter_block, offset, length)
+        };
+
+        // the keystream can start at any offset, including within a block
+        assert_eq!(keystream(0, 20), first_blocks[..20].to_vec());
+        assert_eq!(keystream(5, 7), first_blocks[5..12].to_vec());
+        assert_eq!(keystream(8, 8), first_blocks[8..16].to_vec());
+        assert!(keystream(3, 0).is_empty());
+    }
+
+    #[test]
+    fn ctr_nist_sp_800_38a_f_5_1_test_case() {
+        let key = Key::new(&hex_string_to_bytes("2b7e151628aed2a6abf7158809cf4f3c"));
+        let block_cipher = Aes::new(&key);
+        let counter_block = CounterBlock::big_endian(&[])
+            .with_initial_counter(0xf0f1f2f3f4f5f6f7f8f9fafbfcfdfeff);
+        let raw = hex_string_to_bytes(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+             30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710"
+        );
+        let expected_cipher = hex_string_to_bytes(
+            "874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff\
+             5ae4df3edbd5d35e5b4f09020db03eab1e031dda2fbe03d1792170a0f3009cee"
+        );
+
+        let actual_cipher = ctr(&block_cipher, &counter_block, &raw);
+
+        assert_eq!(actual_cipher, expected_cipher);
+    }
+
+    #[test]
+    fn ctr_rfc_3686_test_vector_2() {
+        let key = Key::new(&hex_string_to_bytes("7e24067817fae0d743d6ce1f32539163"));
+        let block_cipher = Aes::new(&key);
+        let nonce = hex_string_to_bytes("006cb6db");
+        let iv = hex_string_to_bytes("c0543b59da48d90b");
+        let counter_block = CounterBlock::big_endian(&[&nonce[..], &iv[..]].concat())
+            .with_initial_counter(1);
+        let raw = (0x00..0x20).collect::<Vec<u8>>();
+        let expected_cipher = hex_string_to_bytes(
+            "5104a106168a72d9790d41ee8edad388eb2e1efc46da57c8fce630df9141be28"
+        );
+
+        let actual_cipher = ctr(&block_cipher, &counter_block, &raw);
+
+        assert_eq!(actual_cipher, expected_cipher);
+    }
+
+    #[test]
+    fn shift_in_bit_test() {
+        let register = &mut [0b1000_0001, 0b0100_0000];
+
+        shift_in_bit(register, 1);
+
+        assert_eq!(register, &[0b0000_0010, 0b1000_0001]);
+    }
+
+    #[test]
+    fn ofb_nist_sp_800_38a_f_4_1_test_case() {
+        let (block_cipher, iv, raw) = nist_sp_800_38a_aes_128_example();
+        let expected_cipher = hex_string_to_bytes(
+            "3b3fd92eb72dad20333449f8e83cfb4a7789508d16918f03f53c52dac54ed825\
+             9740051e9c5fecf64344f7a82260edcc304c6528f659c77866a510d9c1d6ae5e"
+        );
+
+        let actual_cipher = ofb(&block_cipher, &iv, &raw);
+        let deciphered = ofb(&block_cipher, &iv, &actual_cipher);
+
+        assert_eq!(actual_cipher, expected_cipher);
+        assert_eq!(deciphered, raw);
+    }
+
+    #[test]
+    fn cfb128_nist_sp_800_38a_f_3_13_test_case() {
+        let (block_cipher, iv, raw) = nist_sp_800_38a_aes_128_example();
+        let expected_cipher = hex_string_to_bytes(
+            "3b3fd92eb72dad20333449f8e83cfb4ac8a64537a0b3a93fcde3cdad9f1ce58b\
+             26751f67a3cbb140b1808cf187a4f4dfc04b05357c5d1c0eeac4c66f9ff7f2e6"
+        );
+
+        let actual_cipher = cfb_encrypt(&block_cipher, &iv, 128, &raw);
+        let deciphered = cfb_decrypt(&block_cipher, &iv, 128, &actual_cipher);
+
+        assert_eq!(actual_cipher, expected_cipher);
+        assert_eq!(deciphered, raw);
+    }
+
+    #[test]
+    fn cfb8_nist_sp_800_38a_f_3_7_test_case() {
+        let (block_cipher, iv, raw) = nist_sp_800_38a_aes_128_example();
+        let raw = &raw[..18];
+        let expected_cipher = hex_string_to_bytes("3b79424c9c0dd436bace9e0ed4586a4f32b9");
+
+        let actual_cipher = cfb_encrypt(&block_cipher, &iv, 8, raw);
+        let deciphered = cfb_decrypt(&block_cipher, &iv, 8, &actual_cipher);
+
+        assert_eq!(actual_cipher, expected_cipher);
+        assert_eq!(&deciphered[..], raw);
+    }
+
+    #[test]
+    fn cfb1_nist_sp_800_38a_f_3_1_test_case() {
+        let (block_cipher, iv, raw) = nist_sp_800_38a_aes_128_example();
+        let raw = &raw[..2];
+        let expected_cipher = vec![0b0110_1000, 0b1011_0011];
+
+        let actual_cipher = cfb_encrypt(&block_cipher, &iv, 1, raw);
+        let deciphered = cfb_decrypt(&block_cipher, &iv, 1, &actual_cipher);
+
+        assert_eq!(actual_cipher, expected_cipher);
+        assert_eq!(&deciphered[..], raw);
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_ofb_and_cfb_with_toy_cipher() {
+        let block_cipher = ToyCipher::new(0x0123456789abcdef);
+        let iv: &[u8] = &[7, 6, 5, 4, 3, 2, 1, 0];
+        let raw = "not a multiple of the block size".as_bytes();
+
+        assert_eq!(&ofb(&block_cipher, iv, &ofb(&block_cipher, iv, raw))[..], raw);
+        for segment_size in [1, 8, 24, 64].iter() {
+            let cipher = cfb_encrypt(&block_cipher, iv, *segment_size, raw);
+
+            assert_eq!(cipher.len(), raw.len());
+            assert_eq!(&cfb_decrypt(&block_cipher, iv, *segment_size, &cipher)[..], raw);
+        }
+    }
+
+    /// Key, iv and plaintext shared by the AES-128 examples of SP 800-38A, Appendix F.
+    fn nist_sp_800_38a_aes_128_example() -> (Aes, Vec<u8>, Vec<u8>) {
+        let key = Key::new(&hex_string_to_bytes("2b7e151628aed2a6abf7158809cf4f3c"));
+        let iv = hex_string_to_bytes("000102030405060708090a0b0c0d0e0f");
+        let raw = hex_string_to_bytes(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+             30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710"
+        );
+
+        (Aes::new(&key), iv, raw)
+    }
+
+    #[test]
+    fn cbc_cs3_rfc_3962_test_vectors() {
+        let block_cipher = Aes::new(&Key::new_from_string("chicken teriyaki"));
+        let iv = &[0u8; 16];
+        let raw = &hex_string_to_bytes(
+            "4920776f756c64206c696b65207468652047656e6572616c20476175277320\
+             436869636b656e2c20706c656173652c"
+        );
+        let test_cases = vec![
+            (17, "c6353568f2bf8cb4d8a580362da7ff7f97"),
+            (31, "fc00783e0efdb2c1d445d4c8eff7ed2297687268d6ecccc0c07b25e25ecfe5"),
+            (32, "39312523a78662d5be7fcbcc98ebf5a897687268d6ecccc0c07b25e25ecfe584"),
+            (47, "97687268d6ecccc0c07b25e25ecfe584b3fffd940c16a18c1b5549d2f838029e\
+                  39312523a78662d5be7fcbcc98ebf5"),
+        ];
+
+        for (length, expected_cipher) in test_cases.iter() {
+            let raw = &raw[..*length];
+
+            let cipher = cbc_cs_encrypt(&block_cipher, iv, CiphertextStealing::CS3, raw);
+            let deciphered = cbc_cs_decrypt(&block_cipher, iv, CiphertextStealing::CS3, &cipher);
+
+            assert_eq!(cipher, hex_string_to_bytes(expected_cipher));
+            assert_eq!(&deciphered[..], raw);
+        }
+    }
+
+    #[test]
+    fn cbc_cs1_and_cs2_reorder_the_cs3_cipher() {
+        let block_cipher = ToyCipher::new(0x0123456789abcdef);
+        let iv: &[u8] = &[7, 6, 5, 4, 3, 2, 1, 0];
+        let raw = "twenty-one bytes long".as_bytes();
+        let aligned_raw = &raw[..16];
+
+        let cs1 = cbc_cs_encrypt(&block_cipher, iv, CiphertextStealing::CS1, raw);
+        let cs2 = cbc_cs_encrypt(&block_cipher, iv, CiphertextStealing::CS2, raw);
+        let cs3 = cbc_cs_encrypt(&block_cipher, iv, CiphertextStealing::CS3, raw);
+
+        // the partial second to last block is 5 bytes long
+        assert_eq!(cs1, [&cs3[..8], &cs3[16..], &cs3[8..16]].concat());
+        assert_eq!(cs2, cs3);
+        assert_eq!(cbc_cs_encrypt(&block_cipher, iv, CiphertextStealing::CS1, aligned_raw),
+                   cbc_encrypt(&block_cipher, iv, aligned_raw));
+        assert_eq!(cbc_cs_encrypt(&block_cipher, iv, CiphertextStealing::CS2, aligned_raw),
+                   cbc_encrypt(&block_cipher, iv, aligned_raw));
+        for variant in [CiphertextStealing::CS1, CiphertextStealing::CS2].iter() {
+            let cipher = cbc_cs_encrypt(&block_cipher, iv, *variant, raw);
+
+            assert_eq!(&cbc_cs_decrypt(&block_cipher, iv, *variant, &cipher)[..], raw);
+        }
+    }
+
+    #[test]
+    fn ctr_at_decrypts_any_range() {
+        let block_cipher = Aes::new(&Key::new_from_string("YELLOW SUBMARINE"));
+        let counter_block = &CounterBlock::little_endian(&[0u8; 8]);
+        // long enough for a one-byte counter to overflow
+        let raw = &generate_bytes_for_length(300 * 16 + 5);
+        let cipher = &ctr(&block_cipher, counter_block, raw);
+
+        for (start, end) in [(0, 1), (15, 17), (4095, 4200), (4800, 300 * 16 + 5)].iter() {
+            let deciphered = ctr_at(
+                &block_cipher,
+                counter_block,
+                *start as u64,
+                &cipher[*start..*end],
+            );
+
+            assert_eq!(&deciphered[..], &raw[*start..*end]);
+        }
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_ecb_with_toy_cipher() {
+        let block_cipher = ToyCipher::new(0x0123456789abcdef);
+        let raw = "sixteen byte msg".as_bytes();
+
+        let cipher = ecb_encrypt(&block_cipher, raw);
+        let deciphered = ecb_decrypt(&block_cipher, &cipher);
+
+        assert_eq!(cipher.len(), raw.len());
+        assert_ne!(&cipher[..], raw);
+        assert_eq!(&deciphered[..], raw);
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_cbc_with_toy_cipher() {
+        let block_cipher = ToyCipher::new(0x0123456789abcdef);
+        let iv: &[u8] = &[7, 6, 5, 4, 3, 2, 1, 0];
+        let raw = "aaaaaaaaaaaaaaaaaaaaaaaa".as_bytes();
+
+        let cipher = cbc_encrypt(&block_cipher, iv, raw);
+        let deciphered = cbc_decrypt(&block_cipher, iv, &cipher);
+
+        assert_eq!(cipher.len(), raw.len());
+        assert_ne!(cipher[..8], cipher[8..16]);
+        assert_eq!(&deciphered[..], raw);
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_ctr_with_toy_cipher() {
+        let block_cipher = ToyCipher::new(0x0123456789abcdef);
+        let counter_block = &CounterBlock::little_endian(&[0xde, 0xad, 0xbe, 0xef]);
+        let raw = "not a multiple of the block size".as_bytes();
+
+        let cipher = ctr(&block_cipher, counter_block, raw);
+        let deciphered = ctr(&block_cipher, counter_block, &cipher);
+
+        assert_eq!(cipher.len(), raw.len());
+        assert_ne!(&cipher[..], raw);
+        assert_eq!(&deciphered[..], raw);
+    }
+}
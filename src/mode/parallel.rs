@@ -0,0 +1,202 @@
+/// Resources used:
+/// https://doc.rust-lang.org/std/thread/fn.scope.html
+/// https://en.wikipedia.org/wiki/Block_cipher_mode_of_operation#Parallelizing_block_modes
+
+use std::thread;
+use mode;
+use mode::BlockCipher;
+use mode::counter::CounterBlock;
+
+/// Fewest blocks worth handing to a thread of their own: below that, spawning costs more than it
+/// saves.
+const MIN_BLOCKS_PER_THREAD: usize = 256;
+
+/// ECB encryption spread over the available threads, with the same output as `mode::ecb_encrypt`.
+pub fn par_ecb_encrypt<C: BlockCipher + Sync>(block_cipher: &C, bytes: &[u8]) -> Vec<u8> {
+    ecb_on_threads(block_cipher, bytes, true, threads_count(bytes, block_cipher))
+}
+
+pub fn par_ecb_decrypt<C: BlockCipher + Sync>(block_cipher: &C, cipher: &[u8]) -> Vec<u8> {
+    ecb_on_threads(block_cipher, cipher, false, threads_count(cipher, block_cipher))
+}
+
+/// CTR mode spread over the available threads, each of them generating the keystream from the
+/// offset of its chunk. Same output as `mode::ctr`.
+pub fn par_ctr<C: BlockCipher + Sync>(
+    block_cipher: &C,
+    counter_block: &CounterBlock,
+    bytes: &[u8],
+) -> Vec<u8> {
+    ctr_on_threads(block_cipher, counter_block, bytes, threads_count(bytes, block_cipher))
+}
+
+/// CBC decryption spread over the available threads: every plaintext block only depends on two
+/// cipher blocks, so unlike CBC encryption, the blocks can be decrypted in any order. Same output
+/// as `mode::cbc_decrypt`.
+pub fn par_cbc_decrypt<C: BlockCipher + Sync>(block_cipher: &C, iv: &[u8], cipher: &[u8])
+                                              -> Vec<u8> {
+    cbc_decrypt_on_threads(block_cipher, iv, cipher, threads_count(cipher, block_cipher))
+}
+
+fn ecb_on_threads<C: BlockCipher + Sync>(
+    block_cipher: &C,
+    bytes: &[u8],
+    encrypting: bool,
+    threads_count: usize,
+) -> Vec<u8> {
+    let mut blocks = whole_blocks(bytes, block_cipher.block_size());
+
+    for_each_chunk(&mut blocks, block_cipher.block_size(), threads_count, |_, chunk| {
+        if encrypting {
+            block_cipher.encrypt_blocks(chunk);
+        } else {
+            block_cipher.decrypt_blocks(chunk);
+        }
+    });
+
+    blocks
+}
+
+fn ctr_on_threads<C: BlockCipher + Sync>(
+    block_cipher: &C,
+    counter_block: &CounterBlock,
+    bytes: &[u8],
+    threads_count: usize,
+) -> Vec<u8> {
+    let mut output = bytes.to_vec();
+
+    for_each_chunk(&mut output, block_cipher.block_size(), threads_count, |offset, chunk| {
+        let xored = mode::ctr_at(block_cipher, counter_block, offset as u64, chunk);
+        chunk.copy_from_slice(&xored);
+    });
+
+    output
+}
+
+fn cbc_decrypt_on_threads<C: BlockCipher + Sync>(
+    block_cipher: &C,
+    iv: &[u8],
+    cipher: &[u8],
+    threads_count: usize,
+) -> Vec<u8> {
+    let block_size = block_cipher.block_size();
+    assert_eq!(iv.len(), block_size);
+    let cipher = &whole_blocks(cipher, block_size);
+    let mut output = cipher.clone();
+
+    for_each_chunk(&mut output, block_size, threads_count, |offset, chunk| {
+        let previous_blocks = match offset {
+            0 => [iv, &cipher[..chunk.len() - block_size]].concat(),
+            _ => cipher[offset - block_size..offset - block_size + chunk.len()].to_vec(),
+        };
+
+        block_cipher.decrypt_blocks(chunk);
+        for (byte, previous_byte) in chunk.iter_mut().zip(previous_blocks.iter()) {
+            *byte ^= previous_byte;
+        }
+    });
+
+    output
+}
+
+/// Copy of the bytes filled with zeros up to a whole number of blocks, as `mode::bytes_to_blocks`
+/// does.
+fn whole_blocks(bytes: &[u8], block_size: usize) -> Vec<u8> {
+    let mut blocks = bytes.to_vec();
+    blocks.resize((bytes.len() + block_size - 1) / block_size * block_size, 0);
+
+    blocks
+}
+
+/// Number of threads to split the blocks of the bytes over, at most one per available core.
+fn threads_count<C: BlockCipher>(bytes: &[u8], block_cipher: &C) -> usize {
+    let available = thread::available_parallelism().map(|count| count.get()).unwrap_or(1);
+    let blocks_count = bytes.len() / block_cipher.block_size();
+
+    available.min(blocks_count / MIN_BLOCKS_PER_THREAD).max(1)
+}
+
+/// Splits the buffer into chunks of whole blocks (except maybe the last one), one per thread, and
+/// processes them in parallel. The function is given the offset of its chunk in the buffer.
+fn for_each_chunk<F>(buffer: &mut [u8], block_size: usize, threads_count: usize, process: F)
+    where F: Fn(usize, &mut [u8]) + Sync {
+    let blocks_count = (buffer.len() + block_size - 1) / block_size;
+    if threads_count <= 1 || blocks_count <= 1 {
+        if !buffer.is_empty() {
+            process(0, buffer);
+        }
+        return;
+    }
+
+    let chunk_size = (blocks_count + threads_count - 1) / threads_count * block_size;
+    let process = &process;
+    thread::scope(|scope| {
+        for (i, chunk) in buffer.chunks_mut(chunk_size).enumerate() {
+            scope.spawn(move || process(i * chunk_size, chunk));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::{Aes, Key};
+    use aes::generate::generate_bytes_for_length;
+    use mode::toy::ToyCipher;
+
+    #[test]
+    fn parallel_modes_match_the_sequential_ones() {
+        let block_cipher = Aes::new(&Key::new(&generate_bytes_for_length(16)));
+        let counter_block = CounterBlock::big_endian(&generate_bytes_for_length(8))
+            .with_initial_counter(u64::max_value() as u128);
+        let iv = &generate_bytes_for_length(16);
+
+        // empty, short, then long enough for several threads (when there are cores for them)
+        for length in [0, 100, 16 * 2048 + 5].iter() {
+            let bytes = &generate_bytes_for_length(*length);
+
+            let ecb_cipher = par_ecb_encrypt(&block_cipher, bytes);
+            assert_eq!(ecb_cipher, mode::ecb_encrypt(&block_cipher, bytes));
+            assert_eq!(par_ecb_decrypt(&block_cipher, &ecb_cipher),
+                       mode::ecb_decrypt(&block_cipher, &ecb_cipher));
+
+            assert_eq!(par_ctr(&block_cipher, &counter_block, bytes),
+                       mode::ctr(&block_cipher, &counter_block, bytes));
+
+            let cbc_cipher = mode::cbc_encrypt(&block_cipher, iv, bytes);
+            assert_eq!(par_cbc_decrypt(&block_cipher, iv, &cbc_cipher),
+                       mode::cbc_decrypt(&block_cipher, iv, &cbc_cipher));
+        }
+    }
+
+    #[test]
+    fn chunks_on_several_threads_match_the_sequential_modes() {
+        let block_cipher = Aes::new(&Key::new(&generate_bytes_for_length(16)));
+        let counter_block = CounterBlock::little_endian(&[0x17; 8]);
+        let iv = &generate_bytes_for_length(16);
+        let bytes = &generate_bytes_for_length(16 * 10 + 7);
+        let cbc_cipher = &mode::cbc_encrypt(&block_cipher, iv, bytes);
+
+        // whatever the number of cores, including more threads than blocks
+        for threads_count in [2, 3, 4, 16].iter() {
+            assert_eq!(ecb_on_threads(&block_cipher, bytes, true, *threads_count),
+                       mode::ecb_encrypt(&block_cipher, bytes));
+            assert_eq!(ctr_on_threads(&block_cipher, &counter_block, bytes, *threads_count),
+                       mode::ctr(&block_cipher, &counter_block, bytes));
+            assert_eq!(cbc_decrypt_on_threads(&block_cipher, iv, cbc_cipher, *threads_count),
+                       mode::cbc_decrypt(&block_cipher, iv, cbc_cipher));
+        }
+    }
+
+    #[test]
+    fn parallel_modes_with_8_byte_blocks() {
+        let block_cipher = ToyCipher::new(0x0123_4567);
+        let iv = &[0x42; 8];
+        let bytes = &generate_bytes_for_length(8 * 4096);
+
+        let cbc_cipher = mode::cbc_encrypt(&block_cipher, iv, bytes);
+
+        assert_eq!(&par_cbc_decrypt(&block_cipher, iv, &cbc_cipher), bytes);
+        assert_eq!(par_ecb_encrypt(&block_cipher, bytes), mode::ecb_encrypt(&block_cipher, bytes));
+    }
+}
@@ -0,0 +1,240 @@
+/// Resources used:
+/// https://tools.ietf.org/html/rfc5297
+
+use aes::math::double_in_gf_128;
+use mode;
+use mode::{AuthenticationError, BlockCipher};
+use mode::counter::CounterBlock;
+use mode::in_place::ctr_in_place;
+use mode::mac::cmac;
+use xor;
+
+/// Synthetic initialization vector: the iv is the S2V pseudo-random function (CMAC under the
+/// first cipher) of the associated data and the bytes, which are then encrypted in CTR mode under
+/// the second cipher from that iv. Sealing is deterministic: the same inputs always give the same
+/// output, and a nonce is simply a last component of the associated data. Returns the iv followed
+/// by the cipher.
+pub fn siv_seal<C: BlockCipher>(
+    mac_cipher: &C,
+    ctr_cipher: &C,
+    associated_data: &[&[u8]],
+    raw_bytes: &[u8],
+) -> Vec<u8> {
+    let iv = s2v(mac_cipher, associated_data, raw_bytes);
+    let cipher = ctr(ctr_cipher, &iv, raw_bytes);
+
+    [iv, cipher].concat()
+}
+
+/// Decrypts sealed bytes, the iv followed by the cipher, then checks in constant time that the
+/// iv is the one synthesized from the associated data and the decrypted bytes.
+pub fn siv_open<C: BlockCipher>(
+    mac_cipher: &C,
+    ctr_cipher: &C,
+    associated_data: &[&[u8]],
+    sealed: &[u8],
+) -> Result<Vec<u8>, AuthenticationError> {
+    if sealed.len() < 16 {
+        return Err(AuthenticationError::InvalidLength);
+    }
+
+    let (iv, cipher) = sealed.split_at(16);
+    let raw_bytes = ctr(ctr_cipher, iv, cipher);
+
+    let expected_iv = s2v(mac_cipher, associated_data, &raw_bytes);
+    if !mode::constant_time_eq(&expected_iv, iv) {
+        return Err(AuthenticationError::TagMismatch);
+    }
+
+    Ok(raw_bytes)
+}
+
+/// Same as `siv_seal`, the cipher taking the place of the bytes in the buffer. Returns the iv.
+/// S2V still works on a copy of the bytes.
+pub fn siv_seal_in_place<C: BlockCipher>(
+    mac_cipher: &C,
+    ctr_cipher: &C,
+    associated_data: &[&[u8]],
+    buffer: &mut [u8],
+) -> Vec<u8> {
+    let iv = s2v(mac_cipher, associated_data, buffer);
+    ctr_in_place(ctr_cipher, &counter_block(ctr_cipher, &iv), buffer);
+
+    iv
+}
+
+/// Same as `siv_open`, the bytes taking the place of the cipher in the buffer, the iv being given
+/// on its own. When the iv doesn't match, the buffer is encrypted back.
+pub fn siv_open_in_place<C: BlockCipher>(
+    mac_cipher: &C,
+    ctr_cipher: &C,
+    associated_data: &[&[u8]],
+    iv: &[u8],
+    buffer: &mut [u8],
+) -> Result<(), AuthenticationError> {
+    if iv.len() != 16 {
+        return Err(AuthenticationError::InvalidTagLength);
+    }
+
+    let counter_block = counter_block(ctr_cipher, iv);
+    ctr_in_place(ctr_cipher, &counter_block, buffer);
+
+    let expected_iv = s2v(mac_cipher, associated_data, buffer);
+    if !mode::constant_time_eq(&expected_iv, iv) {
+        ctr_in_place(ctr_cipher, &counter_block, buffer);
+        return Err(AuthenticationError::TagMismatch);
+    }
+
+    Ok(())
+}
+
+/// S2V (RFC 5297, Sec. 2.4): the CMACs of the associated data are chained by doublings in
+/// GF(2^128), then mixed into the bytes, whose CMAC is the iv.
+fn s2v<C: BlockCipher>(mac_cipher: &C, associated_data: &[&[u8]], raw_bytes: &[u8]) -> Vec<u8> {
+    assert!(associated_data.len() <= 126, "SIV takes at most 126 associated data components.");
+
+    let mut d = cmac(mac_cipher, &[0u8; 16]);
+    for component in associated_data.iter() {
+        d = xor::fixed_xor(&double_in_gf_128(&d), &cmac(mac_cipher, component));
+    }
+
+    let t = if raw_bytes.len() >= 16 {
+        // xorend: the last 16 bytes are xored with D
+        let (head, tail) = raw_bytes.split_at(raw_bytes.len() - 16);
+        [head, &xor::fixed_xor(tail, &d)[..]].concat()
+    } else {
+        let mut padded_bytes = raw_bytes.to_vec();
+        padded_bytes.push(0x80);
+        padded_bytes.resize(16, 0);
+        xor::fixed_xor(&double_in_gf_128(&d), &padded_bytes)
+    };
+
+    cmac(mac_cipher, &t)
+}
+
+/// CTR mode over the whole 128-bit block, from the iv whose 31st and 63rd bits (from the right)
+/// are cleared, so that implementations may use 32-bit or 64-bit counters.
+fn ctr<C: BlockCipher>(ctr_cipher: &C, iv: &[u8], bytes: &[u8]) -> Vec<u8> {
+    mode::ctr(ctr_cipher, &counter_block(ctr_cipher, iv), bytes)
+}
+
+fn counter_block<C: BlockCipher>(ctr_cipher: &C, iv: &[u8]) -> CounterBlock {
+    assert_eq!(ctr_cipher.block_size(), 16, "SIV requires a 128-bit block cipher.");
+
+    let mut counter = [0u8; 16];
+    counter.copy_from_slice(iv);
+    counter[8] &= 0x7f;
+    counter[12] &= 0x7f;
+
+    CounterBlock::big_endian(&[]).with_initial_counter(u128::from_be_bytes(counter))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::{Aes, Key};
+    use hex::hex_string_to_bytes;
+
+    fn aes_pair(mac_key: &str, ctr_key: &str) -> (Aes, Aes) {
+        (Aes::new(&Key::new(&hex_string_to_bytes(mac_key))),
+         Aes::new(&Key::new(&hex_string_to_bytes(ctr_key))))
+    }
+
+    #[test]
+    fn siv_rfc_5297_deterministic_authenticated_encryption_example() {
+        let (mac_cipher, ctr_cipher) = aes_pair("fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0",
+                                                "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+        let associated_data = &hex_string_to_bytes(
+            "101112131415161718191a1b1c1d1e1f2021222324252627"
+        );
+        let raw = hex_string_to_bytes("112233445566778899aabbccddee");
+
+        let sealed = siv_seal(&mac_cipher, &ctr_cipher, &[associated_data], &raw);
+        let opened = siv_open(&mac_cipher, &ctr_cipher, &[associated_data], &sealed);
+
+        assert_eq!(sealed, hex_string_to_bytes(
+            "85632d07c6e8f37f950acd320a2ecc9340c02b9690c4dc04daef7f6afe5c"
+        ));
+        assert_eq!(opened, Ok(raw));
+    }
+
+    #[test]
+    fn siv_rfc_5297_nonce_based_authenticated_encryption_example() {
+        let (mac_cipher, ctr_cipher) = aes_pair("7f7e7d7c7b7a79787776757473727170",
+                                                "404142434445464748494a4b4c4d4e4f");
+        let associated_data = vec![
+            hex_string_to_bytes("00112233445566778899aabbccddeeffdeaddadadeaddadaffeeddccbbaa9988\
+                                 7766554433221100"),
+            hex_string_to_bytes("102030405060708090a0"),
+            // the nonce
+            hex_string_to_bytes("09f911029d74e35bd84156c5635688c0"),
+        ];
+        let associated_data: Vec<&[u8]> = associated_data.iter().map(|ad| &ad[..]).collect();
+        let raw = hex_string_to_bytes("7468697320697320736f6d6520706c61696e7465787420746f20656e\
+                                       6372797074207573696e67205349562d414553");
+
+        let sealed = siv_seal(&mac_cipher, &ctr_cipher, &associated_data, &raw);
+        let opened = siv_open(&mac_cipher, &ctr_cipher, &associated_data, &sealed);
+
+        assert_eq!(sealed, hex_string_to_bytes(
+            "7bdb6e3b432667eb06f4d14bff2fbd0fcb900f2fddbe404326601965c889bf17\
+             dba77ceb094fa663b7a3f748ba8af829ea64ad544a272e9c485b62a3fd5c0d"
+        ));
+        assert_eq!(opened, Ok(raw));
+    }
+
+    #[test]
+    fn siv_seal_empty_inputs() {
+        let (mac_cipher, ctr_cipher) = aes_pair("7f7e7d7c7b7a79787776757473727170",
+                                                "404142434445464748494a4b4c4d4e4f");
+
+        assert_eq!(siv_seal(&mac_cipher, &ctr_cipher, &[], &[]),
+                   hex_string_to_bytes("0c425c17acc305c2caa2cfc64360fad8"));
+        // an empty component still counts
+        assert_eq!(siv_seal(&mac_cipher, &ctr_cipher, &[&[][..]], &[]),
+                   hex_string_to_bytes("6810f8311a59ebfdaf8c3ebed5065663"));
+    }
+
+    #[test]
+    fn siv_open_rejects_altered_bytes() {
+        let (mac_cipher, ctr_cipher) = aes_pair("7f7e7d7c7b7a79787776757473727170",
+                                                "404142434445464748494a4b4c4d4e4f");
+        let header: &[&[u8]] = &[b"header"];
+        let sealed = siv_seal(&mac_cipher, &ctr_cipher, header, b"backup of the week");
+        let mut altered = sealed.clone();
+        altered[20] ^= 0x01;
+
+        assert_eq!(siv_open(&mac_cipher, &ctr_cipher, header, &altered),
+                   Err(AuthenticationError::TagMismatch));
+        assert_eq!(siv_open(&mac_cipher, &ctr_cipher, &[&b"footer"[..]], &sealed),
+                   Err(AuthenticationError::TagMismatch));
+        assert_eq!(siv_open(&mac_cipher, &ctr_cipher, &[], &sealed),
+                   Err(AuthenticationError::TagMismatch));
+        assert_eq!(siv_open(&mac_cipher, &ctr_cipher, header, &sealed[..15]),
+                   Err(AuthenticationError::InvalidLength));
+        // sealing is deterministic
+        assert_eq!(siv_seal(&mac_cipher, &ctr_cipher, header, b"backup of the week"),
+                   sealed);
+    }
+
+    #[test]
+    fn siv_in_place_matches_siv() {
+        let (mac_cipher, ctr_cipher) = aes_pair("7f7e7d7c7b7a79787776757473727170",
+                                                "404142434445464748494a4b4c4d4e4f");
+        let header: &[&[u8]] = &[b"header"];
+        let raw = b"backup of the week";
+        let sealed = siv_seal(&mac_cipher, &ctr_cipher, header, raw);
+        let buffer = &mut raw.to_vec();
+
+        let iv = siv_seal_in_place(&mac_cipher, &ctr_cipher, header, buffer);
+
+        assert_eq!(&[&iv[..], &buffer[..]].concat(), &sealed);
+        assert_eq!(siv_open_in_place(&mac_cipher, &ctr_cipher, &[], &iv, buffer),
+                   Err(AuthenticationError::TagMismatch));
+        assert_eq!(buffer[..], sealed[16..]);
+        assert_eq!(siv_open_in_place(&mac_cipher, &ctr_cipher, header, &iv[..15], buffer),
+                   Err(AuthenticationError::InvalidTagLength));
+        assert_eq!(siv_open_in_place(&mac_cipher, &ctr_cipher, header, &iv, buffer), Ok(()));
+        assert_eq!(&buffer[..], &raw[..]);
+    }
+}
@@ -0,0 +1,390 @@
+use aes::{Padding, DecryptError};
+use mode::{BlockCipher, generate_keystream};
+use mode::counter::CounterBlock;
+use xor;
+
+/// State a stream carries from one update to the next, depending on its block cipher mode.
+enum ModeState {
+    ECB,
+    CBC {
+        previous_block: Vec<u8>,
+    },
+    CTR {
+        counter_block: CounterBlock,
+        /// Offset in the keystream of the next byte.
+        position: u64,
+    },
+}
+
+impl ModeState {
+    fn cbc(iv: &[u8]) -> Self {
+        ModeState::CBC { previous_block: iv.to_vec() }
+    }
+
+    fn ctr(counter_block: &CounterBlock) -> Self {
+        ModeState::CTR { counter_block: counter_block.clone(), position: 0 }
+    }
+
+    fn encrypt_block<C: BlockCipher>(&mut self, block_cipher: &C, block: &mut Vec<u8>) {
+        if let ModeState::CBC { previous_block } = self {
+            *block = xor::fixed_xor(block, previous_block);
+            block_cipher.encrypt_block(block);
+            *previous_block = block.to_vec();
+        } else {
+            block_cipher.encrypt_block(block);
+        }
+    }
+
+    fn decrypt_block<C: BlockCipher>(&mut self, block_cipher: &C, block: &mut Vec<u8>) {
+        if let ModeState::CBC { previous_block } = self {
+            let cipher_block = block.to_vec();
+            block_cipher.decrypt_block(block);
+            *block = xor::fixed_xor(block, previous_block);
+            *previous_block = cipher_block;
+        } else {
+            block_cipher.decrypt_block(block);
+        }
+    }
+
+    /// Xors the bytes with the next keystream bytes.
+    fn apply_keystream<C: BlockCipher>(&mut self, block_cipher: &C, bytes: &[u8]) -> Vec<u8> {
+        if let ModeState::CTR { counter_block, position } = self {
+            let keystream = generate_keystream(block_cipher, counter_block, *position, bytes.len());
+            *position += bytes.len() as u64;
+
+            xor::fixed_xor(bytes, &keystream)
+        } else {
+            panic!("Only CTR mode generates a keystream.");
+        }
+    }
+
+    fn is_stream(&self) -> bool {
+        match self {
+            ModeState::CTR { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// Incremental encryption, for bytes which are not all available at once. Feeding the bytes
+/// through `update` in any number of parts, then calling `finalize`, produces the same cipher as
+/// encrypting all of them at once.
+pub struct Encryptor<'a, C: BlockCipher + 'a> {
+    block_cipher: &'a C,
+    mode_state: ModeState,
+    padding: &'a Padding,
+    /// Bytes of a partial block, waiting for the rest of the block.
+    buffer: Vec<u8>,
+}
+
+impl<'a, C: BlockCipher + 'a> Encryptor<'a, C> {
+    pub fn ecb(block_cipher: &'a C, padding: &'a Padding) -> Self {
+        Encryptor::new(block_cipher, ModeState::ECB, padding)
+    }
+
+    pub fn cbc(block_cipher: &'a C, iv: &[u8], padding: &'a Padding) -> Self {
+        assert_eq!(iv.len(), block_cipher.block_size());
+
+        Encryptor::new(block_cipher, ModeState::cbc(iv), padding)
+    }
+
+    pub fn ctr(block_cipher: &'a C, counter_block: &CounterBlock) -> Self {
+        Encryptor::new(block_cipher, ModeState::ctr(counter_block), &Padding::None)
+    }
+
+    fn new(block_cipher: &'a C, mode_state: ModeState, padding: &'a Padding) -> Self {
+        Encryptor {
+            block_cipher,
+            mode_state,
+            padding,
+            buffer: Vec::with_capacity(block_cipher.block_size()),
+        }
+    }
+
+    /// Encrypts as much of the bytes as possible, keeping any partial block for the next update.
+    pub fn update(&mut self, bytes: &[u8]) -> Vec<u8> {
+        if self.mode_state.is_stream() {
+            return self.mode_state.apply_keystream(self.block_cipher, bytes);
+        }
+
+        self.buffer.extend_from_slice(bytes);
+
+        let block_size = self.block_cipher.block_size();
+        let complete_length = self.buffer.len() - self.buffer.len() % block_size;
+        let complete_blocks: Vec<u8> = self.buffer.drain(..complete_length).collect();
+
+        self.encrypt_blocks(&complete_blocks)
+    }
+
+    /// Encrypts the last partial block, after padding it.
+    pub fn finalize(mut self) -> Vec<u8> {
+        if self.mode_state.is_stream() {
+            return vec![];
+        }
+
+        let block_size = self.block_cipher.block_size();
+        let last_bytes = if self.padding != &Padding::None {
+            self.padding.pad(&self.buffer, block_size)
+        } else if self.buffer.is_empty() {
+            vec![]
+        } else {
+            let mut last_block = self.buffer.to_vec();
+            last_block.resize(block_size, 0);
+
+            last_block
+        };
+
+        self.encrypt_blocks(&last_bytes)
+    }
+
+    fn encrypt_blocks(&mut self, bytes: &[u8]) -> Vec<u8> {
+        let mut cipher = Vec::with_capacity(bytes.len());
+        for chunk in bytes.chunks(self.block_cipher.block_size()) {
+            let mut block = chunk.to_vec();
+            self.mode_state.encrypt_block(self.block_cipher, &mut block);
+            cipher.append(&mut block);
+        }
+
+        cipher
+    }
+}
+
+/// Incremental decryption, the counterpart of `Encryptor`. When the plaintext is padded, the last
+/// complete block is held back until `finalize`, as it is the only one holding the padding.
+pub struct Decryptor<'a, C: BlockCipher + 'a> {
+    block_cipher: &'a C,
+    mode_state: ModeState,
+    padding: &'a Padding,
+    /// Cipher bytes not yet decrypted.
+    buffer: Vec<u8>,
+}
+
+impl<'a, C: BlockCipher + 'a> Decryptor<'a, C> {
+    pub fn ecb(block_cipher: &'a C, padding: &'a Padding) -> Self {
+        Decryptor::new(block_cipher, ModeState::ECB, padding)
+    }
+
+    pub fn cbc(block_cipher: &'a C, iv: &[u8], padding: &'a Padding) -> Self {
+        assert_eq!(iv.len(), block_cipher.block_size());
+
+        Decryptor::new(block_cipher, ModeState::cbc(iv), padding)
+    }
+
+    pub fn ctr(block_cipher: &'a C, counter_block: &CounterBlock) -> Self {
+        Decryptor::new(block_cipher, ModeState::ctr(counter_block), &Padding::None)
+    }
+
+    fn new(block_cipher: &'a C, mode_state: ModeState, padding: &'a Padding) -> Self {
+        Decryptor {
+            block_cipher,
+            mode_state,
+            padding,
+            buffer: Vec::with_capacity(2 * block_cipher.block_size()),
+        }
+    }
+
+    /// Decrypts as much of the cipher as possible, keeping any partial block, as well as the last
+    /// block when the plaintext is padded, for the next update.
+    pub fn update(&mut self, cipher: &[u8]) -> Vec<u8> {
+        if self.mode_state.is_stream() {
+            return self.mode_state.apply_keystream(self.block_cipher, cipher);
+        }
+
+        self.buffer.extend_from_slice(cipher);
+
+        let block_size = self.block_cipher.block_size();
+        let mut complete_length = self.buffer.len() - self.buffer.len() % block_size;
+        if self.padding != &Padding::None && complete_length == self.buffer.len()
+            && complete_length > 0 {
+            complete_length -= block_size;
+        }
+        let complete_blocks: Vec<u8> = self.buffer.drain(..complete_length).collect();
+
+        self.decrypt_blocks(&complete_blocks)
+    }
+
+    /// Whether the decryptor can move through the cipher, which only CTR mode allows.
+    pub fn is_seekable(&self) -> bool {
+        self.mode_state.is_stream()
+    }
+
+    /// Moves to the given offset of the keystream, so that the next update decrypts the cipher
+    /// found there.
+    pub fn seek(&mut self, offset: u64) {
+        match self.mode_state {
+            ModeState::CTR { ref mut position, .. } => *position = offset,
+            _ => panic!("Only CTR mode allows random access."),
+        }
+    }
+
+    /// Decrypts the held back block and removes its padding.
+    pub fn finalize(mut self) -> Result<Vec<u8>, DecryptError> {
+        if self.mode_state.is_stream() {
+            return Ok(vec![]);
+        }
+
+        let block_size = self.block_cipher.block_size();
+        // a padded cipher ends with the held back block, an unpadded one with whole blocks
+        if self.buffer.len() % block_size != 0 {
+            return Err(DecryptError::InvalidLength {
+                length: self.buffer.len(),
+                block_size,
+            });
+        }
+
+        let last_bytes = self.buffer.to_vec();
+        let deciphered = self.decrypt_blocks(&last_bytes);
+
+        Ok(self.padding.unpad(&deciphered, block_size)?)
+    }
+
+    fn decrypt_blocks(&mut self, cipher: &[u8]) -> Vec<u8> {
+        let mut deciphered = Vec::with_capacity(cipher.len());
+        for chunk in cipher.chunks(self.block_cipher.block_size()) {
+            let mut block = chunk.to_vec();
+            self.mode_state.decrypt_block(self.block_cipher, &mut block);
+            deciphered.append(&mut block);
+        }
+
+        deciphered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::{Aes, Key, PaddingError, pkcs7_pad};
+    use aes::generate::{generate_bytes_for_length, generate_aes_128_key};
+    use mode;
+
+    /// Splits bytes in parts of uneven lengths, so that updates straddle block boundaries.
+    fn uneven_parts(bytes: &[u8]) -> Vec<&[u8]> {
+        let mut parts = vec![];
+        let mut start = 0;
+        let mut length = 0;
+        while start < bytes.len() {
+            let end = (start + length).min(bytes.len());
+            parts.push(&bytes[start..end]);
+            start = end;
+            length = (length + 7) % 23;
+        }
+
+        parts
+    }
+
+    fn encrypt_in_parts<C: BlockCipher>(mut encryptor: Encryptor<C>, raw: &[u8]) -> Vec<u8> {
+        let mut cipher = vec![];
+        for part in uneven_parts(raw) {
+            cipher.append(&mut encryptor.update(part));
+        }
+        cipher.append(&mut encryptor.finalize());
+
+        cipher
+    }
+
+    fn decrypt_in_parts<C: BlockCipher>(mut decryptor: Decryptor<C>, cipher: &[u8])
+                                        -> Result<Vec<u8>, DecryptError> {
+        let mut deciphered = vec![];
+        for part in uneven_parts(cipher) {
+            deciphered.append(&mut decryptor.update(part));
+        }
+        deciphered.append(&mut decryptor.finalize()?);
+
+        Ok(deciphered)
+    }
+
+    #[test]
+    fn ecb_stream_matches_one_shot_encryption() {
+        let block_cipher = &Aes::new(&generate_aes_128_key());
+        let raw = &generate_bytes_for_length(1000);
+
+        let cipher = encrypt_in_parts(Encryptor::ecb(block_cipher, &Padding::PKCS7), raw);
+        let deciphered = decrypt_in_parts(Decryptor::ecb(block_cipher, &Padding::PKCS7), &cipher);
+
+        assert_eq!(cipher, mode::ecb_encrypt(block_cipher, &pkcs7_pad(raw, 16)));
+        assert_eq!(&deciphered.unwrap(), raw);
+    }
+
+    #[test]
+    fn cbc_stream_matches_one_shot_encryption() {
+        let block_cipher = &Aes::new(&generate_aes_128_key());
+        let iv = &generate_bytes_for_length(16);
+        let raw = &generate_bytes_for_length(1024);
+
+        let cipher = encrypt_in_parts(Encryptor::cbc(block_cipher, iv, &Padding::PKCS7), raw);
+        let deciphered = decrypt_in_parts(
+            Decryptor::cbc(block_cipher, iv, &Padding::PKCS7),
+            &cipher,
+        );
+
+        assert_eq!(cipher.len(), 1024 + 16);
+        assert_eq!(cipher, mode::cbc_encrypt(block_cipher, iv, &pkcs7_pad(raw, 16)));
+        assert_eq!(&deciphered.unwrap(), raw);
+    }
+
+    #[test]
+    fn cbc_stream_without_padding_matches_one_shot_encryption() {
+        let block_cipher = &Aes::new(&generate_aes_128_key());
+        let iv = &generate_bytes_for_length(16);
+        let raw = &generate_bytes_for_length(512);
+
+        let cipher = encrypt_in_parts(Encryptor::cbc(block_cipher, iv, &Padding::None), raw);
+        let deciphered = decrypt_in_parts(
+            Decryptor::cbc(block_cipher, iv, &Padding::None),
+            &cipher,
+        );
+
+        assert_eq!(cipher, mode::cbc_encrypt(block_cipher, iv, raw));
+        assert_eq!(&deciphered.unwrap(), raw);
+    }
+
+    #[test]
+    fn ctr_stream_matches_one_shot_encryption() {
+        let block_cipher = &Aes::new(&Key::new_from_string("YELLOW SUBMARINE"));
+        let counter_block = &CounterBlock::little_endian(&[0u8; 8]);
+        let raw = &generate_bytes_for_length(999);
+
+        let cipher = encrypt_in_parts(Encryptor::ctr(block_cipher, counter_block), raw);
+        let deciphered = decrypt_in_parts(Decryptor::ctr(block_cipher, counter_block), &cipher);
+
+        assert_eq!(cipher, mode::ctr(block_cipher, counter_block, raw));
+        assert_eq!(&deciphered.unwrap(), raw);
+    }
+
+    #[test]
+    fn ctr_decryptor_seeks_through_the_keystream() {
+        let block_cipher = &Aes::new(&Key::new_from_string("YELLOW SUBMARINE"));
+        let counter_block = &CounterBlock::little_endian(&[0u8; 8]);
+        let raw = &generate_bytes_for_length(100);
+        let cipher = &mode::ctr(block_cipher, counter_block, raw);
+        let mut decryptor = Decryptor::ctr(block_cipher, counter_block);
+
+        decryptor.seek(37);
+
+        assert!(decryptor.is_seekable());
+        assert_eq!(&decryptor.update(&cipher[37..70])[..], &raw[37..70]);
+        assert!(!Decryptor::ecb(block_cipher, &Padding::None).is_seekable());
+    }
+
+    #[test]
+    fn decryptor_rejects_invalid_padding() {
+        let block_cipher = &Aes::new(&generate_aes_128_key());
+        let cipher = mode::ecb_encrypt(block_cipher, &[&[0x05u8; 31][..], &[0x04][..]].concat());
+
+        let deciphered = decrypt_in_parts(Decryptor::ecb(block_cipher, &Padding::PKCS7), &cipher);
+
+        assert_eq!(deciphered,
+                   Err(DecryptError::Padding(PaddingError::PaddingNotConsistent)));
+    }
+
+    #[test]
+    fn decryptor_rejects_partial_blocks() {
+        let block_cipher = &Aes::new(&generate_aes_128_key());
+        let cipher = &generate_bytes_for_length(40);
+        let decryptor = Decryptor::cbc(block_cipher, &[0; 16], &Padding::None);
+
+        let deciphered = decrypt_in_parts(decryptor, cipher);
+
+        assert_eq!(deciphered, Err(DecryptError::InvalidLength { length: 8, block_size: 16 }));
+    }
+}
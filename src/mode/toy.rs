@@ -0,0 +1,63 @@
+use mode::BlockCipher;
+
+/// Toy 8-byte block cipher, a balanced Feistel network over two 32-bit halves. It is not meant to
+/// be secure, only to exercise the modes of operation and attacks with a block size other than
+/// the one of AES.
+pub struct ToyCipher {
+    round_keys: [u32; 8],
+}
+
+impl ToyCipher {
+    pub fn new(key: u64) -> Self {
+        let mut round_keys = [0u32; 8];
+        for (i, round_key) in round_keys.iter_mut().enumerate() {
+            *round_key = (key.rotate_left(8 * i as u32) >> 32) as u32 ^ i as u32;
+        }
+
+        ToyCipher { round_keys }
+    }
+
+    fn round_function(half: u32, round_key: u32) -> u32 {
+        (half ^ round_key).wrapping_mul(0x9e37_79b1).rotate_left(7)
+    }
+}
+
+impl BlockCipher for ToyCipher {
+    fn block_size(&self) -> usize {
+        8
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        let (mut left, mut right) = split(block);
+        for round_key in self.round_keys.iter() {
+            let next_right = left ^ ToyCipher::round_function(right, *round_key);
+            left = right;
+            right = next_right;
+        }
+        join(block, left, right);
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        let (mut left, mut right) = split(block);
+        for round_key in self.round_keys.iter().rev() {
+            let previous_left = right ^ ToyCipher::round_function(left, *round_key);
+            right = left;
+            left = previous_left;
+        }
+        join(block, left, right);
+    }
+}
+
+fn split(block: &[u8]) -> (u32, u32) {
+    assert_eq!(block.len(), 8);
+
+    (
+        u32::from_be_bytes([block[0], block[1], block[2], block[3]]),
+        u32::from_be_bytes([block[4], block[5], block[6], block[7]])
+    )
+}
+
+fn join(block: &mut [u8], left: u32, right: u32) {
+    block[..4].copy_from_slice(&left.to_be_bytes());
+    block[4..].copy_from_slice(&right.to_be_bytes());
+}
@@ -0,0 +1,321 @@
+/// Resources used:
+/// https://ieeexplore.ieee.org/document/4493450 (IEEE 1619-2007)
+/// https://nvlpubs.nist.gov/nistpubs/Legacy/SP/nistspecialpublication800-38e.pdf
+/// https://en.wikipedia.org/wiki/Disk_encryption_theory
+
+use aes::{Aes, Key};
+use aes::math::{double_in_gf_128_little_endian, double_u128_in_gf_128};
+use mode::BlockCipher;
+use mode::in_place::xor_in_place;
+use xor;
+
+/// XTS (XEX-based tweaked codebook mode with ciphertext stealing): each sector of a disk is
+/// encrypted on its own, under a tweak derived from its sector number. Every block j of the sector
+/// is masked before and after its encryption by T = E_K2(sector number) * alpha^j in GF(2^128),
+/// so that equal blocks at different positions don't encrypt to the same cipher.
+pub struct Xts<C: BlockCipher> {
+    data_cipher: C,
+    tweak_cipher: C,
+}
+
+impl Xts<Aes> {
+    /// XTS-AES under Key1, encrypting the data, and Key2, encrypting the tweaks. Both keys have the
+    /// same size: 256-bit XTS-AES keys are two AES-128 keys.
+    pub fn from_keys(data_key: &Key, tweak_key: &Key) -> Self {
+        assert!(data_key.size() == tweak_key.size(), "Both XTS keys must have the same size.");
+
+        Xts::new(Aes::new(data_key), Aes::new(tweak_key))
+    }
+}
+
+impl<C: BlockCipher> Xts<C> {
+    pub fn new(data_cipher: C, tweak_cipher: C) -> Self {
+        assert_eq!(data_cipher.block_size(), 16, "XTS requires a 128-bit block cipher.");
+        assert_eq!(tweak_cipher.block_size(), 16, "XTS requires a 128-bit block cipher.");
+
+        Xts { data_cipher, tweak_cipher }
+    }
+
+    /// Encrypts a sector of at least one block. When its length isn't a whole number of blocks,
+    /// the last partial block steals the end of the cipher of the previous block, and the cipher
+    /// keeps the length of the sector.
+    pub fn encrypt_sector(&self, sector_number: u128, bytes: &[u8]) -> Vec<u8> {
+        self.process_sector(sector_number, bytes, true)
+    }
+
+    pub fn decrypt_sector(&self, sector_number: u128, cipher: &[u8]) -> Vec<u8> {
+        self.process_sector(sector_number, cipher, false)
+    }
+
+    /// Encrypts consecutive sectors of the given size, the first one having the given number. The
+    /// last sector may be shorter, but no shorter than a block.
+    pub fn encrypt_sectors(&self, first_sector_number: u128, sector_size: usize, bytes: &[u8])
+                           -> Vec<u8> {
+        bytes.chunks(sector_size)
+            .enumerate()
+            .flat_map(|(i, sector)| self.encrypt_sector(first_sector_number + i as u128, sector))
+            .collect()
+    }
+
+    pub fn decrypt_sectors(&self, first_sector_number: u128, sector_size: usize, cipher: &[u8])
+                           -> Vec<u8> {
+        cipher.chunks(sector_size)
+            .enumerate()
+            .flat_map(|(i, sector)| self.decrypt_sector(first_sector_number + i as u128, sector))
+            .collect()
+    }
+
+    /// Same as `encrypt_sector`, the cipher taking the place of the sector. The tweaks are kept on
+    /// the stack instead of being collected.
+    pub fn encrypt_sector_in_place(&self, sector_number: u128, sector: &mut [u8]) {
+        self.process_sector_in_place(sector_number, sector, true)
+    }
+
+    pub fn decrypt_sector_in_place(&self, sector_number: u128, sector: &mut [u8]) {
+        self.process_sector_in_place(sector_number, sector, false)
+    }
+
+    /// Same as `encrypt_sectors`, the cipher taking the place of the sectors in the buffer.
+    pub fn encrypt_sectors_in_place(&self, first_sector_number: u128, sector_size: usize,
+                                    buffer: &mut [u8]) {
+        for (i, sector) in buffer.chunks_mut(sector_size).enumerate() {
+            self.encrypt_sector_in_place(first_sector_number + i as u128, sector);
+        }
+    }
+
+    pub fn decrypt_sectors_in_place(&self, first_sector_number: u128, sector_size: usize,
+                                    buffer: &mut [u8]) {
+        for (i, sector) in buffer.chunks_mut(sector_size).enumerate() {
+            self.decrypt_sector_in_place(first_sector_number + i as u128, sector);
+        }
+    }
+
+    fn process_sector(&self, sector_number: u128, bytes: &[u8], encrypting: bool) -> Vec<u8> {
+        assert!(bytes.len() >= 16, "XTS requires at least one block per sector.");
+
+        let tweaks = self.tweaks(sector_number, (bytes.len() + 15) / 16);
+        let full_blocks_count = bytes.len() / 16;
+        let last_length = bytes.len() % 16;
+
+        // without stealing, every full block is processed under its own tweak
+        let stolen_block_index = match last_length {
+            0 => full_blocks_count,
+            _ => full_blocks_count - 1,
+        };
+        let mut output: Vec<u8> = bytes[..16 * stolen_block_index].chunks(16)
+            .zip(tweaks.iter())
+            .flat_map(|(block, tweak)| self.process_block(block, tweak, encrypting))
+            .collect();
+
+        if last_length != 0 {
+            let last_full_block = &bytes[16 * stolen_block_index..16 * full_blocks_count];
+            let partial_block = &bytes[16 * full_blocks_count..];
+            // the decryption of the last full block uses the tweak of the partial block, which
+            // was used last on encryption
+            let (first_tweak, second_tweak) = if encrypting {
+                (&tweaks[stolen_block_index], &tweaks[full_blocks_count])
+            } else {
+                (&tweaks[full_blocks_count], &tweaks[stolen_block_index])
+            };
+
+            let intermediate_block = self.process_block(last_full_block, first_tweak, encrypting);
+            let stolen_block = [partial_block, &intermediate_block[last_length..]].concat();
+
+            output.extend(self.process_block(&stolen_block, second_tweak, encrypting));
+            output.extend_from_slice(&intermediate_block[..last_length]);
+        }
+
+        output
+    }
+
+    /// Same as `process_sector`, keeping the tweak of the current block as an integer.
+    fn process_sector_in_place(&self, sector_number: u128, sector: &mut [u8], encrypting: bool) {
+        assert!(sector.len() >= 16, "XTS requires at least one block per sector.");
+
+        let mut tweak = sector_number.to_le_bytes();
+        self.tweak_cipher.encrypt_block(&mut tweak);
+        let mut tweak = u128::from_le_bytes(tweak);
+        let full_blocks_count = sector.len() / 16;
+        let last_length = sector.len() % 16;
+
+        let stolen_block_index = match last_length {
+            0 => full_blocks_count,
+            _ => full_blocks_count - 1,
+        };
+        for block in sector[..16 * stolen_block_index].chunks_mut(16) {
+            self.process_block_in_place(block, tweak, encrypting);
+            tweak = double_u128_in_gf_128(tweak);
+        }
+
+        if last_length != 0 {
+            let next_tweak = double_u128_in_gf_128(tweak);
+            let (first_tweak, second_tweak) = match encrypting {
+                true => (tweak, next_tweak),
+                false => (next_tweak, tweak),
+            };
+            let (last_full_block, partial_block) = sector[16 * stolen_block_index..]
+                .split_at_mut(16);
+
+            self.process_block_in_place(last_full_block, first_tweak, encrypting);
+            // the partial block steals the end of the intermediate block, whose start moves to
+            // the end of the sector
+            last_full_block[..last_length].swap_with_slice(partial_block);
+            self.process_block_in_place(last_full_block, second_tweak, encrypting);
+        }
+    }
+
+    fn process_block_in_place(&self, block: &mut [u8], tweak: u128, encrypting: bool) {
+        let tweak = tweak.to_le_bytes();
+        xor_in_place(block, &tweak);
+        if encrypting {
+            self.data_cipher.encrypt_block(block);
+        } else {
+            self.data_cipher.decrypt_block(block);
+        }
+        xor_in_place(block, &tweak);
+    }
+
+    fn process_block(&self, block: &[u8], tweak: &[u8], encrypting: bool) -> Vec<u8> {
+        let mut block = xor::fixed_xor(block, tweak);
+        if encrypting {
+            self.data_cipher.encrypt_block(&mut block);
+        } else {
+            self.data_cipher.decrypt_block(&mut block);
+        }
+
+        xor::fixed_xor(&block, tweak)
+    }
+
+    /// Tweaks of the blocks of a sector: the encrypted sector number, as a 128-bit little-endian
+    /// integer, doubled once per block.
+    fn tweaks(&self, sector_number: u128, blocks_count: usize) -> Vec<Vec<u8>> {
+        let mut tweak = sector_number.to_le_bytes().to_vec();
+        self.tweak_cipher.encrypt_block(&mut tweak);
+
+        let mut tweaks = Vec::with_capacity(blocks_count);
+        for _ in 0..blocks_count {
+            let next_tweak = double_in_gf_128_little_endian(&tweak);
+            tweaks.push(tweak);
+            tweak = next_tweak;
+        }
+
+        tweaks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex::hex_string_to_bytes;
+
+    fn xts_aes(data_key: &str, tweak_key: &str) -> Xts<Aes> {
+        Xts::from_keys(&Key::new(&hex_string_to_bytes(data_key)),
+                       &Key::new(&hex_string_to_bytes(tweak_key)))
+    }
+
+    #[test]
+    fn xts_aes_128_ieee_1619_vectors_1_to_3() {
+        // (Key1, Key2, data unit sequence number, plaintext, ciphertext)
+        let test_cases = vec![
+            ("00000000000000000000000000000000", "00000000000000000000000000000000", 0,
+             "0000000000000000000000000000000000000000000000000000000000000000",
+             "917cf69ebd68b2ec9b9fe9a3eadda692cd43d2f59598ed858c02c2652fbf922e"),
+            ("11111111111111111111111111111111", "22222222222222222222222222222222", 0x3333333333,
+             "4444444444444444444444444444444444444444444444444444444444444444",
+             "c454185e6a16936e39334038acef838bfb186fff7480adc4289382ecd6d394f0"),
+            ("fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0", "22222222222222222222222222222222", 0x3333333333,
+             "4444444444444444444444444444444444444444444444444444444444444444",
+             "af85336b597afc1a900b2eb21ec949d292df4c047e0b21532186a5971a227a89"),
+        ];
+
+        for (data_key, tweak_key, sector_number, raw, expected_cipher) in test_cases.iter() {
+            let xts = xts_aes(data_key, tweak_key);
+            let raw = &hex_string_to_bytes(raw);
+
+            let cipher = xts.encrypt_sector(*sector_number, raw);
+
+            assert_eq!(cipher, hex_string_to_bytes(expected_cipher));
+            assert_eq!(&xts.decrypt_sector(*sector_number, &cipher), raw);
+        }
+    }
+
+    #[test]
+    fn xts_aes_512_byte_sectors_ieee_1619_vectors_4_and_10() {
+        let raw = &(0..=255).chain(0..=255).collect::<Vec<u8>>();
+        // (Key1, Key2, data unit sequence number, first and last 32 bytes of the ciphertext)
+        let test_cases = vec![
+            ("27182818284590452353602874713526", "31415926535897932384626433832795", 0,
+             "27a7479befa1d476489f308cd4cfa6e2a96e4bbe3208ff25287dd3819616e89c",
+             "eb4a427d1923ce3ff262735779a418f20a282df920147beabe421ee5319d0568"),
+            ("2718281828459045235360287471352662497757247093699959574966967627",
+             "3141592653589793238462643383279502884197169399375105820974944592", 0xff,
+             "1c3b3a102f770386e4836c99e370cf9bea00803f5e482357a4ae12d414a3e63b",
+             "773dad38014bd2092fa755c824bb5e54c4f36ffda9fcea70b9c6e693e148c151"),
+        ];
+
+        for (data_key, tweak_key, sector_number, cipher_start, cipher_end) in test_cases.iter() {
+            let xts = xts_aes(data_key, tweak_key);
+
+            let cipher = xts.encrypt_sector(*sector_number, raw);
+
+            assert_eq!(cipher[..32], hex_string_to_bytes(cipher_start)[..]);
+            assert_eq!(cipher[480..], hex_string_to_bytes(cipher_end)[..]);
+            assert_eq!(&xts.decrypt_sector(*sector_number, &cipher), raw);
+        }
+    }
+
+    #[test]
+    fn xts_aes_ciphertext_stealing_ieee_1619_vectors_15_to_18() {
+        let xts = xts_aes("fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0", "bfbebdbcbbbab9b8b7b6b5b4b3b2b1b0");
+        let test_cases = vec![
+            "641610679dcbf92e505c41333fb06c2a95",
+            "223a725cbcd4dc647b9a9826d54c99c895c8",
+            "0d39809a65c1d55501960b671d4b8b6b95c871",
+            "a8ba0048d75084603eb8423a09b7bf7595c871f6",
+        ];
+
+        for (i, expected_cipher) in test_cases.iter().enumerate() {
+            let raw = &(0..17 + i as u8).collect::<Vec<u8>>();
+
+            let cipher = xts.encrypt_sector(0x9a78563412, raw);
+
+            assert_eq!(cipher, hex_string_to_bytes(expected_cipher));
+            assert_eq!(&xts.decrypt_sector(0x9a78563412, &cipher), raw);
+        }
+    }
+
+    #[test]
+    fn decrypt_a_single_sector_of_an_image() {
+        let xts = xts_aes("27182818284590452353602874713526", "31415926535897932384626433832795");
+        let image = &(0..2000).map(|i| (i * 7) as u8).collect::<Vec<u8>>();
+
+        let encrypted_image = xts.encrypt_sectors(100, 512, image);
+        let third_sector = xts.decrypt_sector(102, &encrypted_image[1024..1536]);
+
+        assert_eq!(encrypted_image.len(), image.len());
+        assert_eq!(&third_sector[..], &image[1024..1536]);
+        assert_eq!(&xts.decrypt_sectors(100, 512, &encrypted_image), image);
+        // the same data in another sector encrypts differently
+        assert_ne!(xts.encrypt_sector(101, &image[..512]), encrypted_image[..512].to_vec());
+    }
+
+    #[test]
+    fn in_place_sectors_match_the_allocating_ones() {
+        let xts = xts_aes("fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0", "bfbebdbcbbbab9b8b7b6b5b4b3b2b1b0");
+
+        for length in [16, 17, 31, 32, 47, 512, 1000].iter() {
+            let bytes = &(0..*length).map(|i| (i * 13) as u8).collect::<Vec<u8>>();
+            let buffer = &mut bytes.clone();
+
+            xts.encrypt_sector_in_place(0x9a78563412, buffer);
+            assert_eq!(buffer, &xts.encrypt_sector(0x9a78563412, bytes));
+            xts.decrypt_sector_in_place(0x9a78563412, buffer);
+            assert_eq!(buffer, bytes);
+
+            xts.encrypt_sectors_in_place(7, 48, buffer);
+            assert_eq!(buffer, &xts.encrypt_sectors(7, 48, bytes));
+            xts.decrypt_sectors_in_place(7, 48, buffer);
+            assert_eq!(buffer, bytes);
+        }
+    }
+}
@@ -176,7 +176,7 @@ fn detect_aes_in_ecb_mode(cipher_candidates: Vec<Vec<u8>>) -> Vec<u8> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use aes::BlockCipherMode;
+    use aes::{BlockCipherMode, AESEncryptionOptions, Padding};
 
     #[test]
     fn challenge1() {
@@ -281,7 +281,9 @@ mod tests {
         let cipher = &file::read_base64_file_bytes("./resources/7.txt");
         let key = aes::Key::new_from_string("YELLOW SUBMARINE");
 
-        let deciphered = aes::decrypt_aes_128(&cipher, &key, &BlockCipherMode::ECB);
+        let options = &AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::PKCS7);
+
+        let deciphered = aes::decrypt_aes_128(&cipher, &key, options).unwrap();
 
         assert!(deciphered.starts_with("I'm back and I'm ringin' the bell".as_bytes()));
     }
@@ -21,16 +21,19 @@ pub fn encrypt_under_random_key<'a>(content: &[u8], iv: &'a aes::Iv) -> (Vec<u8>
         &padded_content,
         &key,
         &AESEncryptionOptions::new(&block_cipher_mode, &Padding::PKCS7),
-    );
+    ).unwrap();
 
     (cipher, block_cipher_mode)
 }
 
 pub fn is_admin(cipher: &[u8], key: &aes::Key, iv: &aes::Iv) -> bool {
-    let text = aes::decrypt_aes_128(&cipher, &key, &BlockCipherMode::CBC(iv));
-    let as_string = String::from_utf8_lossy(&text);
+    let mode = &BlockCipherMode::CBC(iv);
+    let options = &AESEncryptionOptions::new(mode, &Padding::None);
 
-    as_string.contains(";admin=true;")
+    match aes::decrypt_aes_128(&cipher, &key, options) {
+        Ok(text) => String::from_utf8_lossy(&text).contains(";admin=true;"),
+        Err(_) => false,
+    }
 }
 
 #[cfg(test)]
@@ -53,11 +56,11 @@ mod tests {
 
     #[test]
     fn challenge9_full_block_padding_needed() {
-        let block_size = 16u8;
+        let block_size = 16;
         let message = "YELLOW SUBMARINE".as_bytes();
         let expected_result = &[
             &message[..],
-            &vec![block_size; block_size as usize][..]
+            &vec![block_size as u8; block_size][..]
         ].concat();
 
         let actual_result = &aes::pkcs7_pad(&message, block_size);
@@ -72,7 +75,11 @@ mod tests {
         let iv = &aes::Iv::empty();
         let mode = &BlockCipherMode::CBC(iv);
 
-        let deciphered = aes::decrypt_aes_128(cbc_cipher, key, mode);
+        let deciphered = aes::decrypt_aes_128(
+            cbc_cipher,
+            key,
+            &AESEncryptionOptions::new(mode, &Padding::PKCS7),
+        ).unwrap();
 
         assert!(deciphered.starts_with("I'm back and I'm ringin' the bell".as_bytes()));
     }
@@ -98,7 +105,6 @@ mod tests {
     }
 
     #[test]
-    #[ignore] // takes up to 2 minutes to run.
     fn challenge12() {
         let unknown_string = file::read_base64_file_bytes("./resources/12.txt");
         let key = aes::generate::generate_aes_128_key();
@@ -114,8 +120,11 @@ mod tests {
         let key = aes::generate::generate_aes_128_key();
 
         let cipher = attack::ecb::ecb_cut_and_paste(&key);
-        let decrypted_encoded_profile = aes::decrypt_aes_128(&cipher, &key,
-                                                             &BlockCipherMode::ECB);
+        let decrypted_encoded_profile = aes::decrypt_aes_128(
+            &cipher,
+            &key,
+            &AESEncryptionOptions::new(&BlockCipherMode::ECB, &Padding::PKCS7),
+        ).unwrap();
 
         // it is normal that the encoded profile is not a properly encoded utf-8 string
         let result = String::from_utf8_lossy(&decrypted_encoded_profile);
@@ -123,7 +132,6 @@ mod tests {
     }
 
     #[test]
-    #[ignore] // takes up to 2 minutes to run.
     fn challenge14() {
         let random_prefix = aes::generate::generate_bytes_for_length(rand::thread_rng().gen_range
         (5, 64));
@@ -165,7 +173,7 @@ mod tests {
         let block_size = 16;
         let invalid_padding_length = [
             "ICE ICE BABY!!!".as_bytes(),
-            &[block_size + 1][..]
+            &[block_size as u8 + 1][..]
         ].concat();
 
         assert_eq!(
@@ -204,7 +212,7 @@ mod tests {
         let mode = &BlockCipherMode::CBC(iv);
         let oracle = attack::cbc::build_cbc_bitflip_oracle(key, mode);
 
-        let cipher = attack::cbc::cbc_bitflip(&oracle, key, iv);
+        let cipher = attack::cbc::cbc_bitflip(&oracle, &aes::AesNi::new(key), &iv.to_bytes());
         let is_admin = is_admin(&cipher, key, iv);
 
         assert!(is_admin);
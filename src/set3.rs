@@ -14,7 +14,7 @@ pub fn generate_cbc_padding_oracle_cipher<'a>(
         &selected_line,
         key,
         &AESEncryptionOptions::new(&BlockCipherMode::CBC(&iv), &Padding::PKCS7),
-    );
+    ).unwrap();
 
     CipherWithIvAndKey { cipher: cipher.to_vec(), iv, key }
 }
@@ -51,8 +51,13 @@ mod tests {
             &cipher_with_iv_and_key.iv,
         );
 
-        let deciphered = attack::cbc::cbc_padding_attack(&cipher_with_iv_and_key.cipher, oracle);
-        let deciphered_without_padding = remove_pkcs7_padding(&deciphered);
+        let deciphered = attack::cbc::cbc_padding_attack(
+            &cipher_with_iv_and_key.cipher,
+            16,
+            &Padding::PKCS7,
+            oracle,
+        );
+        let deciphered_without_padding = remove_pkcs7_padding(&deciphered, 16).unwrap();
 
         assert_eq!(calculate_human_resemblance_score(&deciphered_without_padding), 1f32);
     }
@@ -65,7 +70,9 @@ mod tests {
         let key = Key::new_from_string("YELLOW SUBMARINE");
         let mode = BlockCipherMode::CTR(&[0u8; 8]);
 
-        let deciphered = encrypt_aes_128(&input, &key, &AESEncryptionOptions::new(&mode, &Padding::None));
+        let options = &AESEncryptionOptions::new(&mode, &Padding::None);
+
+        let deciphered = encrypt_aes_128(&input, &key, options).unwrap();
 
         assert_eq!(calculate_human_resemblance_score(&deciphered), 1f32);
     }
@@ -80,7 +87,7 @@ mod tests {
         let options = AESEncryptionOptions::new(&mode, &Padding::None);
 
         let mut ciphers = lines.iter()
-            .map(|line| encrypt_aes_128(&line, &key, &options))
+            .map(|line| encrypt_aes_128(&line, &key, &options).unwrap())
             .collect();
 
         let deciphered = break_fixed_nonce_ctr_mode_using_substitutions(&ciphers);
@@ -102,7 +109,7 @@ mod tests {
         let options = AESEncryptionOptions::new(&mode, &Padding::None);
 
         /*let mut ciphers = lines.iter()
-            .map(|line| encrypt_aes_128(&line, &key, &options))
+            .map(|line| encrypt_aes_128(&line, &key, &options).unwrap())
             .collect();*/
 
         // TODO(nich): Break multiple repeating-key xor